@@ -0,0 +1,292 @@
+//! BNO08x sensor hub driven over SHTP, instead of the UART-RVC mode.
+//!
+//! The hub is told which reports to send with Set Feature commands (see
+//! `BNO08X_FEATURES`), and has to be told again after every reset. Input
+//! reports are collected per packet: a packet with a rotation vector becomes
+//! an `ImuData` sample, with yaw, pitch and roll converted like the RVC mode
+//! does, and the latest accelerometer, gyroscope, linear acceleration,
+//! stability and tap reports as `ImuExtras`.
+
+use embassy_time::Duration;
+
+use crate::{
+    imu::{Bno080RawRvcData, ImuExtras, ImuLinkStats, ImuStability},
+    shtp::{
+        ShtpCommand, ShtpHeader, ShtpWriter, SHTP_CHANNEL_CONTROL, SHTP_CHANNEL_EXECUTABLE,
+        SHTP_CHANNEL_REPORTS, SHTP_CHANNEL_WAKE_REPORTS, SHTP_HEADER_SIZE,
+    },
+};
+
+pub const BNO08X_REPORT_ACCELEROMETER: u8 = 0x01;
+pub const BNO08X_REPORT_GYROSCOPE: u8 = 0x02;
+pub const BNO08X_REPORT_LINEAR_ACCELERATION: u8 = 0x04;
+pub const BNO08X_REPORT_ROTATION_VECTOR: u8 = 0x05;
+pub const BNO08X_REPORT_GAME_ROTATION_VECTOR: u8 = 0x08;
+pub const BNO08X_REPORT_TAP_DETECTOR: u8 = 0x10;
+pub const BNO08X_REPORT_STABILITY_CLASSIFIER: u8 = 0x13;
+
+const REPORT_TIMESTAMP_REBASE: u8 = 0xFA;
+const REPORT_BASE_TIMESTAMP: u8 = 0xFB;
+const REPORT_SET_FEATURE: u8 = 0xFD;
+const REPORT_GET_FEATURE_RESPONSE: u8 = 0xFC;
+const REPORT_COMMAND_REQUEST: u8 = 0xF2;
+const REPORT_COMMAND_RESPONSE: u8 = 0xF1;
+const REPORT_PRODUCT_ID_RESPONSE: u8 = 0xF8;
+
+const COMMAND_SAVE_DCD: u8 = 0x06;
+/// Sent by the hub on its own after a reset
+const COMMAND_INITIALIZE: u8 = 0x84;
+
+/// First byte on the executable channel after a reset.
+const EXECUTABLE_RESET_COMPLETE: u8 = 0x01;
+
+/// Reports enabled at startup and after each reset, with their intervals.
+/// The game rotation vector leaves out the magnetometer, which the motors
+/// would disturb.
+pub const BNO08X_FEATURES: [(u8, Duration); 6] = [
+    (
+        BNO08X_REPORT_GAME_ROTATION_VECTOR,
+        Duration::from_millis(10),
+    ),
+    (BNO08X_REPORT_ACCELEROMETER, Duration::from_millis(10)),
+    (BNO08X_REPORT_GYROSCOPE, Duration::from_millis(10)),
+    (BNO08X_REPORT_LINEAR_ACCELERATION, Duration::from_millis(10)),
+    (
+        BNO08X_REPORT_STABILITY_CLASSIFIER,
+        Duration::from_millis(100),
+    ),
+    (BNO08X_REPORT_TAP_DETECTOR, Duration::from_millis(10)),
+];
+
+/// Length of an input report, report id included, `None` when unknown (the
+/// rest of the packet cannot be parsed).
+fn report_length(report: u8) -> Option<usize> {
+    match report {
+        REPORT_TIMESTAMP_REBASE | REPORT_BASE_TIMESTAMP => Some(5),
+        // Accelerometer, gyroscope, magnetometer, linear acceleration, gravity
+        0x01..=0x04 | 0x06 => Some(10),
+        BNO08X_REPORT_ROTATION_VECTOR | 0x09 => Some(14),
+        0x07 => Some(16),
+        BNO08X_REPORT_GAME_ROTATION_VECTOR => Some(12),
+        BNO08X_REPORT_TAP_DETECTOR => Some(5),
+        0x11 => Some(12),
+        BNO08X_REPORT_STABILITY_CLASSIFIER => Some(6),
+        _ => None,
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Bno08xEvent {
+    /// A packet with a rotation vector
+    Sample(Bno080RawRvcData, ImuExtras),
+    /// The hub restarted and lost its feature settings
+    Reset,
+    /// Answer to a calibration save, `true` when it worked
+    CalibrationSaved(bool),
+}
+
+/// Gravity, in m/s^2 scaled by 10000.
+const GRAVITY_X10000: i64 = 98067;
+
+fn saturate(value: i64) -> i16 {
+    value.clamp(i16::MIN as i64, i16::MAX as i64) as i16
+}
+
+/// Q8 m/s^2 to mg.
+fn accel_mg(value: i16) -> i16 {
+    saturate(value as i64 * 10_000_000 / (GRAVITY_X10000 * 256))
+}
+
+/// Q9 rad/s to centidegrees per second.
+fn rate_cdeg(value: i16) -> i16 {
+    saturate(value as i64 * 57296 / 5120)
+}
+
+fn q14(value: i16) -> f32 {
+    value as f32 / 16384.0
+}
+
+fn cdeg(radians: f32) -> i16 {
+    saturate(libm::roundf(radians * (18000.0 / core::f32::consts::PI)) as i64)
+}
+
+/// Yaw, pitch and roll (in centidegrees, like the RVC mode) of a unit
+/// quaternion, rotating in z, y, x order.
+pub fn quaternion_to_ypr(i: i16, j: i16, k: i16, real: i16) -> (i16, i16, i16) {
+    let (x, y, z, w) = (q14(i), q14(j), q14(k), q14(real));
+    let yaw = libm::atan2f(2.0 * (w * z + x * y), 1.0 - 2.0 * (y * y + z * z));
+    let pitch = libm::asinf((2.0 * (w * y - z * x)).clamp(-1.0, 1.0));
+    let roll = libm::atan2f(2.0 * (w * x + y * z), 1.0 - 2.0 * (x * x + y * y));
+    (cdeg(yaw), cdeg(pitch), cdeg(roll))
+}
+
+fn value(bytes: &[u8], offset: usize) -> i16 {
+    i16::from_le_bytes([bytes[offset], bytes[offset + 1]])
+}
+
+pub struct Bno08x {
+    writer: ShtpWriter,
+    command_sequence: u8,
+    stats: ImuLinkStats,
+    /// Sequence number of the last reports packet
+    last_sequence: Option<u8>,
+    /// Latest accelerometer reading (side, forward, vertical)
+    accel: [i16; 3],
+    extras: ImuExtras,
+}
+
+impl Bno08x {
+    pub const fn new() -> Self {
+        Self {
+            writer: ShtpWriter::new(),
+            command_sequence: 0,
+            stats: ImuLinkStats::new(),
+            last_sequence: None,
+            accel: [0; 3],
+            extras: ImuExtras::new(),
+        }
+    }
+
+    /// Packets received (good), and missing from the reports sequence
+    /// (lost). Transport errors are counted by the transport.
+    pub fn stats(&self) -> ImuLinkStats {
+        self.stats
+    }
+
+    /// Enables a report every `interval` (disables it with a zero interval).
+    pub fn set_feature(&mut self, report: u8, interval: Duration) -> ShtpCommand {
+        let mut payload = [0u8; 17];
+        payload[0] = REPORT_SET_FEATURE;
+        payload[1] = report;
+        let interval = interval.as_micros().min(u32::MAX as u64) as u32;
+        payload[5..9].copy_from_slice(&interval.to_le_bytes());
+        self.writer.packet(SHTP_CHANNEL_CONTROL, &payload)
+    }
+
+    /// Saves the dynamic calibration to the hub flash, answered by
+    /// `Bno08xEvent::CalibrationSaved`.
+    pub fn save_calibration(&mut self) -> ShtpCommand {
+        let mut payload = [0u8; 12];
+        payload[0] = REPORT_COMMAND_REQUEST;
+        payload[1] = self.command_sequence;
+        payload[2] = COMMAND_SAVE_DCD;
+        self.command_sequence = self.command_sequence.wrapping_add(1);
+        self.writer.packet(SHTP_CHANNEL_CONTROL, &payload)
+    }
+
+    /// Handles a packet, header included.
+    pub fn process(&mut self, packet: &[u8]) -> Option<Bno08xEvent> {
+        let header = ShtpHeader::parse(packet)?;
+        if header.length < SHTP_HEADER_SIZE {
+            return None;
+        }
+        let payload = &packet[SHTP_HEADER_SIZE..header.length.min(packet.len())];
+        self.stats.good += 1;
+        match header.channel {
+            SHTP_CHANNEL_EXECUTABLE => match payload.first() {
+                Some(&EXECUTABLE_RESET_COMPLETE) => {
+                    // Sequence numbers restart too
+                    self.last_sequence = None;
+                    Some(Bno08xEvent::Reset)
+                }
+                _ => None,
+            },
+            SHTP_CHANNEL_CONTROL => self.process_control(payload),
+            SHTP_CHANNEL_REPORTS | SHTP_CHANNEL_WAKE_REPORTS => {
+                if header.channel == SHTP_CHANNEL_REPORTS {
+                    self.count_lost(header.sequence);
+                }
+                self.process_reports(payload, header.sequence)
+            }
+            _ => None,
+        }
+    }
+
+    fn count_lost(&mut self, sequence: u8) {
+        if let Some(last) = self.last_sequence {
+            self.stats.lost += sequence.wrapping_sub(last).wrapping_sub(1) as u32;
+        }
+        self.last_sequence = Some(sequence);
+    }
+
+    fn process_control(&mut self, payload: &[u8]) -> Option<Bno08xEvent> {
+        match *payload.first()? {
+            REPORT_COMMAND_RESPONSE if payload.len() >= 6 => match payload[2] {
+                COMMAND_SAVE_DCD => Some(Bno08xEvent::CalibrationSaved(payload[5] == 0)),
+                COMMAND_INITIALIZE => {
+                    log::info!("BNO08x: initialized");
+                    None
+                }
+                command => {
+                    log::info!("BNO08x: response to command {}", command);
+                    None
+                }
+            },
+            REPORT_GET_FEATURE_RESPONSE | REPORT_PRODUCT_ID_RESPONSE => None,
+            report => {
+                log::info!("BNO08x: unknown control report {}", report);
+                None
+            }
+        }
+    }
+
+    fn process_reports(&mut self, payload: &[u8], sequence: u8) -> Option<Bno08xEvent> {
+        let mut ypr = None;
+        let mut rest = payload;
+        while let Some(&report) = rest.first() {
+            let length = match report_length(report) {
+                Some(length) if length <= rest.len() => length,
+                _ => {
+                    log::info!("BNO08x: cannot parse report {}", report);
+                    break;
+                }
+            };
+            let bytes = &rest[..length];
+            match report {
+                BNO08X_REPORT_ROTATION_VECTOR | BNO08X_REPORT_GAME_ROTATION_VECTOR => {
+                    ypr = Some(quaternion_to_ypr(
+                        value(bytes, 4),
+                        value(bytes, 6),
+                        value(bytes, 8),
+                        value(bytes, 10),
+                    ));
+                }
+                BNO08X_REPORT_ACCELEROMETER => {
+                    for (axis, accel) in self.accel.iter_mut().enumerate() {
+                        *accel = accel_mg(value(bytes, 4 + axis * 2));
+                    }
+                }
+                BNO08X_REPORT_GYROSCOPE => {
+                    self.extras.yaw_rate = rate_cdeg(value(bytes, 8));
+                }
+                BNO08X_REPORT_LINEAR_ACCELERATION => {
+                    self.extras.linear_side = accel_mg(value(bytes, 4));
+                    self.extras.linear_forward = accel_mg(value(bytes, 6));
+                    self.extras.linear_vertical = accel_mg(value(bytes, 8));
+                }
+                BNO08X_REPORT_STABILITY_CLASSIFIER => {
+                    self.extras.stability = ImuStability::from_report(bytes[4]);
+                }
+                BNO08X_REPORT_TAP_DETECTOR => {
+                    self.extras.taps = self.extras.taps.wrapping_add(1);
+                }
+                _ => {}
+            }
+            rest = &rest[length..];
+        }
+
+        ypr.map(|(yaw, pitch, roll)| {
+            let [side, forward, vertical] = self.accel;
+            Bno08xEvent::Sample(
+                Bno080RawRvcData::new(yaw, pitch, roll, side, forward, vertical, sequence),
+                self.extras,
+            )
+        })
+    }
+}
+
+impl Default for Bno08x {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -1,4 +1,6 @@
-use embassy_sync::{blocking_mutex::raw::CriticalSectionRawMutex, signal::Signal};
+//! User commands, from the buttons or from the serial console.
+
+use embassy_time::Instant;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Cmd {
@@ -10,10 +12,7 @@ pub enum Cmd {
     Minus,
 }
 
-pub static CMD: Signal<CriticalSectionRawMutex, Cmd> = Signal::new();
-
 impl Cmd {
-    #[allow(unused)]
     pub fn from_serial_char(code: char) -> Option<Self> {
         match code {
             'Z' | 'z' => Some(Self::Ok),
@@ -49,3 +48,39 @@ impl Cmd {
         }
     }
 }
+
+/// Where a command came from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CmdSource {
+    Button,
+    Usb,
+    Network,
+}
+
+impl CmdSource {
+    pub fn name(&self) -> &'static str {
+        match self {
+            CmdSource::Button => "BUTTON",
+            CmdSource::Usb => "USB",
+            CmdSource::Network => "NETWORK",
+        }
+    }
+}
+
+/// A queued command, with when and where it was raised.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CmdEvent {
+    pub cmd: Cmd,
+    pub source: CmdSource,
+    pub timestamp: Instant,
+}
+
+impl CmdEvent {
+    pub fn new(cmd: Cmd, source: CmdSource, timestamp: Instant) -> Self {
+        Self {
+            cmd,
+            source,
+            timestamp,
+        }
+    }
+}
@@ -0,0 +1,572 @@
+use embassy_time::Duration;
+
+use arrayvec::ArrayVec;
+
+use crate::{
+    imu::StillnessConfig,
+    lasers::{Gp2Y0E02bAcc, Gp2Y0E02bMedian, Gp2Y0E02bShift, LaserSettings},
+    race::{Angle, ANGLE_SCALE},
+    safety::SafetyLimits,
+    vision::LaserSidePosition,
+};
+
+/// How a config value is presented to the user.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ConfigValueKind {
+    Number,
+    /// 0 is NO, 1 is YES
+    Bool,
+    /// Labels for the values from `min` to `max`
+    Enum(&'static [&'static str]),
+}
+
+/// Everything a front-end needs to know to show and edit a config entry.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct ConfigEntrySchema {
+    pub entry: RaceConfigEntry,
+    /// Stable identifier for the serial and network protocols
+    pub key: &'static str,
+    /// Label shown on the LCD
+    pub name: &'static str,
+    pub unit: &'static str,
+    pub min: i16,
+    pub max: i16,
+    pub step: i16,
+    pub default: i16,
+    pub kind: ConfigValueKind,
+}
+
+impl ConfigEntrySchema {
+    pub fn is_bool(&self) -> bool {
+        self.kind == ConfigValueKind::Bool
+    }
+
+    pub fn value_name(&self, value: i16) -> Option<&'static str> {
+        match self.kind {
+            ConfigValueKind::Number => None,
+            ConfigValueKind::Bool => match value {
+                0 => Some("NO"),
+                1 => Some("YES"),
+                _ => None,
+            },
+            ConfigValueKind::Enum(labels) => usize::try_from(value - self.min)
+                .ok()
+                .and_then(|index| labels.get(index).copied()),
+        }
+    }
+}
+
+/// Declares the config entries: each row generates a `RaceConfigEntry`
+/// variant, a `RaceConfig` field, its schema and its accessors.
+macro_rules! race_config_schema {
+    ($(
+        $variant:ident $field:ident $key:literal $name:literal $unit:literal
+        min $min:literal max $max:literal step $step:literal default $default:literal
+        $kind:expr;
+    )*) => {
+        #[derive(Clone, Copy, PartialEq, Eq, Debug)]
+        #[repr(usize)]
+        pub enum RaceConfigEntry {
+            $($variant,)*
+            End,
+        }
+
+        pub const RACE_CONFIG_SCHEMA: [ConfigEntrySchema; RACE_CONFIG_ENTRY_END] = [
+            $(ConfigEntrySchema {
+                entry: RaceConfigEntry::$variant,
+                key: $key,
+                name: $name,
+                unit: $unit,
+                min: $min,
+                max: $max,
+                step: $step,
+                default: $default,
+                kind: $kind,
+            },)*
+        ];
+
+        #[derive(Clone, Copy, PartialEq, Eq)]
+        pub struct RaceConfig {
+            $(pub $field: i16,)*
+        }
+
+        impl RaceConfig {
+            pub const fn init() -> Self {
+                Self {
+                    $($field: $default,)*
+                }
+            }
+
+            pub fn get(&self, entry: RaceConfigEntry) -> i16 {
+                match entry {
+                    $(RaceConfigEntry::$variant => self.$field,)*
+                    RaceConfigEntry::End => 0,
+                }
+            }
+
+            pub fn set(&mut self, entry: RaceConfigEntry, value: i16) {
+                match entry {
+                    $(RaceConfigEntry::$variant => self.$field = value,)*
+                    RaceConfigEntry::End => {}
+                }
+            }
+        }
+    };
+}
+
+use ConfigValueKind::{Bool, Enum, Number};
+
+race_config_schema! {
+    MaxSpeed max_speed "max_speed" "MAX SPEED" "pwr"
+        min 2000 max 10000 step 500 default 4500 Number; // 4650, 3500
+    MinSpeed min_speed "min_speed" "MIN SPEED" "pwr"
+        min 1000 max 9000 step 500 default 4200 Number; // 4500, 3200
+    SteerBias steer_bias "steer_bias" "STEER BIAS" "deg"
+        min -20 max 20 step 1 default -5 Number;
+    SafeAngle safe_angle "safe_angle" "SAFE ANGLE" "deg"
+        min 0 max 35 step 1 default 10 Number;
+    BackSpeed back_speed "back_speed" "BACK SPEED" "pwr"
+        min 2000 max 10000 step 500 default 5000 Number;
+    BackTime back_time "back_time" "BACK TIME" "ms"
+        min 100 max 1000 step 10 default 100 Number;
+    SprintSpeed sprint_speed "sprint_speed" "SPRINT SPEED" "pwr"
+        min 2000 max 10000 step 500 default 9000 Number;
+    SprintTime sprint_time "sprint_time" "SPRINT TIME" "ms"
+        min 0 max 3000 step 10 default 700 Number;
+    AlertDistanceCenter alert_distance_center "alert_distance_center" "ALERT D  0" "mm"
+        min 200 max 500 step 25 default 380 Number;
+    AlertDistanceSide30 alert_distance_side_30 "alert_distance_side_30" "ALERT D 30" "mm"
+        min 100 max 500 step 25 default 190 Number;
+    AlertDistanceSide60 alert_distance_side_60 "alert_distance_side_60" "ALERT D 60" "mm"
+        min 100 max 500 step 25 default 150 Number;
+    BackDistanceCenter back_distance_center "back_distance_center" "BACK D  0" "mm"
+        min 25 max 400 step 25 default 80 Number;
+    BackDistanceSide30 back_distance_side_30 "back_distance_side_30" "BACK D 30" "mm"
+        min 25 max 400 step 25 default 60 Number;
+    BackDistanceSide60 back_distance_side_60 "back_distance_side_60" "BACK D 60" "mm"
+        min 25 max 400 step 25 default 50 Number;
+    SlopeDistanceDelta slope_distance_delta "slope_distance_delta" "SLOPE DELTA" "mm"
+        min 50 max 300 step 10 default 150 Number;
+    ClimbingSpeed climbing_speed "climbing_speed" "CLIMB SPD" "pwr"
+        min 2000 max 10000 step 500 default 9000 Number;
+    ClimbingAngle climbing_angle "climbing_angle" "CLIMB ANG" "deg"
+        min 5 max 25 step 1 default 15 Number;
+    ClimbingIgnore climbing_ignore "climbing_ignore" "CLIMB IGN" "deg"
+        min 0 max 10 step 1 default 3 Number;
+    StillnessDelta stillness_delta "stillness_delta" "STILL DELTA" "mg"
+        min 10 max 1000 step 10 default 150 Number;
+    StillnessTime stillness_time "stillness_time" "STILL TIME" "ms"
+        min 0 max 2000 step 10 default 500 Number;
+    StillFor still_for "still_for" "STILL FOR" "ms"
+        min 10 max 500 step 10 default 50 Number;
+    StillnessWindow stillness_window "stillness_window" "STILL WINDOW" "ms"
+        min 100 max 2000 step 50 default 500 Number;
+    StillnessThreshold stillness_threshold "stillness_threshold" "STILL THRESH" "ms"
+        min 50 max 2000 step 50 default 350 Number;
+    UseStillness use_stillness "use_stillness" "USE STILL" ""
+        min 0 max 1 step 1 default 0 Bool;
+    InversionTime inversion_time "inversion_time" "INV TIME" "ms"
+        min 100 max 1000 step 10 default 750 Number;
+    PostInversionTime post_inversion_time "post_inversion_time" "POST INV TIME" "ms"
+        min 10 max 500 step 10 default 150 Number;
+    ClimbDirection climb_direction "climb_direction" "CLIMB DIR" "deg"
+        min -180 max 180 step 90 default 179 Number;
+    UseClimbDirection use_climb_direction "use_climb_direction" "USE CLIMB DIR" ""
+        min 0 max 1 step 1 default 1 Bool;
+    UseColorInversion use_color_inversion "use_color_inversion" "USE COLOR INV" ""
+        min 0 max 1 step 1 default 0 Bool;
+    MarkerHeading marker_heading "marker_heading" "MARKER DIR" "deg"
+        min -180 max 180 step 15 default 0 Number;
+    UseMarkerAnchor use_marker_anchor "use_marker_anchor" "USE MARKER" ""
+        min 0 max 1 step 1 default 0 Bool;
+    UseDriftCorrection use_drift_correction "use_drift_correction" "USE DRIFT" ""
+        min 0 max 1 step 1 default 1 Bool;
+    LasersTimeout lasers_timeout "lasers_timeout" "LAS TIMEOUT" "ms"
+        min 20 max 1000 step 10 default 100 Number;
+    ImuTimeout imu_timeout "imu_timeout" "IMU TIMEOUT" "ms"
+        min 20 max 1000 step 10 default 100 Number;
+    MotorsTimeout motors_timeout "motors_timeout" "MOT TIMEOUT" "ms"
+        min 20 max 1000 step 10 default 200 Number;
+    LaserShift laser_shift "laser_shift" "LAS SHIFT" ""
+        min 0 max 1 step 1 default 0 Enum(Gp2Y0E02bShift::NAMES);
+    LaserMedian laser_median "laser_median" "LAS MEDIAN" ""
+        min 0 max 3 step 1 default 1 Enum(Gp2Y0E02bMedian::NAMES);
+    LaserAccumulation laser_accumulation "laser_accumulation" "LAS ACCUM" ""
+        min 0 max 3 step 1 default 1 Enum(Gp2Y0E02bAcc::NAMES);
+}
+
+pub const RACE_CONFIG_ENTRY_START: usize = 0;
+pub const RACE_CONFIG_ENTRY_END: usize = RaceConfigEntry::End as usize;
+
+impl From<usize> for RaceConfigEntry {
+    fn from(value: usize) -> RaceConfigEntry {
+        if value < RACE_CONFIG_ENTRY_END {
+            RACE_CONFIG_SCHEMA[value].entry
+        } else {
+            Self::start()
+        }
+    }
+}
+
+impl Into<usize> for RaceConfigEntry {
+    fn into(self) -> usize {
+        self as usize
+    }
+}
+
+impl RaceConfigEntry {
+    pub const fn start() -> Self {
+        RACE_CONFIG_SCHEMA[RACE_CONFIG_ENTRY_START].entry
+    }
+    pub const fn end() -> Self {
+        Self::End
+    }
+
+    /// All the entries, in schema order.
+    pub fn iter() -> impl Iterator<Item = RaceConfigEntry> {
+        RACE_CONFIG_SCHEMA.iter().map(|schema| schema.entry)
+    }
+
+    pub fn from_key(key: &str) -> Option<Self> {
+        RACE_CONFIG_SCHEMA
+            .iter()
+            .find(|schema| schema.key.eq_ignore_ascii_case(key))
+            .map(|schema| schema.entry)
+    }
+
+    pub fn schema(self) -> &'static ConfigEntrySchema {
+        &RACE_CONFIG_SCHEMA[self.index().min(RACE_CONFIG_ENTRY_END - 1)]
+    }
+
+    pub fn index(self) -> usize {
+        self.into()
+    }
+
+    pub fn prev(self) -> Self {
+        let index = if self != Self::start() {
+            self.index()
+        } else {
+            Self::end().index()
+        };
+        (index - 1).into()
+    }
+
+    pub fn next(self) -> Self {
+        let next: Self = (self.index() + 1).into();
+        if next == Self::end() {
+            Self::start()
+        } else {
+            next
+        }
+    }
+
+    pub fn key(self) -> &'static str {
+        self.schema().key
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            RaceConfigEntry::End => "END",
+            _ => self.schema().name,
+        }
+    }
+
+    pub fn unit(self) -> &'static str {
+        self.schema().unit
+    }
+
+    pub fn min(self) -> i16 {
+        self.schema().min
+    }
+
+    pub fn max(self) -> i16 {
+        self.schema().max
+    }
+
+    pub fn step(self) -> i16 {
+        self.schema().step
+    }
+
+    pub fn default_value(self) -> i16 {
+        self.schema().default
+    }
+
+    pub fn value_name(self, value: i16) -> Option<&'static str> {
+        self.schema().value_name(value)
+    }
+}
+
+/// A config value, or a combination of values, the race logic cannot work with.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ConfigViolation {
+    OutOfRange(RaceConfigEntry),
+    /// `turn_speed` needs `min_speed <= max_speed`
+    MinSpeedAboveMaxSpeed,
+    /// `turn_speed` divides by `MAX_STEER - safe_angle`
+    SafeAngleTooWide,
+    /// `climb_power_boost` divides by `climbing_angle - climbing_ignore`
+    ClimbingAngleNotAboveIgnore,
+    /// A back distance must be shorter than the alert distance
+    BackNotBelowAlert(LaserSidePosition),
+    /// Stillness could never be detected
+    StillnessThresholdAboveWindow,
+}
+
+pub const MAX_CONFIG_VIOLATIONS: usize = RACE_CONFIG_ENTRY_END + 7;
+pub type ConfigViolations = ArrayVec<ConfigViolation, MAX_CONFIG_VIOLATIONS>;
+
+impl ConfigViolation {
+    /// Short description, fits an LCD row.
+    pub fn description(&self) -> &'static str {
+        match self {
+            ConfigViolation::OutOfRange(_) => "OUT OF RANGE",
+            ConfigViolation::MinSpeedAboveMaxSpeed => "MIN > MAX SPD",
+            ConfigViolation::SafeAngleTooWide => "SAFE ANG WIDE",
+            ConfigViolation::ClimbingAngleNotAboveIgnore => "CLIMB ANG<IGN",
+            ConfigViolation::BackNotBelowAlert(LaserSidePosition::Center) => "BACK>ALERT  0",
+            ConfigViolation::BackNotBelowAlert(LaserSidePosition::Side30) => "BACK>ALERT 30",
+            ConfigViolation::BackNotBelowAlert(LaserSidePosition::Side60) => "BACK>ALERT 60",
+            ConfigViolation::StillnessThresholdAboveWindow => "STILL THR>WIN",
+        }
+    }
+
+    /// The entries that have to change to fix the violation.
+    pub fn entries(&self) -> ArrayVec<RaceConfigEntry, 2> {
+        let entries: &[RaceConfigEntry] = match self {
+            ConfigViolation::OutOfRange(entry) => return [*entry].into_iter().collect(),
+            ConfigViolation::MinSpeedAboveMaxSpeed => {
+                &[RaceConfigEntry::MinSpeed, RaceConfigEntry::MaxSpeed]
+            }
+            ConfigViolation::SafeAngleTooWide => &[RaceConfigEntry::SafeAngle],
+            ConfigViolation::ClimbingAngleNotAboveIgnore => &[
+                RaceConfigEntry::ClimbingAngle,
+                RaceConfigEntry::ClimbingIgnore,
+            ],
+            ConfigViolation::BackNotBelowAlert(LaserSidePosition::Center) => &[
+                RaceConfigEntry::BackDistanceCenter,
+                RaceConfigEntry::AlertDistanceCenter,
+            ],
+            ConfigViolation::BackNotBelowAlert(LaserSidePosition::Side30) => &[
+                RaceConfigEntry::BackDistanceSide30,
+                RaceConfigEntry::AlertDistanceSide30,
+            ],
+            ConfigViolation::BackNotBelowAlert(LaserSidePosition::Side60) => &[
+                RaceConfigEntry::BackDistanceSide60,
+                RaceConfigEntry::AlertDistanceSide60,
+            ],
+            ConfigViolation::StillnessThresholdAboveWindow => &[
+                RaceConfigEntry::StillnessThreshold,
+                RaceConfigEntry::StillnessWindow,
+            ],
+        };
+        entries.iter().copied().collect()
+    }
+
+    pub fn involves(&self, entry: RaceConfigEntry) -> bool {
+        self.entries().contains(&entry)
+    }
+}
+
+impl Default for RaceConfig {
+    fn default() -> Self {
+        Self::init()
+    }
+}
+
+impl RaceConfig {
+    /// Every entry schema with its current value, for front-ends that list
+    /// the whole config.
+    pub fn values(&self) -> impl Iterator<Item = (&'static ConfigEntrySchema, i16)> + '_ {
+        RACE_CONFIG_SCHEMA
+            .iter()
+            .map(move |schema| (schema, self.get(schema.entry)))
+    }
+
+    /// Checks every entry against its schema range, and the cross-field
+    /// constraints the race logic relies on.
+    pub fn validate(&self) -> ConfigViolations {
+        let mut violations = ConfigViolations::new();
+        for (schema, value) in self.values() {
+            if value < schema.min || value > schema.max {
+                violations.push(ConfigViolation::OutOfRange(schema.entry));
+            }
+        }
+        if self.min_speed > self.max_speed {
+            violations.push(ConfigViolation::MinSpeedAboveMaxSpeed);
+        }
+        if self.safe_angle as i32 >= Angle::MAX_STEER.value() {
+            violations.push(ConfigViolation::SafeAngleTooWide);
+        }
+        if self.climbing_angle <= self.climbing_ignore {
+            violations.push(ConfigViolation::ClimbingAngleNotAboveIgnore);
+        }
+        for position in [
+            LaserSidePosition::Center,
+            LaserSidePosition::Side30,
+            LaserSidePosition::Side60,
+        ] {
+            if self.back_distance(position) >= self.alert_distance(position) {
+                violations.push(ConfigViolation::BackNotBelowAlert(position));
+            }
+        }
+        if self.stillness_threshold > self.stillness_window {
+            violations.push(ConfigViolation::StillnessThresholdAboveWindow);
+        }
+        violations
+    }
+
+    pub fn is_valid(&self) -> bool {
+        self.validate().is_empty()
+    }
+
+    pub fn alert_distance(&self, position: LaserSidePosition) -> u16 {
+        match position {
+            LaserSidePosition::Center => self.alert_distance_center as u16,
+            LaserSidePosition::Side30 => self.alert_distance_side_30 as u16,
+            LaserSidePosition::Side60 => self.alert_distance_side_60 as u16,
+        }
+    }
+
+    pub fn back_distance(&self, position: LaserSidePosition) -> u16 {
+        match position {
+            LaserSidePosition::Center => self.back_distance_center as u16,
+            LaserSidePosition::Side30 => self.back_distance_side_30 as u16,
+            LaserSidePosition::Side60 => self.back_distance_side_60 as u16,
+        }
+    }
+
+    pub fn climb_power_boost(&self, pitch: Angle) -> i16 {
+        if pitch <= Angle::ZERO {
+            0
+        } else {
+            let boost_range = (self.climbing_speed - self.max_speed) as i32;
+            let pitch_range = (self.climbing_angle - self.climbing_ignore) as i32 * ANGLE_SCALE;
+            let pitch_delta = (pitch.cdeg() - self.climbing_ignore as i32 * ANGLE_SCALE)
+                .min(pitch_range)
+                .max(0);
+            let boost = pitch_delta * boost_range / pitch_range;
+            boost.min(boost_range) as i16
+        }
+    }
+
+    pub fn turn_speed(&self, steer: Angle) -> i16 {
+        let safe_angle = self.safe_angle as i32 * ANGLE_SCALE;
+        let steer = steer
+            .cdeg()
+            .max(Angle::MIN_STEER.cdeg())
+            .min(Angle::MAX_STEER.cdeg());
+        let speed_range = (self.max_speed - self.min_speed) as i32;
+        let steer_range = Angle::MAX_STEER.cdeg() - safe_angle;
+        let steer_delta = if steer < -safe_angle {
+            -(steer + safe_angle)
+        } else if steer > safe_angle {
+            steer - safe_angle
+        } else {
+            0
+        };
+        let speed_delta = steer_delta * speed_range / steer_range;
+        let speed_delta = speed_delta as i16;
+        self.max_speed - speed_delta
+    }
+
+    pub fn detect_climb(&self, pitch: Angle) -> bool {
+        let climbing_threshold = Angle::from(self.climbing_angle as i32).scale(2, 3);
+        pitch >= climbing_threshold
+    }
+
+    pub fn detect_downhill(&self, pitch: Angle) -> bool {
+        let climbing_threshold = Angle::from(self.climbing_angle as i32).scale(2, 3);
+        pitch <= -climbing_threshold
+    }
+
+    pub fn detect_stillness(&self, stillness_time: Option<Duration>) -> bool {
+        stillness_time
+            .map(|time| time >= Duration::from_millis(self.stillness_time as u64))
+            .unwrap_or(false)
+    }
+
+    pub fn still_for(&self) -> Duration {
+        Duration::from_millis(self.still_for as u64)
+    }
+
+    pub fn stillness(&self) -> StillnessConfig {
+        StillnessConfig {
+            delta: self.stillness_delta,
+            still_for: self.still_for(),
+            window: Duration::from_millis(self.stillness_window as u64),
+            threshold: Duration::from_millis(self.stillness_threshold as u64),
+        }
+    }
+
+    pub fn safety_limits(&self) -> SafetyLimits {
+        SafetyLimits {
+            lasers: Duration::from_millis(self.lasers_timeout as u64),
+            imu: Duration::from_millis(self.imu_timeout as u64),
+            motors: Duration::from_millis(self.motors_timeout as u64),
+        }
+    }
+
+    pub fn laser_settings(&self) -> LaserSettings {
+        LaserSettings {
+            shift: Gp2Y0E02bShift::from_index(self.laser_shift),
+            median: Gp2Y0E02bMedian::from_index(self.laser_median),
+            accumulation: Gp2Y0E02bAcc::from_index(self.laser_accumulation),
+        }
+    }
+
+    /// Servo offset (in PWM duty units) for a logical steer angle:
+    /// `steer_bias` trims the center.
+    pub fn servo_steer(&self, steer: Angle) -> i16 {
+        (steer + Angle::from(self.steer_bias as i32)).servo_units()
+    }
+
+    pub fn climb_direction(&self) -> Angle {
+        (self.climb_direction as i32).into()
+    }
+
+    pub fn use_climb_direction(&self) -> bool {
+        self.use_climb_direction != 0
+    }
+
+    pub fn use_stillness(&self) -> bool {
+        self.use_stillness != 0
+    }
+
+    pub fn use_color_inversion(&self) -> bool {
+        self.use_color_inversion != 0
+    }
+
+    /// Track heading when crossing a color marker in the race direction.
+    pub fn marker_heading(&self) -> Angle {
+        (self.marker_heading as i32).into()
+    }
+
+    pub fn use_marker_anchor(&self) -> bool {
+        self.use_marker_anchor != 0
+    }
+
+    pub fn use_drift_correction(&self) -> bool {
+        self.use_drift_correction != 0
+    }
+
+    pub fn post_inversion_time(&self) -> Duration {
+        Duration::from_millis(self.post_inversion_time as u64)
+    }
+
+    #[allow(unused)]
+    pub fn reset(&mut self, entry: RaceConfigEntry) {
+        self.set(entry, entry.default_value());
+    }
+
+    pub fn inc(&mut self, entry: RaceConfigEntry) -> i16 {
+        let value = (self.get(entry) + entry.step()).min(entry.max());
+        self.set(entry, value);
+        value
+    }
+
+    pub fn dec(&mut self, entry: RaceConfigEntry) -> i16 {
+        let value = (self.get(entry) - entry.step()).max(entry.min());
+        self.set(entry, value);
+        value
+    }
+}
@@ -0,0 +1,180 @@
+//! Serial console protocol.
+//!
+//! At the start of a line, a key that maps to a `Cmd` (see
+//! `Cmd::from_serial_char`) is handled immediately, like a button press.
+//! Anything else is collected up to the end of the line and parsed as a
+//! `ConsoleCommand`:
+//!
+//! ```text
+//! config get <key>
+//! config set <key> <value>
+//! config dump
+//! config save
+//! race start
+//! race stop
+//! trace dump
+//! reboot bootsel
+//! ```
+//!
+//! Config keys are the `key` column of `RACE_CONFIG_SCHEMA`; values are
+//! numbers or, for bool and enum entries, one of their value names.
+
+use arrayvec::ArrayString;
+
+use crate::cmd::Cmd;
+use crate::configuration::RaceConfigEntry;
+
+pub const CONSOLE_LINE_SIZE: usize = 64;
+
+pub type ConsoleLine = ArrayString<CONSOLE_LINE_SIZE>;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ConsoleCommand {
+    ConfigGet(RaceConfigEntry),
+    ConfigSet(RaceConfigEntry, i16),
+    ConfigDump,
+    ConfigSave,
+    RaceStart,
+    RaceStop,
+    TraceDump,
+    RebootBootsel,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ConsoleError {
+    UnknownCommand,
+    UnknownKey,
+    MissingArgument,
+    TooManyArguments,
+    BadValue,
+    OutOfRange(RaceConfigEntry),
+    LineTooLong,
+}
+
+impl ConsoleError {
+    pub fn description(&self) -> &'static str {
+        match self {
+            ConsoleError::UnknownCommand => "unknown command",
+            ConsoleError::UnknownKey => "unknown config key",
+            ConsoleError::MissingArgument => "missing argument",
+            ConsoleError::TooManyArguments => "too many arguments",
+            ConsoleError::BadValue => "bad value",
+            ConsoleError::OutOfRange(_) => "value out of range",
+            ConsoleError::LineTooLong => "line too long",
+        }
+    }
+}
+
+fn parse_value(entry: RaceConfigEntry, text: &str) -> Result<i16, ConsoleError> {
+    let schema = entry.schema();
+    let value = match text.parse::<i16>() {
+        Ok(value) => value,
+        Err(_) => (schema.min..=schema.max)
+            .find(|value| {
+                schema
+                    .value_name(*value)
+                    .map(|name| name.eq_ignore_ascii_case(text))
+                    .unwrap_or(false)
+            })
+            .ok_or(ConsoleError::BadValue)?,
+    };
+    if value < schema.min || value > schema.max {
+        Err(ConsoleError::OutOfRange(entry))
+    } else {
+        Ok(value)
+    }
+}
+
+impl ConsoleCommand {
+    pub fn parse(line: &str) -> Result<Self, ConsoleError> {
+        let mut words = line.split_ascii_whitespace();
+        let mut next = || words.next().ok_or(ConsoleError::MissingArgument);
+        let command = match (next()?, next()?) {
+            ("config", "get") => ConsoleCommand::ConfigGet(
+                RaceConfigEntry::from_key(next()?).ok_or(ConsoleError::UnknownKey)?,
+            ),
+            ("config", "set") => {
+                let entry = RaceConfigEntry::from_key(next()?).ok_or(ConsoleError::UnknownKey)?;
+                ConsoleCommand::ConfigSet(entry, parse_value(entry, next()?)?)
+            }
+            ("config", "dump") => ConsoleCommand::ConfigDump,
+            ("config", "save") => ConsoleCommand::ConfigSave,
+            ("race", "start") => ConsoleCommand::RaceStart,
+            ("race", "stop") => ConsoleCommand::RaceStop,
+            ("trace", "dump") => ConsoleCommand::TraceDump,
+            ("reboot", "bootsel") => ConsoleCommand::RebootBootsel,
+            _ => return Err(ConsoleError::UnknownCommand),
+        };
+        match next() {
+            Ok(_) => Err(ConsoleError::TooManyArguments),
+            Err(_) => Ok(command),
+        }
+    }
+}
+
+/// What a console byte stream turns into.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ConsoleInput {
+    Cmd(Cmd),
+    Command(Result<ConsoleCommand, ConsoleError>),
+}
+
+/// Splits the received bytes into single key commands and lines.
+pub struct ConsoleReader {
+    line: ConsoleLine,
+    overflow: bool,
+}
+
+impl ConsoleReader {
+    pub fn new() -> Self {
+        Self {
+            line: ConsoleLine::new(),
+            overflow: false,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.line.is_empty() && !self.overflow
+    }
+
+    pub fn update(&mut self, received: u8) -> Option<ConsoleInput> {
+        match received {
+            b'\r' | b'\n' => {
+                let result = if self.overflow {
+                    Some(Err(ConsoleError::LineTooLong))
+                } else if self.line.trim().is_empty() {
+                    None
+                } else {
+                    Some(ConsoleCommand::parse(&self.line))
+                };
+                self.line.clear();
+                self.overflow = false;
+                result.map(ConsoleInput::Command)
+            }
+            // Backspace and delete
+            0x08 | 0x7f => {
+                self.line.pop();
+                None
+            }
+            _ if !received.is_ascii() || received.is_ascii_control() => None,
+            _ => {
+                let c = received as char;
+                if self.is_empty() {
+                    if let Some(cmd) = Cmd::from_serial_char(c) {
+                        return Some(ConsoleInput::Cmd(cmd));
+                    }
+                }
+                if self.line.try_push(c).is_err() {
+                    self.overflow = true;
+                }
+                None
+            }
+        }
+    }
+}
+
+impl Default for ConsoleReader {
+    fn default() -> Self {
+        Self::new()
+    }
+}
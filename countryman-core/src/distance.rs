@@ -0,0 +1,148 @@
+//! Distance sensors behind the laser multiplexer channels.
+//!
+//! Every sensor model implements `DistanceSensor` on top of an async I2C
+//! bus, reporting millimetres, so `Vision` does not care which one sits on a
+//! channel. `LaserSensor` picks the model of each channel from the beam
+//! table (see `LaserBeam::sensor`).
+
+use embedded_hal_async::i2c::I2c;
+
+use crate::{
+    gp2y0e02b::Gp2Y0E02b,
+    lasers::{LaserChannelState, LaserSettings},
+    vl53l0x::Vl53l0x,
+    vl53l1x::Vl53l1x,
+};
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum DistanceError<E> {
+    I2c(E),
+    /// A reading the sensor cannot have produced with its settings
+    OutOfRange,
+    /// The sensor lost its settings, e.g. after a brown-out reset
+    Reset,
+    /// Another device answers on the channel
+    WrongDevice,
+    /// The sensor did not finish its start up
+    NotBooted,
+}
+
+impl<E> DistanceError<E> {
+    pub fn state(&self) -> LaserChannelState {
+        match self {
+            DistanceError::OutOfRange => LaserChannelState::OutOfRange,
+            DistanceError::Reset => LaserChannelState::Reset,
+            DistanceError::I2c(_) | DistanceError::WrongDevice | DistanceError::NotBooted => {
+                LaserChannelState::I2cError
+            }
+        }
+    }
+
+    pub fn description(&self) -> &'static str {
+        match self {
+            DistanceError::I2c(_) => "bus error",
+            DistanceError::OutOfRange => "out of range",
+            DistanceError::Reset => "settings lost",
+            DistanceError::WrongDevice => "wrong device",
+            DistanceError::NotBooted => "not booted",
+        }
+    }
+}
+
+pub trait DistanceSensor {
+    type Error;
+
+    /// Configures the sensor, also to recover it after a fault.
+    async fn init(&mut self, settings: &LaserSettings) -> Result<(), DistanceError<Self::Error>>;
+
+    /// The distance in mm, `max_distance` when nothing is in sight; `None`
+    /// when there is no new measurement since the last read.
+    async fn read(&mut self) -> Result<Option<u16>, DistanceError<Self::Error>>;
+
+    /// Checks that the sensor is still there with the settings of `init`.
+    async fn health(&mut self) -> Result<(), DistanceError<Self::Error>>;
+
+    /// Longest distance reported, meaning "nothing in sight".
+    fn max_distance(&self) -> u16;
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum DistanceSensorKind {
+    /// Sharp infrared sensor
+    Gp2Y0E02b,
+    /// ST time-of-flight sensor
+    Vl53l1x,
+    /// ST time-of-flight sensor, shorter range
+    Vl53l0x,
+}
+
+impl DistanceSensorKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            DistanceSensorKind::Gp2Y0E02b => "GP2Y0E02B",
+            DistanceSensorKind::Vl53l1x => "VL53L1X",
+            DistanceSensorKind::Vl53l0x => "VL53L0X",
+        }
+    }
+}
+
+/// The sensor on a multiplexer channel, whatever its model.
+pub enum LaserSensor<I2C> {
+    Gp2Y0E02b(Gp2Y0E02b<I2C>),
+    Vl53l1x(Vl53l1x<I2C>),
+    Vl53l0x(Vl53l0x<I2C>),
+}
+
+impl<I2C: I2c> LaserSensor<I2C> {
+    pub fn new(kind: DistanceSensorKind, i2c: I2C) -> Self {
+        match kind {
+            DistanceSensorKind::Gp2Y0E02b => LaserSensor::Gp2Y0E02b(Gp2Y0E02b::new(i2c)),
+            DistanceSensorKind::Vl53l1x => LaserSensor::Vl53l1x(Vl53l1x::new(i2c)),
+            DistanceSensorKind::Vl53l0x => LaserSensor::Vl53l0x(Vl53l0x::new(i2c)),
+        }
+    }
+
+    pub fn kind(&self) -> DistanceSensorKind {
+        match self {
+            LaserSensor::Gp2Y0E02b(_) => DistanceSensorKind::Gp2Y0E02b,
+            LaserSensor::Vl53l1x(_) => DistanceSensorKind::Vl53l1x,
+            LaserSensor::Vl53l0x(_) => DistanceSensorKind::Vl53l0x,
+        }
+    }
+}
+
+impl<I2C: I2c> DistanceSensor for LaserSensor<I2C> {
+    type Error = I2C::Error;
+
+    async fn init(&mut self, settings: &LaserSettings) -> Result<(), DistanceError<Self::Error>> {
+        match self {
+            LaserSensor::Gp2Y0E02b(sensor) => sensor.init(settings).await,
+            LaserSensor::Vl53l1x(sensor) => sensor.init(settings).await,
+            LaserSensor::Vl53l0x(sensor) => sensor.init(settings).await,
+        }
+    }
+
+    async fn read(&mut self) -> Result<Option<u16>, DistanceError<Self::Error>> {
+        match self {
+            LaserSensor::Gp2Y0E02b(sensor) => sensor.read().await,
+            LaserSensor::Vl53l1x(sensor) => sensor.read().await,
+            LaserSensor::Vl53l0x(sensor) => sensor.read().await,
+        }
+    }
+
+    async fn health(&mut self) -> Result<(), DistanceError<Self::Error>> {
+        match self {
+            LaserSensor::Gp2Y0E02b(sensor) => sensor.health().await,
+            LaserSensor::Vl53l1x(sensor) => sensor.health().await,
+            LaserSensor::Vl53l0x(sensor) => sensor.health().await,
+        }
+    }
+
+    fn max_distance(&self) -> u16 {
+        match self {
+            LaserSensor::Gp2Y0E02b(sensor) => sensor.max_distance(),
+            LaserSensor::Vl53l1x(sensor) => sensor.max_distance(),
+            LaserSensor::Vl53l0x(sensor) => sensor.max_distance(),
+        }
+    }
+}
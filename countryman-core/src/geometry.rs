@@ -0,0 +1,192 @@
+//! Laser mounting geometry.
+//!
+//! Each beam is a sensor on a multiplexer channel, mounted on the upper or the
+//! lower tier at some angle. Beams with the same angle make up one laser
+//! position for `Vision`: the lower beam sees the walls, and the upper one
+//! tells a ramp from a wall. A position with a single beam cannot detect
+//! slopes.
+//!
+//! Rebuilding the chassis with more positions, with different angles or with
+//! different sensor models, only needs a different beam table.
+
+use arrayvec::ArrayVec;
+
+use crate::{
+    distance::DistanceSensorKind, lasers::RAW_LASERS_COUNT, race::Angle, vision::LaserSidePosition,
+};
+
+/// Laser positions `Vision` can handle.
+pub const MAX_LASER_POSITIONS: usize = 7;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum LaserTier {
+    Upper,
+    Lower,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct LaserBeam {
+    /// Multiplexer channel, also the index in `RawLaserReadings`
+    pub channel: u8,
+    pub tier: LaserTier,
+    /// Relative to the car heading, positive to the right
+    pub angle: Angle,
+    /// mm from the car axis, positive to the right
+    pub lateral: i16,
+    /// mm ahead of the front bumper, negative when behind it
+    pub forward: i16,
+    pub sensor: DistanceSensorKind,
+}
+
+impl LaserBeam {
+    pub const fn new(channel: u8, tier: LaserTier, angle: Angle) -> Self {
+        Self {
+            channel,
+            tier,
+            angle,
+            lateral: 0,
+            forward: 0,
+            sensor: DistanceSensorKind::Gp2Y0E02b,
+        }
+    }
+
+    pub const fn offset(self, lateral: i16, forward: i16) -> Self {
+        Self {
+            lateral,
+            forward,
+            ..self
+        }
+    }
+
+    pub const fn sensor(self, sensor: DistanceSensorKind) -> Self {
+        Self { sensor, ..self }
+    }
+
+    /// What the beam reads from a flat board `board` mm ahead of the front
+    /// bumper, square to the car heading.
+    pub fn board_distance(self, board: u16) -> u16 {
+        let ahead = (board as i32 - self.forward as i32).max(0) as f32;
+        libm::roundf(ahead / libm::cosf(self.angle.radians())) as u16
+    }
+}
+
+/// What each channel reads from the calibration board `board` mm ahead of
+/// the front bumper: `None` for channels without a beam, or when the beam
+/// would read more than `range` (e.g. the side beams with a far board).
+pub fn calibration_targets(
+    beams: &[LaserBeam],
+    board: u16,
+    range: u16,
+) -> [Option<u16>; RAW_LASERS_COUNT] {
+    let mut targets = [None; RAW_LASERS_COUNT];
+    for beam in beams {
+        let distance = beam.board_distance(board);
+        if let Some(target) = targets.get_mut(beam.channel as usize) {
+            *target = Some(distance).filter(|d| *d < range);
+        }
+    }
+    targets
+}
+
+/// The sensor model on a multiplexer channel, a GP2Y0E02B for channels
+/// without a beam.
+pub fn sensor_kind(beams: &[LaserBeam], channel: u8) -> DistanceSensorKind {
+    beams
+        .iter()
+        .find(|beam| beam.channel == channel)
+        .map(|beam| beam.sensor)
+        .unwrap_or(DistanceSensorKind::Gp2Y0E02b)
+}
+
+/// The current chassis: two tiers at 0 and ±30 degrees, and a single sensor
+/// at ±60 degrees.
+pub const LASER_GEOMETRY: &[LaserBeam] = &[
+    LaserBeam::new(4, LaserTier::Upper, Angle::SLL),
+    LaserBeam::new(1, LaserTier::Upper, Angle::SL),
+    LaserBeam::new(6, LaserTier::Lower, Angle::SL),
+    LaserBeam::new(2, LaserTier::Upper, Angle::SC),
+    LaserBeam::new(7, LaserTier::Lower, Angle::SC),
+    LaserBeam::new(3, LaserTier::Upper, Angle::SR),
+    LaserBeam::new(0, LaserTier::Lower, Angle::SR),
+    LaserBeam::new(5, LaserTier::Upper, Angle::SRR),
+];
+
+/// The beams of one laser position.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct LaserPosition {
+    pub angle: Angle,
+    pub upper: Option<LaserBeam>,
+    pub lower: Option<LaserBeam>,
+}
+
+impl LaserPosition {
+    /// Which alert and back distances of the config apply.
+    pub fn side_position(&self) -> LaserSidePosition {
+        match self.angle.abs().value() {
+            0..=14 => LaserSidePosition::Center,
+            15..=44 => LaserSidePosition::Side30,
+            _ => LaserSidePosition::Side60,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum GeometryError {
+    NoBeams,
+    BadChannel(u8),
+    DuplicateChannel(u8),
+    /// Two beams on the same tier at the same angle
+    DuplicateBeam(u8),
+    TooManyPositions,
+}
+
+pub type LaserPositions = ArrayVec<LaserPosition, MAX_LASER_POSITIONS>;
+
+/// Groups the beams by angle, from left to right.
+pub fn laser_positions(beams: &[LaserBeam]) -> Result<LaserPositions, GeometryError> {
+    if beams.is_empty() {
+        return Err(GeometryError::NoBeams);
+    }
+    let mut used = [false; RAW_LASERS_COUNT];
+    let mut positions = LaserPositions::new();
+    for beam in beams {
+        let channel = beam.channel as usize;
+        if channel >= RAW_LASERS_COUNT {
+            return Err(GeometryError::BadChannel(beam.channel));
+        }
+        if used[channel] {
+            return Err(GeometryError::DuplicateChannel(beam.channel));
+        }
+        used[channel] = true;
+
+        let index = match positions.iter().position(|p| p.angle == beam.angle) {
+            Some(index) => index,
+            None => {
+                let index = positions
+                    .iter()
+                    .position(|p| p.angle > beam.angle)
+                    .unwrap_or(positions.len());
+                positions
+                    .try_insert(
+                        index,
+                        LaserPosition {
+                            angle: beam.angle,
+                            upper: None,
+                            lower: None,
+                        },
+                    )
+                    .map_err(|_| GeometryError::TooManyPositions)?;
+                index
+            }
+        };
+        let slot = match beam.tier {
+            LaserTier::Upper => &mut positions[index].upper,
+            LaserTier::Lower => &mut positions[index].lower,
+        };
+        if slot.is_some() {
+            return Err(GeometryError::DuplicateBeam(beam.channel));
+        }
+        *slot = Some(*beam);
+    }
+    Ok(positions)
+}
@@ -0,0 +1,109 @@
+//! Sharp GP2Y0E02B infrared distance sensor.
+
+use embedded_hal_async::i2c::I2c;
+
+use crate::{
+    distance::{DistanceError, DistanceSensor},
+    lasers::{Gp2Y0E02bShift, LaserSettings},
+};
+
+pub const GP2Y0E02B_ADDR: u8 = 0x40;
+pub const GP2Y0E02B_SHIFT_REG: u8 = 0x35;
+pub const GP2Y0E02B_MEDIAN_REG: u8 = 0x3F;
+pub const GP2Y0E02B_ACC_REG: u8 = 0xA8;
+pub const GP2Y0E02B_READ_REG: u8 = 0x5E;
+/// Reads between two checks of the settings registers: a reset sensor still
+/// gives plausible readings, only scaled or filtered wrong.
+pub const GP2Y0E02B_SETTINGS_CHECK_READS: u16 = 50;
+
+pub struct Gp2Y0E02b<I2C> {
+    i2c: I2C,
+    settings: LaserSettings,
+    reads: u16,
+}
+
+impl<I2C: I2c> Gp2Y0E02b<I2C> {
+    pub fn new(i2c: I2C) -> Self {
+        Self {
+            i2c,
+            settings: LaserSettings::init(),
+            reads: 0,
+        }
+    }
+
+    async fn write_reg(&mut self, reg: u8, value: u8) -> Result<(), DistanceError<I2C::Error>> {
+        self.i2c
+            .write(GP2Y0E02B_ADDR, &[reg, value])
+            .await
+            .map_err(DistanceError::I2c)
+    }
+
+    async fn read_reg(&mut self, reg: u8) -> Result<u8, DistanceError<I2C::Error>> {
+        let mut value = [0u8; 1];
+        self.i2c
+            .write_read(GP2Y0E02B_ADDR, &[reg], &mut value)
+            .await
+            .map_err(DistanceError::I2c)?;
+        Ok(value[0])
+    }
+
+    fn shift(&self) -> Gp2Y0E02bShift {
+        self.settings.shift
+    }
+}
+
+impl<I2C: I2c> DistanceSensor for Gp2Y0E02b<I2C> {
+    type Error = I2C::Error;
+
+    async fn init(&mut self, settings: &LaserSettings) -> Result<(), DistanceError<Self::Error>> {
+        self.settings = *settings;
+        self.reads = 0;
+        self.write_reg(GP2Y0E02B_SHIFT_REG, settings.shift as u8)
+            .await?;
+        self.write_reg(GP2Y0E02B_MEDIAN_REG, settings.median as u8)
+            .await?;
+        self.write_reg(GP2Y0E02B_ACC_REG, settings.accumulation as u8)
+            .await
+    }
+
+    /// Every read returns a new measurement: the sensor keeps updating its
+    /// distance register on its own. Every `GP2Y0E02B_SETTINGS_CHECK_READS`
+    /// reads the settings are checked first.
+    async fn read(&mut self) -> Result<Option<u16>, DistanceError<Self::Error>> {
+        self.reads += 1;
+        if self.reads >= GP2Y0E02B_SETTINGS_CHECK_READS {
+            self.reads = 0;
+            self.health().await?;
+        }
+        let mut result_buf = [0u8; 2];
+        self.i2c
+            .write_read(GP2Y0E02B_ADDR, &[GP2Y0E02B_READ_REG], &mut result_buf)
+            .await
+            .map_err(DistanceError::I2c)?;
+        let raw_distance = ((result_buf[0] as u16) << 4) | ((result_buf[1] & 0xf) as u16);
+        let distance = (raw_distance * 10) / (1 << self.shift().divisor());
+        Ok(Some(self.shift().clamp_reading(distance)))
+    }
+
+    /// A reset sensor is back to its power-on settings (64cm shift, 7
+    /// samples median), so all three registers are read back: a reset shows
+    /// in any of them set away from its default.
+    async fn health(&mut self) -> Result<(), DistanceError<Self::Error>> {
+        let settings = self.settings;
+        let shift = self.read_reg(GP2Y0E02B_SHIFT_REG).await?;
+        let median = self.read_reg(GP2Y0E02B_MEDIAN_REG).await?;
+        let accumulation = self.read_reg(GP2Y0E02B_ACC_REG).await?;
+        if shift == settings.shift as u8
+            && median == settings.median as u8
+            && accumulation == settings.accumulation as u8
+        {
+            Ok(())
+        } else {
+            Err(DistanceError::Reset)
+        }
+    }
+
+    fn max_distance(&self) -> u16 {
+        self.shift().max_distance()
+    }
+}
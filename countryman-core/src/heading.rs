@@ -0,0 +1,173 @@
+//! Heading of the car on the track, from the IMU yaw.
+//!
+//! The yaw drifts slowly over a heat. `DriftMeter` measures the drift rate
+//! while the car stands still, and `HeadingTracker` removes it from the yaw
+//! measured since the last zero. The tracker can also be re-anchored to a
+//! known heading, e.g. when the car crosses a color marker.
+//!
+//! Everything is computed in centidegrees, like the IMU values.
+
+use embassy_time::{Duration, Instant};
+
+use crate::{
+    imu::{ImuData, ImuStability},
+    race::Angle,
+};
+
+/// Shortest measurement giving a drift rate.
+pub const DRIFT_MIN_WINDOW: Duration = Duration::from_secs(10);
+/// Yaw change between two samples (in centidegrees) meaning the car moved.
+pub const DRIFT_MAX_STEP: i32 = 20;
+/// Drift rates (in centidegrees per minute) above this mean the car moved.
+pub const DRIFT_MAX_RATE: i32 = 500;
+
+const CDEG_FULL_TURN: i32 = 36000;
+const MS_PER_MINUTE: i64 = 60_000;
+
+/// Wraps an angle in centidegrees to -180..180 degrees.
+pub fn wrap_cdeg(value: i32) -> i32 {
+    (value + CDEG_FULL_TURN / 2).rem_euclid(CDEG_FULL_TURN) - CDEG_FULL_TURN / 2
+}
+
+/// Measures the yaw drift while the car stands still, restarting whenever
+/// it moves.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct DriftMeter {
+    start: Option<Instant>,
+    last_yaw: i16,
+    last_at: Instant,
+    /// Yaw change since `start`, in centidegrees
+    total: i32,
+}
+
+impl DriftMeter {
+    pub fn new() -> Self {
+        Self {
+            start: None,
+            last_yaw: 0,
+            last_at: Instant::from_ticks(0),
+            total: 0,
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.start = None;
+    }
+
+    fn restart(&mut self, imu: &ImuData) {
+        self.start = Some(imu.timestamp);
+        self.last_yaw = imu.yaw;
+        self.last_at = imu.timestamp;
+        self.total = 0;
+    }
+
+    pub fn update(&mut self, imu: &ImuData) {
+        if self.start.is_none() {
+            self.restart(imu);
+            return;
+        }
+        let moving = matches!(imu.extras, Some(extras) if extras.stability == ImuStability::Motion);
+        let step = wrap_cdeg(imu.yaw as i32 - self.last_yaw as i32);
+        if moving || step.abs() > DRIFT_MAX_STEP {
+            self.restart(imu);
+            return;
+        }
+        self.total += step;
+        self.last_yaw = imu.yaw;
+        self.last_at = imu.timestamp;
+        if matches!(self.rate(), Some(rate) if rate.abs() > DRIFT_MAX_RATE) {
+            self.restart(imu);
+        }
+    }
+
+    /// How long the car has been still.
+    pub fn elapsed(&self) -> Duration {
+        self.start
+            .map(|start| self.last_at - start)
+            .unwrap_or(Duration::from_ticks(0))
+    }
+
+    /// Drift in centidegrees per minute, once measured for
+    /// `DRIFT_MIN_WINDOW`.
+    pub fn rate(&self) -> Option<i32> {
+        let elapsed = self.elapsed();
+        if elapsed < DRIFT_MIN_WINDOW {
+            return None;
+        }
+        Some((self.total as i64 * MS_PER_MINUTE / elapsed.as_millis() as i64) as i32)
+    }
+}
+
+impl Default for DriftMeter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Turns the IMU yaw into a track heading: zero where the yaw was zeroed,
+/// minus the drift since then.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct HeadingTracker {
+    /// Yaw reading as heading zero, in centidegrees
+    zero: i32,
+    zeroed_at: Instant,
+    /// In centidegrees per minute
+    drift_rate: i32,
+    /// Re-anchors since the last zero
+    anchors: u32,
+}
+
+impl HeadingTracker {
+    pub fn new(yaw: i16, now: Instant) -> Self {
+        Self {
+            zero: yaw as i32,
+            zeroed_at: now,
+            drift_rate: 0,
+            anchors: 0,
+        }
+    }
+
+    /// Makes the current yaw heading zero, keeping the drift rate.
+    pub fn rezero(&mut self, yaw: i16, now: Instant) {
+        self.zero = yaw as i32;
+        self.zeroed_at = now;
+        self.anchors = 0;
+    }
+
+    pub fn drift_rate(&self) -> i32 {
+        self.drift_rate
+    }
+
+    /// Applies a drift rate from now on (the drift accumulated so far is
+    /// kept).
+    pub fn set_drift_rate(&mut self, drift_rate: i32, now: Instant) {
+        self.zero += self.drift(now);
+        self.zeroed_at = now;
+        self.drift_rate = drift_rate;
+    }
+
+    pub fn anchors(&self) -> u32 {
+        self.anchors
+    }
+
+    /// Drift removed since the last zero, in centidegrees.
+    pub fn drift(&self, now: Instant) -> i32 {
+        let elapsed = now.saturating_duration_since(self.zeroed_at).as_millis() as i64;
+        (self.drift_rate as i64 * elapsed / MS_PER_MINUTE) as i32
+    }
+
+    /// Heading in centidegrees, in -180..180 degrees.
+    pub fn heading_cdeg(&self, yaw: i16, now: Instant) -> i32 {
+        wrap_cdeg(yaw as i32 - self.zero - self.drift(now))
+    }
+
+    pub fn heading(&self, yaw: i16, now: Instant) -> Angle {
+        Angle::from_cdeg(self.heading_cdeg(yaw, now))
+    }
+
+    /// Makes the current yaw read as `heading`.
+    pub fn anchor(&mut self, yaw: i16, now: Instant, heading: Angle) {
+        self.zero = wrap_cdeg(yaw as i32 - self.drift(now) - heading.cdeg());
+        self.anchors += 1;
+    }
+}
@@ -1,74 +1,6 @@
-use embassy_rp::{
-    peripherals::{PIN_16, PIN_17, UART0},
-    uart::{BufferedUart, Config},
-};
-use embassy_sync::{blocking_mutex::raw::CriticalSectionRawMutex, signal::Signal};
 use embassy_time::{Duration, Instant};
-use embedded_io_async::Read;
-use static_cell::make_static;
-
-pub static IMU_DATA: Signal<CriticalSectionRawMutex, ImuData> = Signal::new();
-
-const BUF_SIZE: usize = 64;
-
-pub async fn imu_task(uart0: UART0, pin_16: PIN_16, pin_17: PIN_17) {
-    let tx_buf = &mut make_static!([0u8; BUF_SIZE])[..];
-    let rx_buf = &mut make_static!([0u8; BUF_SIZE])[..];
-    let uart = BufferedUart::new(
-        uart0,
-        super::Irqs,
-        pin_16,
-        pin_17,
-        tx_buf,
-        rx_buf,
-        Config::default(),
-    );
-    let (mut rx, _) = uart.split();
-
-    let mut decoder = Bno080Decoder::init();
-    let mut data = ImuData::init();
-    let mut stillness_detector = ImuStillnessDetector::new();
-
-    let mut timestamp = Instant::now();
-    loop {
-        let mut buf = [0; 1];
-
-        match embassy_time::with_timeout(Duration::from_secs(5), rx.read_exact(&mut buf)).await {
-            Ok(result) => match result {
-                Ok(_) => {
-                    let now = Instant::now();
-                    let dt = now - timestamp;
-                    timestamp = now;
-
-                    let received = buf[0];
-                    if let Some(raw) = decoder.update(received) {
-                        stillness_detector.process_data(&raw, now, dt);
-                        // log::info!(
-                        //     "IMU [R {} P {} Y {}] [F {} S {} V {}]",
-                        //     raw.roll,
-                        //     raw.pitch,
-                        //     raw.yaw,
-                        //     raw.forward,
-                        //     raw.side,
-                        //     raw.vertical
-                        // );
-
-                        data.update(&raw, now, dt, stillness_detector.last_stillness);
-                        IMU_DATA.signal(data);
-                    }
-                }
-                Err(err) => {
-                    log::info!("IMU uart read error: {}", err);
-                }
-            },
-            Err(_) => {
-                log::error!("timeout reading IMU uart");
-            }
-        }
-    }
-}
 
-#[derive(Clone, Copy, PartialEq, Eq, Default)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
 pub struct Bno080RawRvcData {
     yaw: i16,
     pitch: i16,
@@ -79,11 +11,155 @@ pub struct Bno080RawRvcData {
     counter: u8,
 }
 
-const STILLNESS_SIDE: i16 = 150;
-const STILLNESS_FORWARD: i16 = 150;
-const STILL_FOR: Duration = Duration::from_millis(50);
-const STILLNESS_TIME_WINDOW: Duration = Duration::from_millis(500);
-const STILLNESS_TIME_THRESHOLD: Duration = Duration::from_millis(350);
+impl Bno080RawRvcData {
+    /// Angles in centidegrees, accelerations in mg, as in the RVC frames.
+    pub fn new(
+        yaw: i16,
+        pitch: i16,
+        roll: i16,
+        side: i16,
+        forward: i16,
+        vertical: i16,
+        counter: u8,
+    ) -> Self {
+        Self {
+            yaw,
+            pitch,
+            roll,
+            side,
+            forward,
+            vertical,
+            counter,
+        }
+    }
+}
+
+/// Output of the BNO08x stability classifier.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum ImuStability {
+    #[default]
+    Unknown,
+    OnTable,
+    Stationary,
+    Stable,
+    Motion,
+}
+
+impl ImuStability {
+    pub fn from_report(value: u8) -> Self {
+        match value {
+            1 => ImuStability::OnTable,
+            2 => ImuStability::Stationary,
+            3 => ImuStability::Stable,
+            4 => ImuStability::Motion,
+            _ => ImuStability::Unknown,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ImuStability::Unknown => "UNKNOWN",
+            ImuStability::OnTable => "ON-TABLE",
+            ImuStability::Stationary => "STATIONARY",
+            ImuStability::Stable => "STABLE",
+            ImuStability::Motion => "MOTION",
+        }
+    }
+}
+
+/// Readings only available when the IMU is driven over SHTP.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct ImuExtras {
+    /// Calibrated gyroscope around the vertical axis, in centidegrees per
+    /// second (counterclockwise), saturated at about 327 degrees per second
+    pub yaw_rate: i16,
+    /// Accelerations without gravity, in mg
+    pub linear_side: i16,
+    pub linear_forward: i16,
+    pub linear_vertical: i16,
+    pub stability: ImuStability,
+    /// Taps detected since startup, wrapping
+    pub taps: u8,
+}
+
+impl ImuExtras {
+    pub const fn new() -> Self {
+        Self {
+            yaw_rate: 0,
+            linear_side: 0,
+            linear_forward: 0,
+            linear_vertical: 0,
+            stability: ImuStability::Unknown,
+            taps: 0,
+        }
+    }
+}
+
+/// Stillness detection parameters (set from `RaceConfig::stillness`).
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct StillnessConfig {
+    /// Side or forward accelerations below this (in mg) look still
+    pub delta: i16,
+    /// How long a detected stillness is reported
+    pub still_for: Duration,
+    /// Length of the window the still samples are accumulated over
+    pub window: Duration,
+    /// Still time in a window needed to detect stillness
+    pub threshold: Duration,
+}
+
+impl StillnessConfig {
+    pub const fn init() -> Self {
+        Self {
+            delta: 150,
+            still_for: Duration::from_millis(50),
+            window: Duration::from_millis(500),
+            threshold: Duration::from_millis(350),
+        }
+    }
+}
+
+/// Frame counters of the serial link with the IMU, since startup.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct ImuLinkStats {
+    pub good: u32,
+    /// Frames dropped because of a wrong checksum
+    pub bad: u32,
+    /// Frames cut short by a header
+    pub resyncs: u32,
+    /// Frames missing from the counter sequence
+    pub lost: u32,
+}
+
+impl ImuLinkStats {
+    pub const fn new() -> Self {
+        Self {
+            good: 0,
+            bad: 0,
+            resyncs: 0,
+            lost: 0,
+        }
+    }
+
+    /// Frames sent by the IMU, good or not.
+    pub fn frames(&self) -> u32 {
+        self.good
+            .wrapping_add(self.bad)
+            .wrapping_add(self.resyncs)
+            .wrapping_add(self.lost)
+    }
+
+    /// Percentage of good frames since `earlier`, 100 when there were none.
+    pub fn quality_since(&self, earlier: &Self) -> u8 {
+        let good = self.good.wrapping_sub(earlier.good);
+        let total = self.frames().wrapping_sub(earlier.frames());
+        if total == 0 {
+            100
+        } else {
+            (good as u64 * 100 / total as u64) as u8
+        }
+    }
+}
 
 #[derive(Clone, Copy)]
 pub struct ImuData {
@@ -96,12 +172,15 @@ pub struct ImuData {
     pub timestamp: Instant,
     pub dt: Duration,
     pub last_stillness: Option<Instant>,
+    pub link: ImuLinkStats,
+    /// `None` in UART-RVC mode
+    pub extras: Option<ImuExtras>,
 }
 
 impl ImuData {
-    pub fn is_still(&self, now: Instant) -> bool {
+    pub fn is_still(&self, now: Instant, still_for: Duration) -> bool {
         self.last_stillness
-            .map(|since| now - since < STILL_FOR)
+            .map(|since| now - since < still_for)
             .unwrap_or(false)
     }
 }
@@ -120,8 +199,8 @@ impl StillnessMemory {
         }
     }
 
-    pub fn ended(&self) -> bool {
-        self.time_window >= STILLNESS_TIME_WINDOW
+    pub fn ended(&self, window: Duration) -> bool {
+        self.time_window >= window
     }
 
     pub fn update(&mut self, dt: Duration, is_still: bool) {
@@ -131,32 +210,40 @@ impl StillnessMemory {
         }
     }
 
-    pub fn is_still(&self) -> bool {
-        self.cumulative_stillness >= STILLNESS_TIME_THRESHOLD
+    pub fn is_still(&self, threshold: Duration) -> bool {
+        self.cumulative_stillness >= threshold
     }
 }
 
 #[derive(Clone, Copy)]
 pub struct ImuStillnessDetector {
+    pub config: StillnessConfig,
     pub memory: Option<StillnessMemory>,
     pub last_stillness: Option<Instant>,
 }
 
 impl ImuStillnessDetector {
-    pub fn new() -> Self {
+    pub fn new(config: StillnessConfig) -> Self {
         Self {
+            config,
             memory: None,
             last_stillness: None,
         }
     }
 
+    /// Applies new parameters, restarting the current detection window.
+    pub fn set_config(&mut self, config: StillnessConfig) {
+        self.config = config;
+        self.memory = None;
+    }
+
     pub fn process_data(&mut self, raw: &Bno080RawRvcData, now: Instant, dt: Duration) {
-        let is_still = raw.side.abs() < STILLNESS_SIDE || raw.forward.abs() < STILLNESS_FORWARD;
+        let is_still = raw.side.abs() < self.config.delta || raw.forward.abs() < self.config.delta;
         match self.memory {
             Some(mut memory) => {
                 memory.update(dt, is_still);
-                self.memory = if memory.ended() {
-                    if memory.is_still() {
+                self.memory = if memory.ended(self.config.window) {
+                    if memory.is_still(self.config.threshold) {
                         self.last_stillness = Some(now);
                     }
                     None
@@ -187,6 +274,8 @@ impl ImuData {
             timestamp: Instant::now(),
             dt: DT_MIN,
             last_stillness: None,
+            link: ImuLinkStats::new(),
+            extras: None,
         }
     }
 
@@ -245,6 +334,12 @@ pub struct Bno080Decoder {
     current: Bno080RawRvcData,
     checksum: u8,
     lsb: u8,
+    stats: ImuLinkStats,
+    /// Counter of the last good frame
+    last_counter: Option<u8>,
+    /// Frames dropped since the last good one, already counted as bad or
+    /// resyncs
+    dropped_since_good: u8,
 }
 
 impl Bno080Decoder {
@@ -252,10 +347,22 @@ impl Bno080Decoder {
         Self::default()
     }
 
+    pub fn stats(&self) -> ImuLinkStats {
+        self.stats
+    }
+
+    /// A header in the middle of a frame: the frame is dropped, and the
+    /// header starts the next one.
+    fn resync(&mut self) {
+        self.stats.resyncs += 1;
+        self.dropped_since_good = self.dropped_since_good.saturating_add(1);
+        self.expected = ExpectedByte::Header2;
+    }
+
     fn handle_lsb(&mut self, received: u8, expected: ExpectedByte, msg: &'static str) {
         if received == HEADER_BYTE {
             log::info!("{}", msg);
-            self.expected = ExpectedByte::Header2;
+            self.resync();
         } else {
             self.lsb = received;
             self.checksum = self.checksum.wrapping_add(received);
@@ -278,11 +385,24 @@ impl Bno080Decoder {
             true
         } else {
             log::info!("IMU: no unused {}", index);
-            self.expected = ExpectedByte::Header2;
+            self.resync();
             false
         }
     }
 
+    /// Counts the gap in the frame counters, minus the frames already
+    /// counted as dropped.
+    fn count_lost(&mut self, counter: u8) {
+        if let Some(last) = self.last_counter {
+            let gap = counter.wrapping_sub(last).wrapping_sub(1);
+            self.stats.lost += gap.saturating_sub(self.dropped_since_good) as u32;
+        }
+        self.last_counter = Some(counter);
+        self.dropped_since_good = 0;
+    }
+
+    /// Decodes a byte, returning a frame when a good one is complete.
+    /// Frames with a wrong checksum are dropped.
     pub fn update(&mut self, received: u8) -> Option<Bno080RawRvcData> {
         match self.expected {
             ExpectedByte::Header1 => {
@@ -386,7 +506,12 @@ impl Bno080Decoder {
                         received,
                         self.current.counter
                     );
+                    self.stats.bad += 1;
+                    self.dropped_since_good = self.dropped_since_good.saturating_add(1);
+                    return None;
                 }
+                self.count_lost(self.current.counter);
+                self.stats.good += 1;
                 Some(self.current)
             }
         }
@@ -0,0 +1,571 @@
+use embassy_time::{Duration, Instant};
+
+use arrayvec::ArrayVec;
+
+use crate::vision::LASER_OVERFLOW;
+
+pub const RAW_LASERS_COUNT: usize = 8;
+
+/// Failed reads in a row after which a channel is re-initialized.
+pub const LASER_MAX_CONSECUTIVE_ERRORS: u8 = 5;
+/// Frames with exactly the same reading after which a channel looks stuck
+/// (real readings always carry some noise, except for the overflow value).
+pub const LASER_STUCK_FRAMES: u16 = 200;
+/// Timeouts in a row, on any channel, after which the I2C bus looks stuck
+/// (a sensor holding SDA low makes every transaction time out).
+pub const I2C_STUCK_TIMEOUTS: u8 = 3;
+
+/// GP2Y0E02B maximum distance setting (shift register values).
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[repr(u8)]
+pub enum Gp2Y0E02bShift {
+    Cm128 = 1,
+    Cm64 = 2,
+}
+
+/// GP2Y0E02B median filter setting (median register values).
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[repr(u8)]
+pub enum Gp2Y0E02bMedian {
+    Med7 = 0x00,
+    Med5 = 0x10,
+    Med9 = 0x20,
+    Med1 = 0x30,
+}
+
+/// GP2Y0E02B accumulation setting (accumulation register values).
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[repr(u8)]
+pub enum Gp2Y0E02bAcc {
+    Acc1 = 0,
+    Acc5 = 1,
+    Acc30 = 2,
+    Acc10 = 3,
+}
+
+/// Half of the 4cm minimum distance of the sensor.
+const GP2Y0E02B_MIN_DISTANCE: u16 = 20;
+
+impl Gp2Y0E02bShift {
+    /// Values of the `laser_shift` config entry, in order.
+    pub const NAMES: &'static [&'static str] = &["128CM", "64CM"];
+
+    pub fn from_index(index: i16) -> Self {
+        match index {
+            1 => Gp2Y0E02bShift::Cm64,
+            _ => Gp2Y0E02bShift::Cm128,
+        }
+    }
+
+    pub const fn divisor(self) -> u16 {
+        match self {
+            Gp2Y0E02bShift::Cm128 => 5,
+            Gp2Y0E02bShift::Cm64 => 6,
+        }
+    }
+
+    /// Longest distance (mm) the sensor reports with this shift, also
+    /// reported when nothing is in sight.
+    pub const fn max_distance(self) -> u16 {
+        ((0xfff * 10) >> self.divisor()) as u16
+    }
+
+    /// Readings below the sensor minimum are real obstacles (e.g. the
+    /// nose of the car on a wall), reported at the minimum distance.
+    pub fn clamp_reading(self, distance: u16) -> u16 {
+        distance.clamp(GP2Y0E02B_MIN_DISTANCE, self.max_distance())
+    }
+}
+
+impl Gp2Y0E02bMedian {
+    /// Values of the `laser_median` config entry, in order.
+    pub const NAMES: &'static [&'static str] = &["MED1", "MED5", "MED7", "MED9"];
+
+    pub fn from_index(index: i16) -> Self {
+        match index {
+            0 => Gp2Y0E02bMedian::Med1,
+            2 => Gp2Y0E02bMedian::Med7,
+            3 => Gp2Y0E02bMedian::Med9,
+            _ => Gp2Y0E02bMedian::Med5,
+        }
+    }
+}
+
+impl Gp2Y0E02bAcc {
+    /// Values of the `laser_accumulation` config entry, in order.
+    pub const NAMES: &'static [&'static str] = &["ACC1", "ACC5", "ACC10", "ACC30"];
+
+    pub fn from_index(index: i16) -> Self {
+        match index {
+            0 => Gp2Y0E02bAcc::Acc1,
+            2 => Gp2Y0E02bAcc::Acc10,
+            3 => Gp2Y0E02bAcc::Acc30,
+            _ => Gp2Y0E02bAcc::Acc5,
+        }
+    }
+}
+
+/// GP2Y0E02B register settings, trading accuracy against scan rate.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct LaserSettings {
+    pub shift: Gp2Y0E02bShift,
+    pub median: Gp2Y0E02bMedian,
+    pub accumulation: Gp2Y0E02bAcc,
+}
+
+impl LaserSettings {
+    pub const fn init() -> Self {
+        Self {
+            shift: Gp2Y0E02bShift::Cm128,
+            median: Gp2Y0E02bMedian::Med5,
+            accumulation: Gp2Y0E02bAcc::Acc5,
+        }
+    }
+}
+
+/// Calibration scale meaning "leave readings as they are".
+pub const LASER_SCALE_ONE: i16 = 1000;
+/// Board distances (mm) the calibration screen asks for, in order: from the
+/// front bumper, with the board square to the car (see
+/// `geometry::calibration_targets`).
+pub const LASER_CALIBRATION_DISTANCES: [u16; 3] = [100, 300, 600];
+/// Valid readings averaged for each channel at each distance.
+pub const LASER_CALIBRATION_SAMPLES: u16 = 32;
+/// Fewest readings averaged when the sampling times out.
+pub const LASER_CALIBRATION_MIN_SAMPLES: u16 = 8;
+/// Longest sampling at one distance, channels short of readings by then are
+/// not used.
+pub const LASER_CALIBRATION_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Per channel distance correction: `reading * scale / LASER_SCALE_ONE + offset`.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct LaserCalibration {
+    /// mm
+    pub offset: i16,
+    /// Thousandths
+    pub scale: i16,
+}
+
+impl LaserCalibration {
+    pub const fn init() -> Self {
+        Self {
+            offset: 0,
+            scale: LASER_SCALE_ONE,
+        }
+    }
+
+    pub fn apply(&self, reading: u16) -> u16 {
+        let corrected =
+            reading as i32 * self.scale as i32 / LASER_SCALE_ONE as i32 + self.offset as i32;
+        corrected.clamp(0, u16::MAX as i32) as u16
+    }
+
+    /// Least squares fit of averaged `readings` against the actual
+    /// `distances`. Returns `None` when the readings do not change with the
+    /// distance or the correction is implausible (a broken or blocked
+    /// sensor).
+    pub fn fit(readings: &[u16], distances: &[u16]) -> Option<Self> {
+        let n = readings.len().min(distances.len()) as i64;
+        let (mut sx, mut sy, mut sxx, mut sxy) = (0i64, 0i64, 0i64, 0i64);
+        for (x, y) in readings.iter().zip(distances.iter()) {
+            let (x, y) = (*x as i64, *y as i64);
+            sx += x;
+            sy += y;
+            sxx += x * x;
+            sxy += x * y;
+        }
+        let denominator = n * sxx - sx * sx;
+        if n < 2 || denominator == 0 {
+            return None;
+        }
+        let one = LASER_SCALE_ONE as i64;
+        let scale = (n * sxy - sx * sy) * one / denominator;
+        let offset = (sy * one - scale * sx) / (n * one);
+        if !(one / 2..=one * 2).contains(&scale) || !(-300..=300).contains(&offset) {
+            return None;
+        }
+        Some(Self {
+            offset: offset as i16,
+            scale: scale as i16,
+        })
+    }
+
+    /// Fits the sampled `(reading, distance)` points, skipping the missing
+    /// ones.
+    pub fn fit_points(points: &[Option<(u16, u16)>]) -> Option<Self> {
+        let mut readings = ArrayVec::<u16, { LASER_CALIBRATION_DISTANCES.len() }>::new();
+        let mut distances = ArrayVec::<u16, { LASER_CALIBRATION_DISTANCES.len() }>::new();
+        for (reading, distance) in points.iter().flatten() {
+            readings.try_push(*reading).ok()?;
+            distances.try_push(*distance).ok()?;
+        }
+        Self::fit(&readings, &distances)
+    }
+}
+
+impl Default for LaserCalibration {
+    fn default() -> Self {
+        Self::init()
+    }
+}
+
+/// Distance corrections of all the channels, stored with the profiles.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct LasersCalibration {
+    pub channels: [LaserCalibration; RAW_LASERS_COUNT],
+}
+
+impl LasersCalibration {
+    pub const fn init() -> Self {
+        Self {
+            channels: [LaserCalibration::init(); RAW_LASERS_COUNT],
+        }
+    }
+}
+
+impl Default for LasersCalibration {
+    fn default() -> Self {
+        Self::init()
+    }
+}
+
+/// Averages uncalibrated readings of some channels (a bit mask) at one
+/// distance, for up to `LASER_CALIBRATION_TIMEOUT`.
+pub struct LaserCalibrationSampler {
+    channels: u8,
+    sums: [u32; RAW_LASERS_COUNT],
+    counts: [u16; RAW_LASERS_COUNT],
+    started: Option<Instant>,
+    last: Option<Instant>,
+}
+
+impl LaserCalibrationSampler {
+    pub const fn new(channels: u8) -> Self {
+        Self {
+            channels,
+            sums: [0; RAW_LASERS_COUNT],
+            counts: [0; RAW_LASERS_COUNT],
+            started: None,
+            last: None,
+        }
+    }
+
+    fn is_sampled(&self, channel: usize) -> bool {
+        self.channels & (1 << channel) != 0
+    }
+
+    /// Adds the valid channels read for a frame, except where nothing is in
+    /// sight: that is up to the raw reading, the calibrated one depends on the
+    /// calibration being replaced.
+    pub fn add(&mut self, readings: &RawLaserReadings) {
+        self.started.get_or_insert(readings.timestamp);
+        self.last = Some(readings.timestamp);
+        for channel in 0..RAW_LASERS_COUNT {
+            if self.is_sampled(channel)
+                && readings.is_updated(channel)
+                && readings.is_valid(channel)
+                && !readings.is_overflowing(channel)
+                && self.counts[channel] < LASER_CALIBRATION_SAMPLES
+            {
+                self.sums[channel] += readings.raw[channel] as u32;
+                self.counts[channel] += 1;
+            }
+        }
+    }
+
+    /// Channels that still need samples.
+    pub fn missing(&self) -> usize {
+        (0..RAW_LASERS_COUNT)
+            .filter(|channel| {
+                self.is_sampled(*channel) && self.counts[*channel] < LASER_CALIBRATION_SAMPLES
+            })
+            .count()
+    }
+
+    /// Every channel has its samples, or the time is up.
+    pub fn is_done(&self) -> bool {
+        let elapsed = match (self.started, self.last) {
+            (Some(started), Some(last)) => last - started,
+            _ => Duration::from_ticks(0),
+        };
+        self.missing() == 0 || elapsed >= LASER_CALIBRATION_TIMEOUT
+    }
+
+    /// `None` for channels not sampled, or with less than
+    /// `LASER_CALIBRATION_MIN_SAMPLES` readings.
+    pub fn average(&self, channel: usize) -> Option<u16> {
+        if !self.is_sampled(channel) || self.counts[channel] < LASER_CALIBRATION_MIN_SAMPLES {
+            None
+        } else {
+            Some((self.sums[channel] / self.counts[channel] as u32) as u16)
+        }
+    }
+}
+
+/// Outcome of the last read of a laser channel.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum LaserChannelState {
+    Valid,
+    I2cError,
+    Timeout,
+    /// Outside the range of the configured sensor scale
+    OutOfRange,
+    /// The sensor lost its settings
+    Reset,
+}
+
+impl LaserChannelState {
+    pub fn is_valid(self) -> bool {
+        self == LaserChannelState::Valid
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            LaserChannelState::Valid => "OK",
+            LaserChannelState::I2cError => "I2C",
+            LaserChannelState::Timeout => "TIMEOUT",
+            LaserChannelState::OutOfRange => "RANGE",
+            LaserChannelState::Reset => "RESET",
+        }
+    }
+}
+
+/// Why a channel needs to be re-initialized.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum LaserFault {
+    Errors,
+    OutOfRange,
+    Stuck,
+    Reset,
+}
+
+impl LaserFault {
+    pub fn name(&self) -> &'static str {
+        match self {
+            LaserFault::Errors => "errors",
+            LaserFault::OutOfRange => "out of range",
+            LaserFault::Stuck => "stuck",
+            LaserFault::Reset => "settings lost",
+        }
+    }
+}
+
+/// Health tracking of a laser channel.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct LaserChannelHealth {
+    pub consecutive_errors: u8,
+    pub last_value: u16,
+    pub same_value_frames: u16,
+    /// Readings outside of the sensor range since startup
+    pub out_of_range: u32,
+    /// Times the channel has been re-initialized
+    pub reinits: u32,
+    pub last_fault: Option<LaserFault>,
+}
+
+impl LaserChannelHealth {
+    pub const fn new() -> Self {
+        Self {
+            consecutive_errors: 0,
+            last_value: 0,
+            same_value_frames: 0,
+            out_of_range: 0,
+            reinits: 0,
+            last_fault: None,
+        }
+    }
+
+    /// Tracks a channel read (`overflow` is the reading for "nothing in
+    /// sight"), returning a fault when the channel should be re-initialized.
+    /// Readings at either end of the range (nothing in sight, or clamped to
+    /// the minimum with the nose on a wall) legitimately repeat, and are
+    /// never counted as stuck.
+    pub fn update(
+        &mut self,
+        reading: Result<u16, LaserChannelState>,
+        overflow: u16,
+    ) -> Option<LaserFault> {
+        let fault = match reading {
+            Ok(value) => {
+                self.consecutive_errors = 0;
+                let clamped = value <= GP2Y0E02B_MIN_DISTANCE || value >= overflow;
+                if value == self.last_value && !clamped {
+                    self.same_value_frames = self.same_value_frames.saturating_add(1);
+                } else {
+                    self.same_value_frames = 0;
+                }
+                self.last_value = value;
+                if self.same_value_frames >= LASER_STUCK_FRAMES {
+                    Some(LaserFault::Stuck)
+                } else {
+                    None
+                }
+            }
+            // Its readings are wrong until it is configured again
+            Err(LaserChannelState::Reset) => Some(LaserFault::Reset),
+            Err(state) => {
+                if state == LaserChannelState::OutOfRange {
+                    self.out_of_range += 1;
+                }
+                self.consecutive_errors = self.consecutive_errors.saturating_add(1);
+                if self.consecutive_errors >= LASER_MAX_CONSECUTIVE_ERRORS {
+                    Some(if state == LaserChannelState::OutOfRange {
+                        LaserFault::OutOfRange
+                    } else {
+                        LaserFault::Errors
+                    })
+                } else {
+                    None
+                }
+            }
+        };
+        if fault.is_some() {
+            self.last_fault = fault;
+        }
+        fault
+    }
+
+    pub fn reinitialized(&mut self) {
+        self.reinits += 1;
+        self.consecutive_errors = 0;
+        self.same_value_frames = 0;
+    }
+}
+
+impl Default for LaserChannelHealth {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Detects a stuck I2C bus from the outcome of the channel reads.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct I2cBusHealth {
+    pub consecutive_timeouts: u8,
+    /// Bus recoveries since startup
+    pub recoveries: u32,
+}
+
+impl I2cBusHealth {
+    pub const fn new() -> Self {
+        Self {
+            consecutive_timeouts: 0,
+            recoveries: 0,
+        }
+    }
+
+    /// Tracks a channel read, returning `true` when the bus must be
+    /// recovered. Any answer from the bus, even an error, means it works.
+    pub fn update(&mut self, reading: Result<u16, LaserChannelState>) -> bool {
+        if reading == Err(LaserChannelState::Timeout) {
+            self.consecutive_timeouts = self.consecutive_timeouts.saturating_add(1);
+        } else {
+            self.consecutive_timeouts = 0;
+        }
+        self.consecutive_timeouts >= I2C_STUCK_TIMEOUTS
+    }
+
+    pub fn recovered(&mut self) {
+        self.recoveries += 1;
+        self.consecutive_timeouts = 0;
+    }
+}
+
+impl Default for I2cBusHealth {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct RawLaserReadings {
+    /// Calibrated distances in mm, `LASER_OVERFLOW` when nothing is in
+    /// sight, meaningful only for valid channels
+    pub values: [u16; RAW_LASERS_COUNT],
+    /// Distances as read from the sensors
+    pub raw: [u16; RAW_LASERS_COUNT],
+    pub states: [LaserChannelState; RAW_LASERS_COUNT],
+    /// Failed reads per channel since startup
+    pub errors: [u32; RAW_LASERS_COUNT],
+    pub health: [LaserChannelHealth; RAW_LASERS_COUNT],
+    pub bus_health: I2cBusHealth,
+    /// When each channel was last read
+    pub read_at: [Instant; RAW_LASERS_COUNT],
+    /// Bit mask of the channels read for this frame: a scan can read only
+    /// some of them, the others keep their last reading
+    pub updated: u8,
+    /// Bit mask of the channels with nothing in sight, set by `calibrate`
+    pub overflowing: u8,
+    pub timestamp: Instant,
+    pub dt: Duration,
+}
+
+impl RawLaserReadings {
+    /// Readings with every channel valid.
+    pub fn new(values: [u16; RAW_LASERS_COUNT], timestamp: Instant, dt: Duration) -> Self {
+        Self {
+            values,
+            raw: values,
+            states: [LaserChannelState::Valid; RAW_LASERS_COUNT],
+            errors: [0; RAW_LASERS_COUNT],
+            health: [LaserChannelHealth::new(); RAW_LASERS_COUNT],
+            bus_health: I2cBusHealth::new(),
+            read_at: [timestamp; RAW_LASERS_COUNT],
+            updated: u8::MAX,
+            overflowing: 0,
+            timestamp,
+            dt,
+        }
+    }
+
+    pub fn is_valid(&self, channel: usize) -> bool {
+        self.states[channel].is_valid()
+    }
+
+    pub fn is_updated(&self, channel: usize) -> bool {
+        self.updated & (1 << channel) != 0
+    }
+
+    pub fn is_overflowing(&self, channel: usize) -> bool {
+        self.overflowing & (1 << channel) != 0
+    }
+
+    /// Stores the outcome of a channel read, counting failures.
+    pub fn set(&mut self, channel: usize, reading: Result<u16, LaserChannelState>, at: Instant) {
+        self.read_at[channel] = at;
+        self.updated |= 1 << channel;
+        match reading {
+            Ok(value) => {
+                self.values[channel] = value;
+                self.raw[channel] = value;
+                self.states[channel] = LaserChannelState::Valid;
+            }
+            Err(state) => {
+                self.states[channel] = state;
+                self.errors[channel] += 1;
+            }
+        }
+    }
+
+    /// Corrects the valid channels, turning the `overflow` reading of each
+    /// sensor (nothing in sight, which depends on the sensor settings) into
+    /// `LASER_OVERFLOW`. Failing channels keep their last corrected value.
+    pub fn calibrate(
+        &mut self,
+        calibration: &LasersCalibration,
+        overflow: &[u16; RAW_LASERS_COUNT],
+    ) {
+        for (channel, overflow) in overflow.iter().enumerate() {
+            if !self.is_valid(channel) {
+                continue;
+            }
+            if self.raw[channel] >= *overflow {
+                self.overflowing |= 1 << channel;
+                self.values[channel] = LASER_OVERFLOW;
+            } else {
+                self.overflowing &= !(1 << channel);
+                self.values[channel] = calibration.channels[channel].apply(self.raw[channel]);
+            }
+        }
+    }
+}
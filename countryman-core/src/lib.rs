@@ -0,0 +1,32 @@
+//! Hardware independent part of the countryman robot.
+//!
+//! Everything in here builds for both the RP2040 firmware and the host, so the
+//! race decision logic and the sensor protocol decoders can be exercised with
+//! `cargo test` on a workstation.
+
+#![no_std]
+#![feature(async_fn_in_trait)]
+#![allow(incomplete_features)]
+
+pub mod bno08x;
+pub mod cmd;
+pub mod configuration;
+pub mod console;
+pub mod distance;
+pub mod esp32c3;
+pub mod geometry;
+pub mod gp2y0e02b;
+pub mod heading;
+pub mod imu;
+pub mod lasers;
+pub mod profiles;
+pub mod race;
+pub mod rgb;
+pub mod safety;
+pub mod scan;
+pub mod shtp;
+pub mod storage;
+pub mod trace;
+pub mod vision;
+pub mod vl53l0x;
+pub mod vl53l1x;
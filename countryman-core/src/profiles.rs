@@ -0,0 +1,212 @@
+//! Named `RaceConfig` sets, so that each kind of track keeps its own tuning.
+
+use arrayvec::{ArrayString, ArrayVec};
+
+use crate::{configuration::RaceConfig, lasers::LasersCalibration};
+
+pub const MAX_PROFILES: usize = 6;
+pub const PROFILE_NAME_SIZE: usize = 8;
+
+pub type ProfileName = ArrayString<PROFILE_NAME_SIZE>;
+
+/// Characters a profile name can be edited with from the buttons.
+const NAME_CHARS: &[u8] = b" ABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789-";
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct RaceProfile {
+    pub name: ProfileName,
+    pub config: RaceConfig,
+}
+
+impl RaceProfile {
+    /// `name` is upper cased and truncated to `PROFILE_NAME_SIZE` characters;
+    /// non ASCII characters are dropped.
+    pub fn new(name: &str, config: RaceConfig) -> Self {
+        let mut profile = Self {
+            name: ProfileName::new(),
+            config,
+        };
+        profile.rename(name);
+        profile
+    }
+
+    pub fn name(&self) -> &str {
+        self.name.as_str()
+    }
+
+    pub fn rename(&mut self, name: &str) {
+        self.name.clear();
+        for c in name
+            .chars()
+            .filter(|c| c.is_ascii_graphic() || *c == ' ')
+            .take(PROFILE_NAME_SIZE)
+        {
+            self.name.push(c.to_ascii_uppercase());
+        }
+    }
+
+    /// Moves the character at `index` by `delta` positions in the editable
+    /// character set, padding the name with spaces if needed.
+    pub fn change_name_char(&mut self, index: usize, delta: i8) {
+        if index >= PROFILE_NAME_SIZE {
+            return;
+        }
+        let mut chars = [b' '; PROFILE_NAME_SIZE];
+        for (i, c) in self.name.bytes().enumerate() {
+            chars[i] = c;
+        }
+        let current = NAME_CHARS
+            .iter()
+            .position(|c| *c == chars[index])
+            .unwrap_or(0) as i32;
+        let next = (current + delta as i32).rem_euclid(NAME_CHARS.len() as i32);
+        chars[index] = NAME_CHARS[next as usize];
+
+        self.name.clear();
+        let len = chars.iter().rposition(|c| *c != b' ').map_or(0, |i| i + 1);
+        for c in chars[..len].iter() {
+            self.name.push(*c as char);
+        }
+    }
+
+    pub fn print(&self) {
+        log::info!("profile {}", self.name());
+        for (schema, value) in self.config.values() {
+            log::info!("  {}: {} {}", schema.key, value, schema.unit);
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Eq)]
+pub struct RaceProfiles {
+    profiles: ArrayVec<RaceProfile, MAX_PROFILES>,
+    active: usize,
+    /// Shared by all the profiles: it belongs to the sensors, not the track
+    calibration: LasersCalibration,
+}
+
+impl RaceProfiles {
+    /// The built in profiles: SAFE uses the default config.
+    pub fn init() -> Self {
+        let safe = RaceConfig::init();
+
+        let mut fast = RaceConfig::init();
+        fast.max_speed = 5500;
+        fast.min_speed = 5000;
+        fast.sprint_speed = 10000;
+        fast.alert_distance_center = 420;
+        fast.alert_distance_side_30 = 210;
+
+        let mut ramp = RaceConfig::init();
+        ramp.max_speed = 4300;
+        ramp.min_speed = 4000;
+        ramp.climbing_speed = 10000;
+        ramp.climbing_angle = 12;
+        ramp.slope_distance_delta = 180;
+
+        let mut profiles = Self::empty();
+        profiles.push(RaceProfile::new("SAFE", safe));
+        profiles.push(RaceProfile::new("FAST", fast));
+        profiles.push(RaceProfile::new("RAMP", ramp));
+        profiles
+    }
+
+    pub fn empty() -> Self {
+        Self {
+            profiles: ArrayVec::new(),
+            active: 0,
+            calibration: LasersCalibration::init(),
+        }
+    }
+
+    /// Returns `false` if there is no room left.
+    pub fn push(&mut self, profile: RaceProfile) -> bool {
+        self.profiles.try_push(profile).is_ok()
+    }
+
+    pub fn len(&self) -> usize {
+        self.profiles.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.profiles.is_empty()
+    }
+
+    pub fn get(&self, index: usize) -> Option<&RaceProfile> {
+        self.profiles.get(index)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &RaceProfile> {
+        self.profiles.iter()
+    }
+
+    pub fn active_index(&self) -> usize {
+        self.active
+    }
+
+    pub fn active(&self) -> &RaceProfile {
+        &self.profiles[self.active]
+    }
+
+    pub fn active_mut(&mut self) -> &mut RaceProfile {
+        &mut self.profiles[self.active]
+    }
+
+    pub fn config(&self) -> &RaceConfig {
+        &self.active().config
+    }
+
+    pub fn config_mut(&mut self) -> &mut RaceConfig {
+        &mut self.active_mut().config
+    }
+
+    pub fn calibration(&self) -> &LasersCalibration {
+        &self.calibration
+    }
+
+    pub fn set_calibration(&mut self, calibration: LasersCalibration) {
+        self.calibration = calibration;
+    }
+
+    pub fn select(&mut self, index: usize) {
+        if index < self.profiles.len() {
+            self.active = index;
+        }
+    }
+
+    pub fn select_next(&mut self) {
+        self.active = (self.active + 1) % self.profiles.len();
+    }
+
+    pub fn select_prev(&mut self) {
+        self.active = (self.active + self.profiles.len() - 1) % self.profiles.len();
+    }
+
+    pub fn find(&self, name: &str) -> Option<usize> {
+        self.profiles
+            .iter()
+            .position(|p| p.name().eq_ignore_ascii_case(name))
+    }
+
+    /// Copies the active profile under a new name (the old one with a digit
+    /// appended) and makes the copy active. Returns `false` if full.
+    pub fn clone_active(&mut self) -> bool {
+        if self.profiles.is_full() {
+            return false;
+        }
+        let mut profile = *self.active();
+        let base_len = profile.name.len().min(PROFILE_NAME_SIZE - 1);
+        for digit in '2'..='9' {
+            let mut name = ProfileName::new();
+            name.push_str(&profile.name[..base_len]);
+            name.push(digit);
+            if self.find(&name).is_none() {
+                profile.name = name;
+                break;
+            }
+        }
+        self.profiles.push(profile);
+        self.active = self.profiles.len() - 1;
+        true
+    }
+}
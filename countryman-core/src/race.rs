@@ -0,0 +1,620 @@
+use embassy_time::{Duration, Instant};
+
+use crate::configuration::RaceConfig;
+use crate::heading::HeadingTracker;
+use crate::imu::ImuData;
+use crate::lasers::RawLaserReadings;
+use crate::rgb::RgbEvent;
+use crate::trace::TraceEvent;
+use crate::vision::{LaserStatus, Vision};
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+#[repr(i8)]
+pub enum Direction {
+    Start = 0,
+    Right = 1,
+    Back = 2,
+    Left = 3,
+}
+
+impl From<i16> for Direction {
+    fn from(value: i16) -> Self {
+        unsafe { core::mem::transmute((value as i8) % 4) }
+    }
+}
+
+impl From<i8> for Direction {
+    fn from(value: i8) -> Self {
+        unsafe { core::mem::transmute(value % 4) }
+    }
+}
+
+impl Into<i8> for Direction {
+    fn into(self) -> i8 {
+        unsafe { core::mem::transmute(self) }
+    }
+}
+
+impl Direction {
+    pub fn right(self) -> Self {
+        let v: i8 = self.into();
+        (v + 1).into()
+    }
+
+    pub fn left(self) -> Self {
+        let v: i8 = self.into();
+        (v + 5).into()
+    }
+
+    pub fn go_right(&mut self) {
+        *self = self.right()
+    }
+
+    pub fn go_left(&mut self) {
+        *self = self.left()
+    }
+
+    pub fn inversion(self) -> Self {
+        let v: i8 = self.into();
+        (v + 2).into()
+    }
+
+    pub fn invert(&mut self) {
+        *self = self.inversion()
+    }
+}
+
+/// Centidegrees in a degree.
+pub const ANGLE_SCALE: i32 = 100;
+/// Servo PWM duty units per degree of steering.
+pub const SERVO_UNITS_PER_DEGREE: i32 = 10;
+
+const CDEG_HALF_TURN: i32 = 180 * ANGLE_SCALE;
+const CDEG_FULL_TURN: i32 = 360 * ANGLE_SCALE;
+
+/// An angle in -180..180 degrees, stored in centidegrees (the resolution of
+/// the IMU values). Converting from and to integers works in whole degrees.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub struct Angle {
+    cdeg: i32,
+}
+
+impl From<i32> for Angle {
+    fn from(degrees: i32) -> Self {
+        Self::from_degrees(degrees)
+    }
+}
+
+impl Into<i32> for Angle {
+    fn into(self) -> i32 {
+        self.value()
+    }
+}
+
+impl Into<i16> for Angle {
+    fn into(self) -> i16 {
+        self.value() as i16
+    }
+}
+
+/// Divides rounding to the nearest integer (halves away from zero).
+fn div_round(value: i64, divisor: i64) -> i64 {
+    let (value, divisor) = if divisor < 0 {
+        (-value, -divisor)
+    } else {
+        (value, divisor)
+    };
+    if value < 0 {
+        (value - divisor / 2) / divisor
+    } else {
+        (value + divisor / 2) / divisor
+    }
+}
+
+impl Angle {
+    const fn normalize_cdeg(cdeg: i32) -> i32 {
+        if cdeg > CDEG_HALF_TURN || cdeg < -CDEG_HALF_TURN {
+            (cdeg + CDEG_HALF_TURN).rem_euclid(CDEG_FULL_TURN) - CDEG_HALF_TURN
+        } else {
+            cdeg
+        }
+    }
+
+    pub const fn from_cdeg(cdeg: i32) -> Self {
+        Self {
+            cdeg: Self::normalize_cdeg(cdeg),
+        }
+    }
+
+    pub const fn from_degrees(degrees: i32) -> Self {
+        Self::from_cdeg((degrees % 360) * ANGLE_SCALE)
+    }
+
+    pub fn from_imu_value(imu_value: i16) -> Self {
+        Self {
+            cdeg: (imu_value as i32).min(CDEG_HALF_TURN).max(-CDEG_HALF_TURN),
+        }
+    }
+
+    pub fn cdeg(self) -> i32 {
+        self.cdeg
+    }
+
+    /// Rounded to whole degrees.
+    pub fn value(self) -> i32 {
+        div_round(self.cdeg as i64, ANGLE_SCALE as i64) as i32
+    }
+
+    pub fn abs(self) -> Self {
+        Self {
+            cdeg: self.cdeg.abs(),
+        }
+    }
+
+    /// Shortest turn from `other` to `self` (positive turning right).
+    pub fn diff(self, other: Self) -> Self {
+        Self::from_cdeg(self.cdeg - other.cdeg)
+    }
+
+    /// Multiplied by `numerator / denominator`, rounded to centidegrees.
+    pub fn scale(self, numerator: i32, denominator: i32) -> Self {
+        Self::from_cdeg(div_round(self.cdeg as i64 * numerator as i64, denominator as i64) as i32)
+    }
+
+    /// Steering servo offset, in PWM duty units.
+    pub fn servo_units(self) -> i16 {
+        div_round(
+            self.cdeg as i64 * SERVO_UNITS_PER_DEGREE as i64,
+            ANGLE_SCALE as i64,
+        ) as i16
+    }
+
+    /// Mean direction of `angles` (so the mean of 170 and -170 is 180),
+    /// `None` when there are none or they cancel out.
+    pub fn circular_mean(angles: impl IntoIterator<Item = Angle>) -> Option<Self> {
+        let (mut sin, mut cos) = (0.0f32, 0.0f32);
+        for angle in angles {
+            let radians = angle.radians();
+            sin += libm::sinf(radians);
+            cos += libm::cosf(radians);
+        }
+        if libm::hypotf(sin, cos) < 1e-3 {
+            return None;
+        }
+        let radians = libm::atan2f(sin, cos);
+        Some(Self::from_cdeg(
+            libm::roundf(radians * (CDEG_HALF_TURN as f32 / core::f32::consts::PI)) as i32,
+        ))
+    }
+
+    pub fn radians(self) -> f32 {
+        self.cdeg as f32 * (core::f32::consts::PI / CDEG_HALF_TURN as f32)
+    }
+
+    pub const ZERO: Self = Self::from_degrees(0);
+    pub const R90: Self = Self::from_degrees(90);
+    pub const L90: Self = Self::from_degrees(-90);
+    pub const BACK: Self = Self::from_degrees(180);
+    pub const L45: Self = Self::from_degrees(-45);
+    pub const R45: Self = Self::from_degrees(45);
+    pub const R170: Self = Self::from_degrees(170);
+    pub const L170: Self = Self::from_degrees(-170);
+
+    pub const R100: Self = Self::from_degrees(100);
+    pub const L100: Self = Self::from_degrees(-100);
+
+    pub const SMALL: Self = Self::from_degrees(5);
+
+    pub const SLL: Self = Self::from_degrees(-60);
+    pub const SL: Self = Self::from_degrees(-30);
+    pub const SC: Self = Self::from_degrees(0);
+    pub const SR: Self = Self::from_degrees(30);
+    pub const SRR: Self = Self::from_degrees(60);
+    pub const SHALF: Self = Self::from_degrees(15);
+
+    pub const TILT_ALERT: Self = Self::from_degrees(45);
+
+    pub const MAX_STEER: Self = Self::from_degrees(35);
+    pub const MIN_STEER: Self = Self::from_degrees(-35);
+}
+
+impl core::ops::Add for Angle {
+    type Output = Angle;
+
+    fn add(self, rhs: Self) -> Self::Output {
+        Self::from_cdeg(self.cdeg + rhs.cdeg)
+    }
+}
+
+impl core::ops::AddAssign for Angle {
+    fn add_assign(&mut self, rhs: Self) {
+        *self = *self + rhs;
+    }
+}
+
+impl core::ops::Sub for Angle {
+    type Output = Angle;
+
+    fn sub(self, rhs: Self) -> Self::Output {
+        self.diff(rhs)
+    }
+}
+
+impl core::ops::SubAssign for Angle {
+    fn sub_assign(&mut self, rhs: Self) {
+        *self = *self - rhs;
+    }
+}
+
+impl core::ops::Neg for Angle {
+    type Output = Self;
+
+    fn neg(self) -> Self::Output {
+        Self { cdeg: -self.cdeg }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+#[repr(i8)]
+pub enum TrackSide {
+    Left = 0,
+    Right = 1,
+}
+
+impl From<i8> for TrackSide {
+    fn from(value: i8) -> Self {
+        unsafe { core::mem::transmute(value % 2) }
+    }
+}
+
+impl Into<i8> for TrackSide {
+    fn into(self) -> i8 {
+        unsafe { core::mem::transmute(self) }
+    }
+}
+
+impl From<i16> for TrackSide {
+    fn from(value: i16) -> Self {
+        (value as i8).into()
+    }
+}
+
+/// Largest difference between the tracked heading and the marker heading for
+/// a marker crossing to re-anchor the heading.
+pub const MARKER_ANCHOR_MAX_ERROR: Angle = Angle::R45;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct RaceAction {
+    pub power: i16,
+    pub steer: Angle,
+}
+
+pub fn detect_tilt_alert(pitch: Angle, roll: Angle) -> bool {
+    pitch < -Angle::TILT_ALERT
+        || pitch > Angle::TILT_ALERT
+        || roll < -Angle::TILT_ALERT
+        || roll > Angle::TILT_ALERT
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct BackSteering {
+    pub remaining_time: Duration,
+    pub steer: Angle,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct RouteTarget {
+    pub remaining_time: Duration,
+    pub go_back: bool,
+    pub was_still: bool,
+    pub start: Angle,
+    pub target: Angle,
+}
+
+impl RouteTarget {
+    pub fn new_for_stillness(
+        config: &RaceConfig,
+        current_heading: Angle,
+        target_delta: Angle,
+    ) -> Self {
+        Self {
+            remaining_time: Duration::from_millis(config.inversion_time as u64),
+            go_back: true,
+            was_still: true,
+            start: current_heading,
+            target: current_heading + target_delta,
+        }
+    }
+
+    pub fn new_for_inversion(config: &RaceConfig, current_heading: Angle, steer: Angle) -> Self {
+        Self {
+            remaining_time: Duration::from_millis(config.inversion_time as u64),
+            go_back: true,
+            was_still: false,
+            start: current_heading,
+            target: current_heading
+                + if steer > Angle::ZERO {
+                    Angle::R170
+                } else {
+                    Angle::L170
+                },
+        }
+    }
+
+    pub fn new_for_climbing(config: &RaceConfig, current_heading: Angle) -> Self {
+        Self {
+            remaining_time: Duration::from_millis(config.inversion_time as u64),
+            go_back: true,
+            was_still: false,
+            start: current_heading,
+            target: config.climb_direction(),
+        }
+    }
+}
+
+/// Latest known value of every sensor the race logic depends on.
+#[derive(Clone, Copy)]
+pub struct RaceInputs {
+    pub lasers: RawLaserReadings,
+    pub imu: ImuData,
+    pub rgb: RgbEvent,
+}
+
+/// What the car is doing, as shown on the LCD while racing.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum RaceColor {
+    /// Tilt alert: motors are stopped
+    Black,
+    /// Back panic maneuver
+    Red,
+    /// Route target triggered by stillness
+    Blue,
+    /// Route target triggered by climbing or color inversion
+    Yellow,
+    /// Initial sprint
+    White,
+    /// Regular driving
+    Green,
+}
+
+#[derive(Clone, Copy)]
+pub struct RaceOutput {
+    pub action: RaceAction,
+    pub color: RaceColor,
+    pub trace: TraceEvent,
+    /// Target proposed by vision, before any maneuver is applied
+    pub vision_target: Angle,
+    pub vision_status: LaserStatus,
+    pub vision_window: Option<(usize, usize)>,
+}
+
+/// The race decision logic, free of any I/O.
+///
+/// The caller feeds it the latest sensor values and applies the resulting
+/// action to the motors.
+pub struct RaceController {
+    config: RaceConfig,
+    heading: HeadingTracker,
+    /// Still on the green of a color marker the heading was anchored on
+    on_marker: bool,
+    simulate: bool,
+    vision: Vision,
+    last_timestamp: Instant,
+    remaining_sprint: Option<Duration>,
+    remaining_back_panic: Option<BackSteering>,
+    route_target: Option<RouteTarget>,
+    action: RaceAction,
+}
+
+impl RaceController {
+    /// `heading` is zeroed on the start direction.
+    pub fn new(config: &RaceConfig, heading: HeadingTracker, simulate: bool, now: Instant) -> Self {
+        let mut heading = heading;
+        if !config.use_drift_correction() {
+            heading.set_drift_rate(0, now);
+        }
+        Self {
+            config: *config,
+            heading,
+            on_marker: false,
+            simulate,
+            vision: Vision::new(),
+            last_timestamp: now,
+            remaining_sprint: Some(Duration::from_millis(config.sprint_time as u64)),
+            remaining_back_panic: None,
+            route_target: None,
+            action: RaceAction {
+                power: 0,
+                steer: Angle::ZERO,
+            },
+        }
+    }
+
+    pub fn vision(&self) -> &Vision {
+        &self.vision
+    }
+
+    pub fn heading(&self) -> &HeadingTracker {
+        &self.heading
+    }
+
+    /// Re-anchors the heading on a good direction crossing of a color
+    /// marker, unless the car crosses it too skewed to trust.
+    fn anchor_on_marker(&mut self, inputs: &RaceInputs, now: Instant) {
+        let config = &self.config;
+        let crossing = inputs.rgb.detect_good_cross();
+        let maneuvering = self.route_target.is_some() || self.remaining_back_panic.is_some();
+        if config.use_marker_anchor() && crossing && !self.on_marker && !maneuvering {
+            let yaw = inputs.imu.yaw;
+            let error = self.heading.heading(yaw, now) - config.marker_heading();
+            if error.abs() <= MARKER_ANCHOR_MAX_ERROR {
+                self.heading.anchor(yaw, now, config.marker_heading());
+            }
+        }
+        self.on_marker = crossing;
+    }
+
+    pub fn step(&mut self, inputs: &RaceInputs, now: Instant) -> RaceOutput {
+        self.anchor_on_marker(inputs, now);
+
+        let config = &self.config;
+        let dt = (now - self.last_timestamp).max(Duration::from_micros(100));
+        self.last_timestamp = now;
+
+        let imu = &inputs.imu;
+        let is_still = imu.is_still(now, config.still_for());
+        let absolute_heading = Angle::from_imu_value(imu.yaw);
+        let track_heading = self.heading.heading(imu.yaw, now);
+        let current_pitch = Angle::from_imu_value(imu.pitch);
+        let tilt_alert = detect_tilt_alert(current_pitch, Angle::from_imu_value(imu.roll));
+        self.vision.update(&inputs.lasers, config, current_pitch);
+
+        let (relative_target, _target_index, mut power_state, window_borders) =
+            self.vision.compute_target();
+        let steer = relative_target.min(Angle::MAX_STEER).max(Angle::MIN_STEER);
+
+        let is_in_back_panic =
+            if self.route_target.is_none() && self.vision.detect_back_panic(config) {
+                self.remaining_back_panic = Some(BackSteering {
+                    remaining_time: Duration::from_millis(config.back_time as u64),
+                    steer,
+                });
+                true
+            } else {
+                false
+            };
+
+        if self.route_target.is_none() {
+            if config.use_stillness() && self.remaining_sprint.is_none() && !self.simulate {
+                if is_still {
+                    let target_delta = if steer < Angle::ZERO {
+                        Angle::L45
+                    } else {
+                        Angle::R45
+                    };
+                    self.route_target = Some(RouteTarget::new_for_stillness(
+                        config,
+                        track_heading,
+                        target_delta,
+                    ));
+                }
+            }
+
+            if config.use_climb_direction() {
+                if config.detect_climb(current_pitch) {
+                    let delta = (track_heading - config.climb_direction()).abs();
+                    if delta > Angle::R100 {
+                        self.route_target =
+                            Some(RouteTarget::new_for_climbing(config, track_heading));
+                    }
+                }
+            }
+
+            if config.use_color_inversion()
+                && self.remaining_sprint.is_none()
+                && self.remaining_back_panic.is_none()
+            {
+                if inputs.rgb.detect_inversion() {
+                    self.route_target = Some(RouteTarget::new_for_inversion(
+                        config,
+                        track_heading,
+                        self.action.steer,
+                    ));
+                }
+            }
+        }
+
+        let (power, steer, color) = if tilt_alert {
+            (0, Angle::ZERO, RaceColor::Black)
+        } else if let Some(back_steering) = self.remaining_back_panic {
+            self.remaining_sprint = None;
+
+            self.remaining_back_panic = if back_steering.remaining_time > dt {
+                Some(BackSteering {
+                    remaining_time: back_steering.remaining_time - dt,
+                    ..back_steering
+                })
+            } else {
+                None
+            };
+            power_state = LaserStatus::Back;
+            (
+                -config.back_speed,
+                if is_in_back_panic {
+                    -back_steering.steer
+                } else {
+                    Angle::ZERO
+                },
+                RaceColor::Red,
+            )
+        } else if let Some(target) = self.route_target {
+            self.remaining_sprint = None;
+            let color = if target.was_still {
+                RaceColor::Blue
+            } else {
+                RaceColor::Yellow
+            };
+
+            let delta = target.target - track_heading;
+
+            if delta.abs() < Angle::from(10) {
+                self.route_target = None;
+                self.remaining_sprint = Some(config.post_inversion_time());
+                (0, Angle::ZERO, color)
+            } else {
+                let steer = delta.min(Angle::MAX_STEER).max(Angle::MIN_STEER);
+
+                let new_target = if target.remaining_time > dt {
+                    RouteTarget {
+                        remaining_time: target.remaining_time - dt,
+                        ..target
+                    }
+                } else {
+                    RouteTarget {
+                        remaining_time: Duration::from_millis(config.inversion_time as u64),
+                        go_back: !target.go_back,
+                        ..target
+                    }
+                };
+                self.route_target = Some(new_target);
+
+                if new_target.go_back {
+                    (-config.back_speed, -steer, color)
+                } else {
+                    (config.max_speed, steer, color)
+                }
+            }
+        } else if let Some(sprint) = self.remaining_sprint {
+            self.remaining_sprint = if sprint > dt { Some(sprint - dt) } else { None };
+            (config.sprint_speed, steer, RaceColor::White)
+        } else {
+            (
+                config.turn_speed(steer) + config.climb_power_boost(current_pitch),
+                steer,
+                RaceColor::Green,
+            )
+        };
+
+        self.action = RaceAction { power, steer };
+
+        RaceOutput {
+            action: self.action,
+            color,
+            trace: TraceEvent::new(
+                absolute_heading,
+                track_heading,
+                imu,
+                is_in_back_panic,
+                &self.remaining_back_panic,
+                &self.route_target,
+                self.action.steer,
+                self.action.power,
+                is_still,
+                dt,
+            ),
+            vision_target: relative_target,
+            vision_status: power_state,
+            vision_window: window_borders,
+        }
+    }
+}
@@ -0,0 +1,168 @@
+use embassy_time::Duration;
+
+const DETECT_COLOR_AT_LEAST_FOR: Duration = Duration::from_millis(10);
+const DETECT_CROSS_AT_MOST_SINCE: Duration = Duration::from_millis(1500);
+
+pub fn rgb2hsv(r: i32, g: i32, b: i32) -> (i32, i32, i32) {
+    const HUE_DEGREE: i32 = 512;
+    let max = r.max(g).max(b);
+    let min = r.min(g).min(b);
+    let delta = max - min;
+
+    let (h, s) = if delta == 0 {
+        (-1, 0)
+    } else {
+        let h = if r == max {
+            ((g - b) * 60 * HUE_DEGREE) / delta
+        } else if g == max {
+            ((b - r) * 60 * HUE_DEGREE) / delta + 120 * HUE_DEGREE
+        } else if b == max {
+            ((r - g) * 60 * HUE_DEGREE) / delta + 240 * HUE_DEGREE
+        } else {
+            0
+        };
+        let h = if h < 0 { h + 360 * HUE_DEGREE } else { h };
+        let s = (256 * delta - 8) / max;
+
+        (h / HUE_DEGREE, s)
+    };
+
+    (h, s, max)
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct RgbEvent {
+    pub dt: Duration,
+    pub r: u16,
+    pub g: u16,
+    pub b: u16,
+    pub l: u16,
+    pub h: u16,
+    pub s: u16,
+    pub v: u16,
+    pub not_red_for: Duration,
+    pub not_green_for: Duration,
+    pub last_red_for: Duration,
+    pub last_green_for: Duration,
+}
+
+impl RgbEvent {
+    pub fn is_red(&self) -> bool {
+        self.not_red_for == Duration::from_micros(0)
+    }
+
+    pub fn is_green(&self) -> bool {
+        self.not_green_for == Duration::from_micros(0)
+    }
+
+    pub fn detect_inversion(&self) -> bool {
+        self.is_red()
+            && self.last_red_for >= DETECT_COLOR_AT_LEAST_FOR
+            && self.not_green_for <= DETECT_CROSS_AT_MOST_SINCE
+            && self.last_green_for >= DETECT_COLOR_AT_LEAST_FOR
+    }
+
+    pub fn detect_good_cross(&self) -> bool {
+        self.is_green()
+            && self.last_green_for >= DETECT_COLOR_AT_LEAST_FOR
+            && self.not_red_for <= DETECT_CROSS_AT_MOST_SINCE
+            && self.last_red_for >= DETECT_COLOR_AT_LEAST_FOR
+    }
+
+    pub fn empty() -> Self {
+        Self {
+            dt: Duration::from_millis(10),
+            r: 0,
+            g: 0,
+            b: 0,
+            l: 0,
+            h: 0,
+            s: 0,
+            v: 0,
+            not_red_for: Duration::from_ticks(0),
+            not_green_for: Duration::from_ticks(0),
+            last_red_for: Duration::from_ticks(0),
+            last_green_for: Duration::from_ticks(0),
+        }
+    }
+}
+
+const DURATION_ZERO: Duration = Duration::from_secs(0);
+const DURATION_MAX: Duration = Duration::from_secs(60);
+
+/// Classifies raw TCS3472 readings as red or green floor markings and keeps
+/// track of how long each color has (or has not) been seen.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct RgbTracker {
+    not_red_for: Duration,
+    not_green_for: Duration,
+    last_red_for: Duration,
+    last_green_for: Duration,
+}
+
+impl RgbTracker {
+    pub fn new() -> Self {
+        Self {
+            not_red_for: DURATION_MAX,
+            not_green_for: DURATION_MAX,
+            last_red_for: DURATION_ZERO,
+            last_green_for: DURATION_ZERO,
+        }
+    }
+
+    pub fn update(&mut self, r: u16, g: u16, b: u16, l: u16, dt: Duration) -> RgbEvent {
+        let (h, s, v) = rgb2hsv(r as i32, g as i32, b as i32);
+
+        // 100 40 40
+        // 0 90 40
+        const RED_HUE: i32 = 1;
+        const RED_SAT: i32 = 90;
+        const GREEN_HUE: i32 = 110;
+        const GREEN_SAT: i32 = 40;
+        const MIN_VAL: i32 = 40;
+        const HUE_DELTA: i32 = 15;
+        let red_matches = (h >= 0 && h <= RED_HUE + HUE_DELTA || h >= RED_HUE + 360 - HUE_DELTA)
+            && s >= RED_SAT
+            && v >= MIN_VAL;
+        let green_matches = h >= GREEN_HUE - HUE_DELTA
+            && h <= GREEN_HUE + HUE_DELTA
+            && s >= GREEN_SAT
+            && v >= MIN_VAL;
+        let (h, s, v) = (h as u16, s as u16, v as u16);
+
+        if red_matches {
+            if self.not_red_for > DURATION_ZERO {
+                self.last_red_for = DURATION_ZERO;
+            }
+            self.last_red_for = (self.last_red_for + dt).min(DURATION_MAX);
+            self.not_red_for = DURATION_ZERO;
+        } else {
+            self.not_red_for = (self.not_red_for + dt).min(DURATION_MAX);
+        }
+
+        if green_matches {
+            if self.not_green_for > DURATION_ZERO {
+                self.last_green_for = DURATION_ZERO;
+            }
+            self.last_green_for = (self.last_green_for + dt).min(DURATION_MAX);
+            self.not_green_for = DURATION_ZERO;
+        } else {
+            self.not_green_for = (self.not_green_for + dt).min(DURATION_MAX);
+        }
+
+        RgbEvent {
+            dt,
+            r,
+            g,
+            b,
+            l,
+            h,
+            s,
+            v,
+            not_red_for: self.not_red_for,
+            not_green_for: self.not_green_for,
+            last_red_for: self.last_red_for,
+            last_green_for: self.last_green_for,
+        }
+    }
+}
@@ -0,0 +1,132 @@
+//! Motor safety supervisor.
+//!
+//! While the motors are driven, the lasers, the IMU and the motor commands
+//! must keep coming: when one of them is older than its limit the car is
+//! driving blind, so a fault is latched and the motors must stay stopped until
+//! the fault is acknowledged.
+
+use embassy_time::{Duration, Instant};
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct SafetyLimits {
+    pub lasers: Duration,
+    pub imu: Duration,
+    pub motors: Duration,
+}
+
+impl SafetyLimits {
+    pub const fn init() -> Self {
+        Self {
+            lasers: Duration::from_millis(100),
+            imu: Duration::from_millis(100),
+            motors: Duration::from_millis(200),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum FaultCode {
+    LasersStale,
+    ImuStale,
+    MotorsStale,
+}
+
+impl FaultCode {
+    /// Short number shown on the LCD.
+    pub fn code(&self) -> i16 {
+        match self {
+            FaultCode::LasersStale => 1,
+            FaultCode::ImuStale => 2,
+            FaultCode::MotorsStale => 3,
+        }
+    }
+
+    pub fn description(&self) -> &'static str {
+        match self {
+            FaultCode::LasersStale => "LASERS STALE",
+            FaultCode::ImuStale => "IMU STALE",
+            FaultCode::MotorsStale => "MOTORS STALE",
+        }
+    }
+}
+
+pub struct SafetySupervisor {
+    limits: SafetyLimits,
+    last_lasers: Instant,
+    last_imu: Instant,
+    last_motors: Instant,
+    driving: bool,
+    fault: Option<FaultCode>,
+}
+
+impl SafetySupervisor {
+    pub fn new(limits: SafetyLimits, now: Instant) -> Self {
+        Self {
+            limits,
+            last_lasers: now,
+            last_imu: now,
+            last_motors: now,
+            driving: false,
+            fault: None,
+        }
+    }
+
+    pub fn set_limits(&mut self, limits: SafetyLimits) {
+        self.limits = limits;
+    }
+
+    pub fn lasers_seen(&mut self, now: Instant) {
+        self.last_lasers = now;
+    }
+
+    pub fn imu_seen(&mut self, now: Instant) {
+        self.last_imu = now;
+    }
+
+    /// Records a motor command, returning the power that can be applied:
+    /// zero while a fault is latched.
+    pub fn motors_commanded(&mut self, power: i16, now: Instant) -> i16 {
+        self.last_motors = now;
+        self.driving = power != 0;
+        if self.fault.is_some() {
+            0
+        } else {
+            power
+        }
+    }
+
+    /// Checks the input ages, returning a fault when it has just been
+    /// latched (the motors must be stopped right away).
+    pub fn check(&mut self, now: Instant) -> Option<FaultCode> {
+        if self.fault.is_some() || !self.driving {
+            return None;
+        }
+        let fault = if now - self.last_lasers > self.limits.lasers {
+            Some(FaultCode::LasersStale)
+        } else if now - self.last_imu > self.limits.imu {
+            Some(FaultCode::ImuStale)
+        } else if now - self.last_motors > self.limits.motors {
+            Some(FaultCode::MotorsStale)
+        } else {
+            None
+        };
+        if fault.is_some() {
+            self.fault = fault;
+            self.driving = false;
+        }
+        fault
+    }
+
+    pub fn fault(&self) -> Option<FaultCode> {
+        self.fault
+    }
+
+    /// Clears the latched fault: the motors move again with the next command.
+    pub fn acknowledge(&mut self, now: Instant) {
+        self.fault = None;
+        self.driving = false;
+        self.last_lasers = now;
+        self.last_imu = now;
+        self.last_motors = now;
+    }
+}
@@ -0,0 +1,110 @@
+//! Laser scan scheduling.
+//!
+//! Reading every channel for every frame makes the center beam as slow as
+//! the 60 degrees ones. The scheduler gives each beam a period in frames:
+//! when the car is fast the center is read every frame and the sides less
+//! often, and the lower tier is read less often unless `Vision` suspects a
+//! slope. Each frame is published on its own, with only the scheduled
+//! channels updated (see `RawLaserReadings::updated`).
+
+use crate::{
+    geometry::{laser_positions, LaserBeam, LaserTier},
+    lasers::RAW_LASERS_COUNT,
+    vision::LaserSidePosition,
+};
+
+/// Power at and above which side beams are read less often.
+pub const LASER_SCAN_FAST_POWER: i16 = 4000;
+/// Longest period of a beam, in frames.
+pub const LASER_SCAN_MAX_PERIOD: u8 = 4;
+
+/// What the race logic knows that matters for the scan.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct LaserScanHints {
+    /// Motor power of the last race step
+    pub power: i16,
+    /// Some laser position sees a slope
+    pub slope: bool,
+}
+
+impl LaserScanHints {
+    /// Standing still on flat ground, as outside of a race.
+    pub const fn init() -> Self {
+        Self {
+            power: 0,
+            slope: false,
+        }
+    }
+}
+
+impl Default for LaserScanHints {
+    fn default() -> Self {
+        Self::init()
+    }
+}
+
+/// Picks the channels to read for each frame.
+pub struct LaserScanScheduler {
+    /// `None` for channels without a beam, which are never read
+    beams: [Option<(LaserTier, LaserSidePosition)>; RAW_LASERS_COUNT],
+    frame: u8,
+}
+
+impl LaserScanScheduler {
+    /// Panics when the beam table is not valid (see `laser_positions`).
+    pub fn new(beams: &[LaserBeam]) -> Self {
+        let positions = laser_positions(beams).expect("invalid laser geometry");
+        let mut scheduled = [None; RAW_LASERS_COUNT];
+        for position in positions.iter() {
+            for beam in [position.upper, position.lower].into_iter().flatten() {
+                scheduled[beam.channel as usize] = Some((beam.tier, position.side_position()));
+            }
+        }
+        Self {
+            beams: scheduled,
+            frame: 0,
+        }
+    }
+
+    /// Frames between two reads of a channel, `None` if it is never read.
+    pub fn period(&self, channel: usize, hints: &LaserScanHints) -> Option<u8> {
+        let (tier, position) = self.beams[channel]?;
+        let period = if hints.power >= LASER_SCAN_FAST_POWER {
+            match position {
+                LaserSidePosition::Center => 1,
+                LaserSidePosition::Side30 => 2,
+                LaserSidePosition::Side60 => 4,
+            }
+        } else {
+            1
+        };
+        let period = if tier == LaserTier::Lower && !hints.slope {
+            period * 2
+        } else {
+            period
+        };
+        Some(period.min(LASER_SCAN_MAX_PERIOD))
+    }
+
+    /// Bit mask of the channels to read for the next frame, never empty
+    /// unless there are no beams at all.
+    pub fn next(&mut self, hints: &LaserScanHints) -> u8 {
+        let mut mask = 0;
+        for _ in 0..LASER_SCAN_MAX_PERIOD {
+            for channel in 0..RAW_LASERS_COUNT {
+                if let Some(period) = self.period(channel, hints) {
+                    // Spread the channels with the same period over the frames
+                    let phase = (self.frame as usize + channel) % period as usize;
+                    if phase == 0 {
+                        mask |= 1 << channel;
+                    }
+                }
+            }
+            self.frame = (self.frame + 1) % LASER_SCAN_MAX_PERIOD;
+            if mask != 0 {
+                break;
+            }
+        }
+        mask
+    }
+}
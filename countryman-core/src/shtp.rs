@@ -0,0 +1,241 @@
+//! Sensor Hub Transport Protocol, as spoken by the BNO08x IMUs.
+//!
+//! Every packet starts with a 4 byte header: the packet length (header
+//! included, bit 15 set on continuations), the channel and a sequence number
+//! kept per channel and direction. Over UART each packet is wrapped in an
+//! HDLC-like frame: a 0x7E flag, a protocol byte (1 for SHTP), the stuffed
+//! packet bytes and another flag. Over I2C the header is read first, then the
+//! whole packet.
+
+use arrayvec::ArrayVec;
+use embedded_hal_async::i2c::I2c;
+
+pub const SHTP_HEADER_SIZE: usize = 4;
+/// Longest packet kept: longer ones (like the start up advertisement) are
+/// dropped.
+pub const SHTP_MAX_PACKET: usize = 256;
+/// Longest packet sent by the host.
+pub const SHTP_MAX_COMMAND: usize = 32;
+
+pub const SHTP_CHANNEL_COMMAND: u8 = 0;
+pub const SHTP_CHANNEL_EXECUTABLE: u8 = 1;
+pub const SHTP_CHANNEL_CONTROL: u8 = 2;
+pub const SHTP_CHANNEL_REPORTS: u8 = 3;
+pub const SHTP_CHANNEL_WAKE_REPORTS: u8 = 4;
+pub const SHTP_CHANNELS: usize = 6;
+
+pub const SHTP_I2C_ADDR: u8 = 0x4A;
+
+const UART_FLAG: u8 = 0x7E;
+const UART_ESCAPE: u8 = 0x7D;
+const UART_ESCAPE_XOR: u8 = 0x20;
+const UART_PROTOCOL_SHTP: u8 = 0x01;
+
+pub type ShtpPacket = ArrayVec<u8, SHTP_MAX_PACKET>;
+pub type ShtpCommand = ArrayVec<u8, SHTP_MAX_COMMAND>;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct ShtpHeader {
+    /// Header included
+    pub length: usize,
+    pub channel: u8,
+    pub sequence: u8,
+}
+
+impl ShtpHeader {
+    pub fn parse(bytes: &[u8]) -> Option<Self> {
+        if bytes.len() < SHTP_HEADER_SIZE {
+            return None;
+        }
+        Some(Self {
+            length: (u16::from_le_bytes([bytes[0], bytes[1]]) & 0x7fff) as usize,
+            channel: bytes[2],
+            sequence: bytes[3],
+        })
+    }
+}
+
+/// Builds the packets sent to the hub, numbering them per channel.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct ShtpWriter {
+    sequences: [u8; SHTP_CHANNELS],
+}
+
+impl ShtpWriter {
+    pub const fn new() -> Self {
+        Self {
+            sequences: [0; SHTP_CHANNELS],
+        }
+    }
+
+    /// Panics when the payload does not fit in `SHTP_MAX_COMMAND`.
+    pub fn packet(&mut self, channel: u8, payload: &[u8]) -> ShtpCommand {
+        let length = (SHTP_HEADER_SIZE + payload.len()) as u16;
+        let sequence = &mut self.sequences[channel as usize];
+        let mut packet = ShtpCommand::new();
+        packet
+            .try_extend_from_slice(&length.to_le_bytes())
+            .expect("SHTP command too long");
+        packet.push(channel);
+        packet.push(*sequence);
+        packet
+            .try_extend_from_slice(payload)
+            .expect("SHTP command too long");
+        *sequence = sequence.wrapping_add(1);
+        packet
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum UartState {
+    /// Waiting for a flag
+    Idle,
+    /// After a flag, the protocol byte or more flags
+    Protocol,
+    Data,
+    Escape,
+    /// Dropping a frame that is too long or not SHTP
+    Skip,
+}
+
+/// Reassembles SHTP packets from the UART byte stream.
+pub struct ShtpUartDecoder {
+    state: UartState,
+    packet: ShtpPacket,
+    /// Frames dropped because they were too long or malformed
+    pub dropped: u32,
+}
+
+impl ShtpUartDecoder {
+    pub const fn new() -> Self {
+        Self {
+            state: UartState::Idle,
+            packet: ShtpPacket::new_const(),
+            dropped: 0,
+        }
+    }
+
+    /// The last complete packet, header included.
+    pub fn packet(&self) -> &[u8] {
+        &self.packet
+    }
+
+    /// Returns `true` when a byte completes a packet.
+    pub fn update(&mut self, received: u8) -> bool {
+        match (self.state, received) {
+            (UartState::Idle, UART_FLAG) => {
+                self.state = UartState::Protocol;
+                false
+            }
+            (UartState::Idle, _) => false,
+            (UartState::Protocol, UART_FLAG) => false,
+            (UartState::Protocol, UART_PROTOCOL_SHTP) => {
+                self.packet.clear();
+                self.state = UartState::Data;
+                false
+            }
+            (UartState::Protocol, _) => {
+                self.state = UartState::Skip;
+                false
+            }
+            (UartState::Data, UART_FLAG) => {
+                self.state = UartState::Protocol;
+                match ShtpHeader::parse(&self.packet) {
+                    Some(header) if header.length == self.packet.len() => true,
+                    _ => {
+                        self.dropped += 1;
+                        false
+                    }
+                }
+            }
+            (UartState::Data, UART_ESCAPE) => {
+                self.state = UartState::Escape;
+                false
+            }
+            (UartState::Data, byte) | (UartState::Escape, byte) => {
+                let byte = byte ^ self.escape_mask();
+                self.state = UartState::Data;
+                if self.packet.try_push(byte).is_err() {
+                    self.dropped += 1;
+                    self.state = UartState::Skip;
+                }
+                false
+            }
+            (UartState::Skip, UART_FLAG) => {
+                self.state = UartState::Protocol;
+                false
+            }
+            (UartState::Skip, _) => false,
+        }
+    }
+
+    fn escape_mask(&self) -> u8 {
+        if self.state == UartState::Escape {
+            UART_ESCAPE_XOR
+        } else {
+            0
+        }
+    }
+}
+
+impl Default for ShtpUartDecoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Wraps a packet in a UART frame.
+pub fn shtp_uart_frame(packet: &[u8]) -> ArrayVec<u8, { SHTP_MAX_COMMAND * 2 + 3 }> {
+    let mut frame = ArrayVec::new();
+    frame.push(UART_FLAG);
+    frame.push(UART_PROTOCOL_SHTP);
+    for byte in packet {
+        if *byte == UART_FLAG || *byte == UART_ESCAPE {
+            frame.push(UART_ESCAPE);
+            frame.push(byte ^ UART_ESCAPE_XOR);
+        } else {
+            frame.push(*byte);
+        }
+    }
+    frame.push(UART_FLAG);
+    frame
+}
+
+/// Reads the next packet over I2C into `packet`, returning its header
+/// (`length` 0 when the hub has nothing to send). Packets longer than
+/// `SHTP_MAX_PACKET` are read in full but only their beginning is kept.
+pub async fn shtp_i2c_read<I2C: I2c>(
+    i2c: &mut I2C,
+    address: u8,
+    packet: &mut ShtpPacket,
+) -> Result<ShtpHeader, I2C::Error> {
+    let mut header = [0u8; SHTP_HEADER_SIZE];
+    i2c.read(address, &mut header).await?;
+    let parsed = ShtpHeader::parse(&header).unwrap_or(ShtpHeader {
+        length: 0,
+        channel: 0,
+        sequence: 0,
+    });
+    packet.clear();
+    if parsed.length <= SHTP_HEADER_SIZE {
+        return Ok(ShtpHeader {
+            length: 0,
+            ..parsed
+        });
+    }
+    // Every read starts with the header again
+    let length = parsed.length.min(SHTP_MAX_PACKET);
+    for _ in 0..length {
+        packet.push(0);
+    }
+    i2c.read(address, packet).await?;
+    Ok(parsed)
+}
+
+pub async fn shtp_i2c_write<I2C: I2c>(
+    i2c: &mut I2C,
+    address: u8,
+    packet: &[u8],
+) -> Result<(), I2C::Error> {
+    i2c.write(address, packet).await
+}
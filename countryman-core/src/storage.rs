@@ -0,0 +1,242 @@
+//! Race profiles persistence on NOR flash.
+//!
+//! All the profiles are stored as a single record at the start of a reserved
+//! flash sector:
+//!
+//! | offset | size | content                                        |
+//! |--------|------|------------------------------------------------|
+//! | 0      | 4    | magic, `CONFIG_MAGIC`                          |
+//! | 4      | 2    | layout version, `CONFIG_VERSION`               |
+//! | 6      | 2    | number of config entries `n`                   |
+//! | 8      | 1    | number of profiles                             |
+//! | 9      | 1    | active profile                                 |
+//! | 10     | 2    | reserved                                       |
+//! | 12     | ...  | `MAX_PROFILES` slots, each with the name       |
+//! |        |      | (`PROFILE_NAME_SIZE` bytes, zero padded) and   |
+//! |        |      | `n` entry values in `RaceConfigEntry` order    |
+//! | ...    | 32   | laser calibration, offset and scale (i16) for  |
+//! |        |      | each of the `RAW_LASERS_COUNT` channels        |
+//! | end-4  | 4    | CRC-32 of everything before it                 |
+//!
+//! All values are little endian. A record with a different magic, version or
+//! entry count, or a bad CRC, is ignored and the default profiles are used
+//! instead.
+
+use embedded_storage::nor_flash::NorFlash;
+
+use crate::configuration::{RaceConfig, RaceConfigEntry, RACE_CONFIG_ENTRY_END};
+use crate::lasers::{LaserCalibration, LasersCalibration, RAW_LASERS_COUNT};
+use crate::profiles::{RaceProfile, RaceProfiles, MAX_PROFILES, PROFILE_NAME_SIZE};
+
+pub const CONFIG_MAGIC: u32 = 0x4643_4d43; // "CMCF"
+/// Bump this whenever the meaning of the stored entries changes.
+pub const CONFIG_VERSION: u16 = 7;
+
+const HEADER_SIZE: usize = 12;
+const PROFILE_SIZE: usize = PROFILE_NAME_SIZE + 2 * RACE_CONFIG_ENTRY_END;
+const CALIBRATION_OFFSET: usize = HEADER_SIZE + MAX_PROFILES * PROFILE_SIZE;
+const CALIBRATION_SIZE: usize = 4 * RAW_LASERS_COUNT;
+const CRC_SIZE: usize = 4;
+pub const CONFIG_RECORD_SIZE: usize = CALIBRATION_OFFSET + CALIBRATION_SIZE + CRC_SIZE;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ConfigStorageError {
+    Flash,
+    BadMagic,
+    BadVersion(u16),
+    BadLength(u16),
+    BadCrc,
+    BadProfiles,
+}
+
+impl ConfigStorageError {
+    pub fn description(&self) -> &'static str {
+        match self {
+            ConfigStorageError::Flash => "flash error",
+            ConfigStorageError::BadMagic => "no config stored",
+            ConfigStorageError::BadVersion(_) => "old config layout",
+            ConfigStorageError::BadLength(_) => "wrong config size",
+            ConfigStorageError::BadCrc => "config checksum mismatch",
+            ConfigStorageError::BadProfiles => "bad profile table",
+        }
+    }
+}
+
+/// CRC-32 (IEEE 802.3), bitwise: the record is small and rarely read.
+pub fn crc32(data: &[u8]) -> u32 {
+    let mut crc = 0xffff_ffffu32;
+    for byte in data {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xedb8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+pub fn encode_profiles(profiles: &RaceProfiles) -> [u8; CONFIG_RECORD_SIZE] {
+    let mut record = [0u8; CONFIG_RECORD_SIZE];
+    record[0..4].copy_from_slice(&CONFIG_MAGIC.to_le_bytes());
+    record[4..6].copy_from_slice(&CONFIG_VERSION.to_le_bytes());
+    record[6..8].copy_from_slice(&(RACE_CONFIG_ENTRY_END as u16).to_le_bytes());
+    record[8] = profiles.len() as u8;
+    record[9] = profiles.active_index() as u8;
+    for (slot, profile) in profiles.iter().enumerate() {
+        let offset = HEADER_SIZE + slot * PROFILE_SIZE;
+        record[offset..offset + profile.name.len()].copy_from_slice(profile.name.as_bytes());
+        for (index, entry) in RaceConfigEntry::iter().enumerate() {
+            let offset = offset + PROFILE_NAME_SIZE + index * 2;
+            record[offset..offset + 2].copy_from_slice(&profile.config.get(entry).to_le_bytes());
+        }
+    }
+    for (channel, calibration) in profiles.calibration().channels.iter().enumerate() {
+        let offset = CALIBRATION_OFFSET + channel * 4;
+        record[offset..offset + 2].copy_from_slice(&calibration.offset.to_le_bytes());
+        record[offset + 2..offset + 4].copy_from_slice(&calibration.scale.to_le_bytes());
+    }
+    let crc_offset = CONFIG_RECORD_SIZE - CRC_SIZE;
+    let crc = crc32(&record[..crc_offset]);
+    record[crc_offset..].copy_from_slice(&crc.to_le_bytes());
+    record
+}
+
+pub fn decode_profiles(
+    record: &[u8; CONFIG_RECORD_SIZE],
+) -> Result<RaceProfiles, ConfigStorageError> {
+    let u16_at = |offset: usize| u16::from_le_bytes([record[offset], record[offset + 1]]);
+
+    let magic = u32::from_le_bytes([record[0], record[1], record[2], record[3]]);
+    if magic != CONFIG_MAGIC {
+        return Err(ConfigStorageError::BadMagic);
+    }
+    let version = u16_at(4);
+    if version != CONFIG_VERSION {
+        return Err(ConfigStorageError::BadVersion(version));
+    }
+    let count = u16_at(6);
+    if count as usize != RACE_CONFIG_ENTRY_END {
+        return Err(ConfigStorageError::BadLength(count));
+    }
+    let crc_offset = CONFIG_RECORD_SIZE - CRC_SIZE;
+    let crc = u32::from_le_bytes([
+        record[crc_offset],
+        record[crc_offset + 1],
+        record[crc_offset + 2],
+        record[crc_offset + 3],
+    ]);
+    if crc != crc32(&record[..crc_offset]) {
+        return Err(ConfigStorageError::BadCrc);
+    }
+
+    let profiles_count = record[8] as usize;
+    let active = record[9] as usize;
+    if profiles_count == 0 || profiles_count > MAX_PROFILES || active >= profiles_count {
+        return Err(ConfigStorageError::BadProfiles);
+    }
+
+    let mut profiles = RaceProfiles::empty();
+    for slot in 0..profiles_count {
+        let offset = HEADER_SIZE + slot * PROFILE_SIZE;
+        let name = &record[offset..offset + PROFILE_NAME_SIZE];
+        let name_len = name
+            .iter()
+            .position(|c| *c == 0)
+            .unwrap_or(PROFILE_NAME_SIZE);
+        let name =
+            core::str::from_utf8(&name[..name_len]).map_err(|_| ConfigStorageError::BadProfiles)?;
+
+        let mut config = RaceConfig::init();
+        for (index, entry) in RaceConfigEntry::iter().enumerate() {
+            config.set(entry, u16_at(offset + PROFILE_NAME_SIZE + index * 2) as i16);
+        }
+        profiles.push(RaceProfile::new(name, config));
+    }
+    profiles.select(active);
+
+    let mut calibration = LasersCalibration::init();
+    for (channel, correction) in calibration.channels.iter_mut().enumerate() {
+        let offset = CALIBRATION_OFFSET + channel * 4;
+        *correction = LaserCalibration {
+            offset: u16_at(offset) as i16,
+            scale: u16_at(offset + 2) as i16,
+        };
+    }
+    profiles.set_calibration(calibration);
+    Ok(profiles)
+}
+
+/// Largest `NorFlash::WRITE_SIZE` `ConfigStorage` can pad the record to.
+pub const CONFIG_MAX_WRITE_SIZE: usize = 256;
+
+/// The race profiles record, kept in one erase sector of a NOR flash.
+pub struct ConfigStorage<F: NorFlash> {
+    flash: F,
+    offset: u32,
+}
+
+impl<F: NorFlash> ConfigStorage<F> {
+    /// `offset` must be aligned to `F::ERASE_SIZE`, and the sector starting
+    /// there must not be used for anything else.
+    pub fn new(flash: F, offset: u32) -> Self {
+        // The padded tail of the record is written from a fixed buffer
+        assert!(F::WRITE_SIZE <= CONFIG_MAX_WRITE_SIZE);
+        Self { flash, offset }
+    }
+
+    pub fn load(&mut self) -> Result<RaceProfiles, ConfigStorageError> {
+        let mut record = [0u8; CONFIG_RECORD_SIZE];
+        self.flash
+            .read(self.offset, &mut record)
+            .map_err(|_| ConfigStorageError::Flash)?;
+        decode_profiles(&record)
+    }
+
+    /// Loads the stored profiles, falling back to `RaceProfiles::init()`.
+    pub fn load_or_default(&mut self) -> RaceProfiles {
+        match self.load() {
+            Ok(profiles) => {
+                log::info!("config loaded from flash");
+                profiles
+            }
+            Err(err) => {
+                log::info!("config not loaded ({}), using defaults", err.description());
+                RaceProfiles::init()
+            }
+        }
+    }
+
+    /// Stores `profiles`, skipping the erase cycle if they are already stored.
+    pub fn save(&mut self, profiles: &RaceProfiles) -> Result<(), ConfigStorageError> {
+        if self.load().as_ref() == Ok(profiles) {
+            return Ok(());
+        }
+
+        let record = encode_profiles(profiles);
+        // Writes must cover whole WRITE_SIZE words: the record is written up
+        // to the last whole word, then the rest padded with erased bytes.
+        let write_size = F::WRITE_SIZE.max(1);
+        let aligned = CONFIG_RECORD_SIZE / write_size * write_size;
+        let tail = CONFIG_RECORD_SIZE - aligned;
+
+        self.flash
+            .erase(self.offset, self.offset + F::ERASE_SIZE as u32)
+            .map_err(|_| ConfigStorageError::Flash)?;
+        if aligned > 0 {
+            self.flash
+                .write(self.offset, &record[..aligned])
+                .map_err(|_| ConfigStorageError::Flash)?;
+        }
+        if tail > 0 {
+            let mut buffer = [0xffu8; CONFIG_MAX_WRITE_SIZE];
+            buffer[..tail].copy_from_slice(&record[aligned..]);
+            self.flash
+                .write(self.offset + aligned as u32, &buffer[..write_size])
+                .map_err(|_| ConfigStorageError::Flash)?;
+        }
+        Ok(())
+    }
+}
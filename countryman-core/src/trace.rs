@@ -1,21 +1,10 @@
-use embassy_sync::{blocking_mutex::raw::CriticalSectionRawMutex, signal::Signal};
-use embassy_time::{Duration, Instant};
-use static_cell::make_static;
+use embassy_time::Duration;
 
 use crate::{
     imu::ImuData,
     race::{Angle, BackSteering, RouteTarget},
 };
 
-pub static TRACE: Signal<CriticalSectionRawMutex, TraceCommand> = Signal::new();
-
-#[derive(Clone, Copy, PartialEq, Eq)]
-pub enum TraceCommand {
-    Clear,
-    Print,
-    Push(TraceEvent),
-}
-
 #[derive(Clone, Copy, PartialEq, Eq)]
 pub enum TraceEventKind {}
 
@@ -51,7 +40,7 @@ impl TraceEvent {
         target: &Option<RouteTarget>,
         steer: Angle,
         speed: i16,
-        now: Instant,
+        is_still: bool,
         dt: Duration,
     ) -> Self {
         Self {
@@ -75,7 +64,7 @@ impl TraceEvent {
                 .unwrap_or(0),
             target: target.map(|t| t.target).unwrap_or(Angle::ZERO),
             target_back: target.map(|t| t.go_back).unwrap_or(false),
-            stillness: imu_data.is_still(now),
+            stillness: is_still,
             dt_us: dt.as_micros() as u32,
         }
     }
@@ -106,82 +95,3 @@ impl TraceEvent {
         );
     }
 }
-
-const EMPTY_EVENT: TraceEvent = TraceEvent {
-    absolute_heading: Angle::ZERO,
-    track_heading: Angle::ZERO,
-    steer: Angle::ZERO,
-    speed: 0,
-    roll: Angle::ZERO,
-    pitch: Angle::ZERO,
-    yaw: Angle::ZERO,
-    forward: 0,
-    side: 0,
-    vertical: 0,
-    has_back_panic: false,
-    remaining_back_panic_ms: 0,
-    has_target: false,
-    remaining_target_ms: 0,
-    target: Angle::ZERO,
-    target_back: false,
-    stillness: false,
-    dt_us: 0,
-};
-
-const TRACE_EVENTS: usize = 3000;
-
-struct TraceData {
-    pub start: usize,
-    pub end: usize,
-    pub events: [TraceEvent; TRACE_EVENTS],
-}
-
-impl TraceData {
-    pub fn clear(&mut self) {
-        self.start = 0;
-        self.end = 0;
-    }
-
-    pub fn push(&mut self, event: TraceEvent) {
-        let new_end = (self.end + 1) % TRACE_EVENTS;
-        if self.start == new_end {
-            self.start = (self.start + 1) % TRACE_EVENTS;
-        }
-        self.events[self.end] = event;
-        self.end = new_end;
-    }
-
-    pub async fn print(&self) {
-        log::info!("printing trace: [{} {}]", self.start, self.end);
-        let mut current = self.start;
-        let mut elapsed = Duration::from_millis(0);
-        loop {
-            self.events[current].print(current, elapsed);
-            embassy_time::Timer::after(Duration::from_millis(16)).await;
-            elapsed += Duration::from_micros(self.events[current].dt_us as u64);
-            current = (current + 1) % TRACE_EVENTS;
-            if current == self.end {
-                break;
-            }
-        }
-    }
-}
-
-pub async fn trace_task() {
-    let data: &'static mut TraceData = make_static!(TraceData {
-        start: 0,
-        end: 0,
-        events: [EMPTY_EVENT; TRACE_EVENTS],
-    });
-
-    loop {
-        match TRACE.wait().await {
-            TraceCommand::Clear => {
-                log::info!("clearing trace");
-                data.clear();
-            }
-            TraceCommand::Print => data.print().await,
-            TraceCommand::Push(event) => data.push(event),
-        }
-    }
-}
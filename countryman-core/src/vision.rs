@@ -0,0 +1,464 @@
+use arrayvec::ArrayVec;
+use embassy_time::{Duration, Instant};
+
+use crate::{
+    configuration::RaceConfig,
+    geometry::{laser_positions, LaserBeam, LaserPosition, LASER_GEOMETRY, MAX_LASER_POSITIONS},
+    lasers::RawLaserReadings,
+    race::Angle,
+};
+
+pub const LASER_OVERFLOW: u16 = 1200;
+/// How long the last good value of a failing laser channel is kept.
+pub const LASER_HOLD: Duration = Duration::from_millis(100);
+
+/// Which set of alert and back distances applies to a laser position.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[repr(u8)]
+pub enum LaserSidePosition {
+    Center,
+    Side30,
+    Side60,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[repr(u8)]
+pub enum LaserStatus {
+    Back,
+    Alert,
+    Regular,
+    Overflow,
+}
+
+impl LaserStatus {
+    pub fn from_value(value: u16, position: LaserSidePosition, config: &RaceConfig) -> Self {
+        if value >= LASER_OVERFLOW {
+            Self::Overflow
+        } else if value > config.alert_distance(position) {
+            Self::Regular
+        } else if value > config.back_distance(position) {
+            Self::Alert
+        } else {
+            Self::Back
+        }
+    }
+
+    pub fn is_ok(self) -> bool {
+        match self {
+            LaserStatus::Back | LaserStatus::Alert => false,
+            LaserStatus::Regular | LaserStatus::Overflow => true,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            LaserStatus::Back => "BACK",
+            LaserStatus::Alert => "ALRT",
+            LaserStatus::Regular => "REGL",
+            LaserStatus::Overflow => "OVER",
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct LaserData {
+    pub upper: u16,
+    pub lower: u16,
+    pub angle: Angle,
+    pub position: LaserSidePosition,
+    pub upper_beam: Option<LaserBeam>,
+    pub lower_beam: Option<LaserBeam>,
+    pub status: LaserStatus,
+    pub slope: bool,
+    /// When the upper and lower channels last had a valid reading
+    pub upper_valid_at: Option<Instant>,
+    pub lower_valid_at: Option<Instant>,
+    /// Both channels failed for longer than `LASER_HOLD`
+    pub missing: bool,
+}
+
+impl LaserData {
+    pub fn new(position: &LaserPosition) -> Self {
+        Self {
+            upper: LASER_OVERFLOW,
+            lower: LASER_OVERFLOW,
+            angle: position.angle,
+            position: position.side_position(),
+            upper_beam: position.upper,
+            lower_beam: position.lower,
+            status: LaserStatus::Overflow,
+            slope: false,
+            upper_valid_at: None,
+            lower_valid_at: None,
+            missing: false,
+        }
+    }
+
+    /// Reads the channel of a beam, measured from the front bumper, holding
+    /// its previous value for `LASER_HOLD` when the read failed; `None` when
+    /// it is not usable. Also returns when the value was read.
+    fn read_channel(
+        raw_readings: &RawLaserReadings,
+        beam: Option<LaserBeam>,
+        previous: u16,
+        valid_at: &mut Option<Instant>,
+    ) -> Option<(u16, Instant)> {
+        let beam = beam?;
+        let index = beam.channel as usize;
+        if raw_readings.is_valid(index) {
+            let read_at = raw_readings.read_at[index];
+            *valid_at = Some(read_at);
+            let value = raw_readings.values[index];
+            if value >= LASER_OVERFLOW {
+                Some((value, read_at))
+            } else {
+                Some(((value as i32 + beam.forward as i32).max(0) as u16, read_at))
+            }
+        } else {
+            valid_at
+                .filter(|at| raw_readings.timestamp - *at <= LASER_HOLD)
+                .map(|at| (previous, at))
+        }
+    }
+
+    pub fn value(&self) -> u16 {
+        if self.slope {
+            LASER_OVERFLOW
+        } else {
+            self.lower.min(self.upper)
+        }
+    }
+
+    pub fn update(&mut self, raw_readings: &RawLaserReadings, config: &RaceConfig, pitch: Angle) {
+        let lower = Self::read_channel(
+            raw_readings,
+            self.lower_beam,
+            self.lower,
+            &mut self.lower_valid_at,
+        );
+        let upper = Self::read_channel(
+            raw_readings,
+            self.upper_beam,
+            self.upper,
+            &mut self.upper_valid_at,
+        );
+        let (lower, upper, slope) = match (lower, upper) {
+            // Beams read far apart do not see the same obstacle: the fresher
+            // one is used alone
+            (Some((lower, lower_at)), Some((upper, upper_at)))
+                if lower_at + LASER_HOLD < upper_at || upper_at + LASER_HOLD < lower_at =>
+            {
+                let value = if lower_at > upper_at { lower } else { upper };
+                (value, value, false)
+            }
+            (Some((lower, _)), Some((upper, _))) => {
+                let slope_delta = config.slope_distance_delta as u16;
+                let slope = (upper <= (lower + slope_delta) && upper >= (lower + slope_delta / 2))
+                    || (config.detect_downhill(pitch) && upper >= (lower + slope_delta / 2));
+                (lower, upper, slope)
+            }
+            // A single channel cannot tell a slope from a wall
+            (Some((value, _)), None) | (None, Some((value, _))) => (value, value, false),
+            (None, None) => {
+                self.missing = true;
+                return;
+            }
+        };
+        self.missing = false;
+        self.lower = lower;
+        self.upper = upper;
+        self.slope = slope;
+        self.status = if slope {
+            LaserStatus::Overflow
+        } else {
+            LaserStatus::from_value(self.value(), self.position, config)
+        };
+    }
+
+    pub fn copy_status(&mut self, other: &Self) {
+        self.upper = other.upper;
+        self.lower = other.lower;
+        self.slope = other.slope;
+        self.status = other.status;
+    }
+
+    /// Stands in for a missing laser with the reading of a neighbour.
+    fn fill_from(&mut self, other: &Self, config: &RaceConfig) {
+        self.upper = other.value();
+        self.lower = other.value();
+        self.slope = false;
+        self.status = LaserStatus::from_value(self.value(), self.position, config);
+    }
+
+    #[allow(unused)]
+    pub fn ch1(&self) -> char {
+        if self.slope {
+            'S'
+        } else {
+            match self.status {
+                LaserStatus::Back => '!',
+                LaserStatus::Alert => 'a',
+                LaserStatus::Regular => ' ',
+                LaserStatus::Overflow => '^',
+            }
+        }
+    }
+    #[allow(unused)]
+    pub fn ch2(&self) -> char {
+        unsafe { char::from_u32_unchecked(('0' as u32) + ((self.value() as u32 / 100) % 10)) }
+    }
+    #[allow(unused)]
+    pub fn ch3(&self) -> char {
+        unsafe { char::from_u32_unchecked(('0' as u32) + ((self.value() as u32 / 10) % 10)) }
+    }
+    #[allow(unused)]
+    pub fn ch4(&self) -> char {
+        unsafe { char::from_u32_unchecked(('0' as u32) + ((self.value() as u32 / 1) % 10)) }
+    }
+}
+
+/// Laser positions from left to right, as described by a beam table (see
+/// `geometry`).
+pub struct Vision {
+    pub lasers: ArrayVec<LaserData, MAX_LASER_POSITIONS>,
+    center: usize,
+}
+
+impl Vision {
+    /// Vision for the current chassis, `LASER_GEOMETRY`.
+    pub fn new() -> Self {
+        Self::with_geometry(LASER_GEOMETRY)
+    }
+
+    /// Panics when the beam table is not valid (see `laser_positions`).
+    pub fn with_geometry(beams: &[LaserBeam]) -> Self {
+        let positions = laser_positions(beams).expect("invalid laser geometry");
+        let lasers: ArrayVec<LaserData, MAX_LASER_POSITIONS> =
+            positions.iter().map(LaserData::new).collect();
+        let center = lasers
+            .iter()
+            .enumerate()
+            .min_by_key(|(_, laser)| laser.angle.abs())
+            .map(|(index, _)| index)
+            .unwrap_or(0);
+        Self { lasers, center }
+    }
+
+    /// Index of the laser closest to straight ahead.
+    pub fn center(&self) -> usize {
+        self.center
+    }
+
+    pub fn update(&mut self, raw_readings: &RawLaserReadings, config: &RaceConfig, pitch: Angle) {
+        for laser in self.lasers.iter_mut() {
+            laser.update(raw_readings, config, pitch);
+        }
+        self.fill_missing(config);
+    }
+
+    /// Missing side lasers take the reading of the next laser towards the
+    /// center, a missing center laser the nearest of its two neighbours.
+    fn fill_missing(&mut self, config: &RaceConfig) {
+        let center = self.center;
+        if self.lasers[center].missing {
+            let left = center.checked_sub(1).filter(|i| !self.lasers[*i].missing);
+            let right =
+                Some(center + 1).filter(|i| *i < self.lasers.len() && !self.lasers[*i].missing);
+            let nearest = match (left, right) {
+                (Some(left), Some(right)) => {
+                    if self.lasers[right].value() < self.lasers[left].value() {
+                        Some(right)
+                    } else {
+                        Some(left)
+                    }
+                }
+                (left, right) => left.or(right),
+            };
+            if let Some(nearest) = nearest {
+                let other = self.lasers[nearest];
+                self.lasers[center].fill_from(&other, config);
+            }
+        }
+        for side in (0..center).rev() {
+            if self.lasers[side].missing {
+                let other = self.lasers[side + 1];
+                self.lasers[side].fill_from(&other, config);
+            }
+        }
+        for side in center + 1..self.lasers.len() {
+            if self.lasers[side].missing {
+                let other = self.lasers[side - 1];
+                self.lasers[side].fill_from(&other, config);
+            }
+        }
+    }
+
+    /// Some laser position sees a slope.
+    pub fn has_slope(&self) -> bool {
+        self.lasers.iter().any(|l| l.slope)
+    }
+
+    /// Laser positions without any working channel.
+    pub fn missing_count(&self) -> usize {
+        self.lasers.iter().filter(|l| l.missing).count()
+    }
+
+    pub fn compute_target(&self) -> (Angle, usize, LaserStatus, Option<(usize, usize)>) {
+        self.compute_target_with_windows()
+    }
+
+    fn find_best_index(&self) -> usize {
+        self.lasers
+            .iter()
+            .map(|l| l.value())
+            .enumerate()
+            .fold((0usize, 0u16), |(best_i, best_v), (ci, cv)| {
+                if cv > best_v {
+                    (ci, cv)
+                } else {
+                    (best_i, best_v)
+                }
+            })
+            .0
+    }
+
+    fn find_best_extreme(&self, best_index: usize) -> Angle {
+        let leftmost = self.sensor_angle(0);
+        let rightmost = self.sensor_angle(self.lasers.len() - 1);
+        if best_index < self.center {
+            leftmost
+        } else if best_index > self.center {
+            rightmost
+        } else {
+            let (target, _, _, _) = self.compute_target_simple();
+            if target < Angle::ZERO {
+                leftmost
+            } else {
+                rightmost
+            }
+        }
+    }
+
+    fn find_open_window(&self, best_index: usize) -> (usize, usize) {
+        let mut left_index = best_index;
+        let mut right_index = best_index;
+        while left_index > 0 {
+            let next = left_index - 1;
+            if self.lasers[next].status.is_ok() {
+                left_index = next;
+            } else {
+                break;
+            }
+        }
+        while right_index < self.lasers.len() - 1 {
+            let next = right_index + 1;
+            if self.lasers[next].status.is_ok() {
+                right_index = next;
+            } else {
+                break;
+            }
+        }
+        (left_index, right_index)
+    }
+
+    pub fn sensor_angle(&self, index: usize) -> Angle {
+        self.lasers[index].angle
+    }
+
+    /// Angle of the lasers in `first..=last`, weighted by their distance.
+    fn weighted_angle(&self, first: usize, last: usize) -> Angle {
+        let window = &self.lasers[first..=last];
+        let sum: i64 = window.iter().map(|l| l.value() as i64).sum();
+        if sum == 0 {
+            return Angle::from_cdeg(
+                (self.sensor_angle(first).cdeg() + self.sensor_angle(last).cdeg()) / 2,
+            );
+        }
+        let weighted: i64 = window
+            .iter()
+            .map(|l| l.value() as i64 * l.angle.cdeg() as i64)
+            .sum();
+        Angle::from_cdeg((weighted / sum) as i32)
+    }
+
+    pub fn compute_target_with_windows(
+        &self,
+    ) -> (Angle, usize, LaserStatus, Option<(usize, usize)>) {
+        let best_index = self.find_best_index();
+        let best_status = self.lasers[best_index].status;
+
+        if best_status.is_ok() {
+            let (window_left, window_right) = self.find_open_window(best_index);
+            let target = self.weighted_angle(window_left, window_right);
+            (
+                target,
+                best_index,
+                best_status,
+                Some((window_left, window_right)),
+            )
+        } else {
+            (
+                self.find_best_extreme(best_index),
+                best_index,
+                best_status,
+                None,
+            )
+        }
+    }
+
+    pub fn compute_target_simple(&self) -> (Angle, usize, LaserStatus, Option<(usize, usize)>) {
+        let target = self.weighted_angle(0, self.lasers.len() - 1);
+        let index = self
+            .lasers
+            .iter()
+            .enumerate()
+            .min_by_key(|(_, laser)| (laser.angle.cdeg() - target.cdeg()).abs())
+            .map(|(index, _)| index)
+            .unwrap_or(self.center);
+        (target, index, self.lasers[index].status, None)
+    }
+
+    pub fn copy_status(&mut self, other: &Self) {
+        self.lasers
+            .iter_mut()
+            .zip(other.lasers.iter())
+            .for_each(|(laser, other_laser)| laser.copy_status(other_laser));
+    }
+
+    pub fn detect_back_panic(&self, config: &RaceConfig) -> bool {
+        self.lasers
+            .iter()
+            .any(|laser| laser.value() < config.back_distance(laser.position))
+    }
+
+    pub fn compute_alert_power(&self, config: &RaceConfig, target_index: Option<usize>) -> i16 {
+        if let Some(target_index) = target_index {
+            let laser = &self.lasers[target_index];
+            let distance = laser.value() as i32;
+            let alert = config.alert_distance(laser.position) as i32;
+
+            if distance >= alert {
+                config.max_speed
+            } else {
+                let max_speed = config.max_speed as i32;
+                let min_speed = config.min_speed as i32;
+                let speed_span = max_speed - min_speed;
+
+                let alert_span = alert / 2;
+                let alert_delta = (distance - (alert / 2)).max(0);
+
+                let speed_delta = speed_span * alert_delta / alert_span;
+                (min_speed + speed_delta) as i16
+            }
+        } else {
+            config.max_speed
+        }
+    }
+}
+
+pub fn is_in_window(index: usize, borders: Option<(usize, usize)>) -> bool {
+    match borders {
+        Some((left, right)) => index >= left && index <= right,
+        None => false,
+    }
+}
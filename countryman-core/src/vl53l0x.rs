@@ -0,0 +1,412 @@
+//! ST VL53L0X time-of-flight distance sensor, ranging back-to-back.
+//!
+//! The start up follows the ST API as condensed by the Pololu driver: the
+//! reference SPADs are set up from the factory values, the tuning settings
+//! are written, then the VHV and phase calibrations each take a measurement.
+//! Those measurements are finished by `read`, so that `init` never waits for
+//! them (like the VL53L1X). The timing budget is left at its default (about
+//! 33ms).
+
+use embassy_time::{Duration, Instant};
+use embedded_hal_async::i2c::I2c;
+
+use crate::{
+    distance::{DistanceError, DistanceSensor},
+    lasers::LaserSettings,
+};
+
+pub const VL53L0X_ADDR: u8 = 0x29;
+/// Longest distance in the default mode.
+pub const VL53L0X_MAX_DISTANCE: u16 = 2000;
+
+pub const VL53L0X_SYSRANGE_START: u8 = 0x00;
+pub const VL53L0X_SYSTEM_SEQUENCE_CONFIG: u8 = 0x01;
+pub const VL53L0X_SYSTEM_INTERRUPT_CONFIG_GPIO: u8 = 0x0A;
+pub const VL53L0X_SYSTEM_INTERRUPT_CLEAR: u8 = 0x0B;
+pub const VL53L0X_RESULT_INTERRUPT_STATUS: u8 = 0x13;
+pub const VL53L0X_RESULT_RANGE_STATUS: u8 = 0x14;
+pub const VL53L0X_MSRC_CONFIG_CONTROL: u8 = 0x60;
+pub const VL53L0X_FINAL_RANGE_MIN_COUNT_RATE_RTN_LIMIT: u8 = 0x44;
+pub const VL53L0X_GPIO_HV_MUX_ACTIVE_HIGH: u8 = 0x84;
+pub const VL53L0X_VHV_CONFIG_PAD_SCL_SDA_EXTSUP_HV: u8 = 0x89;
+pub const VL53L0X_GLOBAL_CONFIG_SPAD_ENABLES_REF_0: u8 = 0xB0;
+pub const VL53L0X_GLOBAL_CONFIG_REF_EN_START_SELECT: u8 = 0xB6;
+pub const VL53L0X_DYNAMIC_SPAD_NUM_REQUESTED_REF_SPAD: u8 = 0x4E;
+pub const VL53L0X_DYNAMIC_SPAD_REF_EN_START_OFFSET: u8 = 0x4F;
+pub const VL53L0X_IDENTIFICATION_MODEL_ID: u8 = 0xC0;
+
+/// Model, as read from `VL53L0X_IDENTIFICATION_MODEL_ID`.
+pub const VL53L0X_ID: u8 = 0xEE;
+
+/// Reported instead of a distance when nothing is in sight.
+const NO_TARGET: u16 = 8190;
+
+/// Device range status values (bits 3 to 6 of the range status).
+const RANGE_STATUS_VALID: u8 = 11;
+const RANGE_STATUS_MSRC_NO_TARGET: u8 = 4;
+const RANGE_STATUS_SNR: u8 = 5;
+const RANGE_STATUS_PHASE: u8 = 6;
+
+/// Polls for the factory SPAD values, which are ready within a few reads.
+const SPAD_INFO_POLLS: u8 = 50;
+/// Longest wait for each calibration measurement.
+const CALIBRATION_TIMEOUT: Duration = Duration::from_millis(200);
+
+/// Default tuning settings of the ST API, in order (register, value).
+pub const VL53L0X_TUNING: [(u8, u8); 80] = [
+    (0xFF, 0x01),
+    (0x00, 0x00),
+    (0xFF, 0x00),
+    (0x09, 0x00),
+    (0x10, 0x00),
+    (0x11, 0x00),
+    (0x24, 0x01),
+    (0x25, 0xFF),
+    (0x75, 0x00),
+    (0xFF, 0x01),
+    (0x4E, 0x2C),
+    (0x48, 0x00),
+    (0x30, 0x20),
+    (0xFF, 0x00),
+    (0x30, 0x09),
+    (0x54, 0x00),
+    (0x31, 0x04),
+    (0x32, 0x03),
+    (0x40, 0x83),
+    (0x46, 0x25),
+    (0x60, 0x00),
+    (0x27, 0x00),
+    (0x50, 0x06),
+    (0x51, 0x00),
+    (0x52, 0x96),
+    (0x56, 0x08),
+    (0x57, 0x30),
+    (0x61, 0x00),
+    (0x62, 0x00),
+    (0x64, 0x00),
+    (0x65, 0x00),
+    (0x66, 0xA0),
+    (0xFF, 0x01),
+    (0x22, 0x32),
+    (0x47, 0x14),
+    (0x49, 0xFF),
+    (0x4A, 0x00),
+    (0xFF, 0x00),
+    (0x7A, 0x0A),
+    (0x7B, 0x00),
+    (0x78, 0x21),
+    (0xFF, 0x01),
+    (0x23, 0x34),
+    (0x42, 0x00),
+    (0x44, 0xFF),
+    (0x45, 0x26),
+    (0x46, 0x05),
+    (0x40, 0x40),
+    (0x0E, 0x06),
+    (0x20, 0x1A),
+    (0x43, 0x40),
+    (0xFF, 0x00),
+    (0x34, 0x03),
+    (0x35, 0x44),
+    (0xFF, 0x01),
+    (0x31, 0x04),
+    (0x4B, 0x09),
+    (0x4C, 0x05),
+    (0x4D, 0x04),
+    (0xFF, 0x00),
+    (0x44, 0x00),
+    (0x45, 0x20),
+    (0x47, 0x08),
+    (0x48, 0x28),
+    (0x67, 0x00),
+    (0x70, 0x04),
+    (0x71, 0x01),
+    (0x72, 0xFE),
+    (0x76, 0x00),
+    (0x77, 0x00),
+    (0xFF, 0x01),
+    (0x0D, 0x01),
+    (0xFF, 0x00),
+    (0x80, 0x01),
+    (0x01, 0xF8),
+    (0xFF, 0x01),
+    (0x8E, 0x01),
+    (0x00, 0x01),
+    (0xFF, 0x00),
+    (0x80, 0x00),
+];
+
+/// Start up steps left after `init`, each ending with a measurement.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Step {
+    VhvCalibration,
+    PhaseCalibration,
+    Ranging,
+}
+
+pub struct Vl53l0x<I2C> {
+    i2c: I2C,
+    /// Read at start up, written again to start ranging
+    stop_variable: u8,
+    step: Step,
+    /// Deadline of the running calibration measurement
+    deadline: Instant,
+}
+
+impl<I2C: I2c> Vl53l0x<I2C> {
+    pub fn new(i2c: I2C) -> Self {
+        Self {
+            i2c,
+            stop_variable: 0,
+            step: Step::VhvCalibration,
+            deadline: Instant::from_ticks(0),
+        }
+    }
+
+    async fn read_regs(
+        &mut self,
+        reg: u8,
+        buf: &mut [u8],
+    ) -> Result<(), DistanceError<I2C::Error>> {
+        self.i2c
+            .write_read(VL53L0X_ADDR, &[reg], buf)
+            .await
+            .map_err(DistanceError::I2c)
+    }
+
+    async fn read_u8(&mut self, reg: u8) -> Result<u8, DistanceError<I2C::Error>> {
+        let mut buf = [0u8; 1];
+        self.read_regs(reg, &mut buf).await?;
+        Ok(buf[0])
+    }
+
+    async fn write_regs(
+        &mut self,
+        reg: u8,
+        values: &[u8],
+    ) -> Result<(), DistanceError<I2C::Error>> {
+        let mut block = [0u8; 7];
+        block[0] = reg;
+        block[1..=values.len()].copy_from_slice(values);
+        self.i2c
+            .write(VL53L0X_ADDR, &block[..=values.len()])
+            .await
+            .map_err(DistanceError::I2c)
+    }
+
+    async fn write_u8(&mut self, reg: u8, value: u8) -> Result<(), DistanceError<I2C::Error>> {
+        self.write_regs(reg, &[value]).await
+    }
+
+    /// Writes `(register, value)` pairs in order.
+    async fn write_pairs(&mut self, pairs: &[(u8, u8)]) -> Result<(), DistanceError<I2C::Error>> {
+        for &(reg, value) in pairs {
+            self.write_u8(reg, value).await?;
+        }
+        Ok(())
+    }
+
+    /// Reference SPAD count and type, programmed at the factory.
+    async fn spad_info(&mut self) -> Result<(u8, bool), DistanceError<I2C::Error>> {
+        self.write_pairs(&[(0x80, 0x01), (0xFF, 0x01), (0x00, 0x00), (0xFF, 0x06)])
+            .await?;
+        let value = self.read_u8(0x83).await?;
+        self.write_u8(0x83, value | 0x04).await?;
+        self.write_pairs(&[
+            (0xFF, 0x07),
+            (0x81, 0x01),
+            (0x80, 0x01),
+            (0x94, 0x6B),
+            (0x83, 0x00),
+        ])
+        .await?;
+        let mut polls = 0;
+        while self.read_u8(0x83).await? == 0x00 {
+            polls += 1;
+            if polls >= SPAD_INFO_POLLS {
+                return Err(DistanceError::NotBooted);
+            }
+        }
+        self.write_u8(0x83, 0x01).await?;
+        let info = self.read_u8(0x92).await?;
+        self.write_pairs(&[(0x81, 0x00), (0xFF, 0x06)]).await?;
+        let value = self.read_u8(0x83).await?;
+        self.write_u8(0x83, value & !0x04).await?;
+        self.write_pairs(&[(0xFF, 0x01), (0x00, 0x01), (0xFF, 0x00), (0x80, 0x00)])
+            .await?;
+        Ok((info & 0x7F, info & 0x80 != 0))
+    }
+
+    /// Enables the first `count` reference SPADs of the right type.
+    async fn set_reference_spads(&mut self) -> Result<(), DistanceError<I2C::Error>> {
+        let (count, aperture) = self.spad_info().await?;
+        let mut map = [0u8; 6];
+        self.read_regs(VL53L0X_GLOBAL_CONFIG_SPAD_ENABLES_REF_0, &mut map)
+            .await?;
+        self.write_pairs(&[
+            (0xFF, 0x01),
+            (VL53L0X_DYNAMIC_SPAD_REF_EN_START_OFFSET, 0x00),
+            (VL53L0X_DYNAMIC_SPAD_NUM_REQUESTED_REF_SPAD, 0x2C),
+            (0xFF, 0x00),
+            (VL53L0X_GLOBAL_CONFIG_REF_EN_START_SELECT, 0xB4),
+        ])
+        .await?;
+        // Aperture SPADs start at 12
+        let first = if aperture { 12 } else { 0 };
+        let mut enabled = 0;
+        for spad in 0..48 {
+            let bit = 1 << (spad % 8);
+            if spad < first || enabled == count {
+                map[spad / 8] &= !bit;
+            } else if map[spad / 8] & bit != 0 {
+                enabled += 1;
+            }
+        }
+        self.write_regs(VL53L0X_GLOBAL_CONFIG_SPAD_ENABLES_REF_0, &map)
+            .await
+    }
+
+    async fn data_ready(&mut self) -> Result<bool, DistanceError<I2C::Error>> {
+        Ok(self.read_u8(VL53L0X_RESULT_INTERRUPT_STATUS).await? & 0x07 != 0)
+    }
+
+    async fn clear_interrupt(&mut self) -> Result<(), DistanceError<I2C::Error>> {
+        self.write_u8(VL53L0X_SYSTEM_INTERRUPT_CLEAR, 0x01).await
+    }
+
+    /// Starts the calibration measurement of a sequence step.
+    async fn start_calibration(
+        &mut self,
+        sequence: u8,
+        start: u8,
+    ) -> Result<(), DistanceError<I2C::Error>> {
+        self.write_u8(VL53L0X_SYSTEM_SEQUENCE_CONFIG, sequence)
+            .await?;
+        self.write_u8(VL53L0X_SYSRANGE_START, 0x01 | start).await?;
+        self.deadline = Instant::now() + CALIBRATION_TIMEOUT;
+        Ok(())
+    }
+
+    /// Ends the finished calibration measurement and starts the next step.
+    async fn next_step(&mut self) -> Result<(), DistanceError<I2C::Error>> {
+        self.clear_interrupt().await?;
+        self.write_u8(VL53L0X_SYSRANGE_START, 0x00).await?;
+        match self.step {
+            Step::VhvCalibration => {
+                self.start_calibration(0x02, 0x00).await?;
+                self.step = Step::PhaseCalibration;
+            }
+            Step::PhaseCalibration => {
+                // Back to the default steps, without MSRC and TCC
+                self.write_u8(VL53L0X_SYSTEM_SEQUENCE_CONFIG, 0xE8).await?;
+                let stop_variable = self.stop_variable;
+                self.write_pairs(&[
+                    (0x80, 0x01),
+                    (0xFF, 0x01),
+                    (0x00, 0x00),
+                    (0x91, stop_variable),
+                    (0x00, 0x01),
+                    (0xFF, 0x00),
+                    (0x80, 0x00),
+                ])
+                .await?;
+                // Back-to-back mode
+                self.write_u8(VL53L0X_SYSRANGE_START, 0x02).await?;
+                self.step = Step::Ranging;
+            }
+            Step::Ranging => {}
+        }
+        Ok(())
+    }
+}
+
+impl<I2C: I2c> DistanceSensor for Vl53l0x<I2C> {
+    type Error = I2C::Error;
+
+    /// The infrared sensor settings do not apply.
+    async fn init(&mut self, _settings: &LaserSettings) -> Result<(), DistanceError<Self::Error>> {
+        self.step = Step::VhvCalibration;
+        self.deadline = Instant::now() + CALIBRATION_TIMEOUT;
+        self.health().await?;
+
+        // 2.8V I/O
+        let pad = self
+            .read_u8(VL53L0X_VHV_CONFIG_PAD_SCL_SDA_EXTSUP_HV)
+            .await?;
+        self.write_u8(VL53L0X_VHV_CONFIG_PAD_SCL_SDA_EXTSUP_HV, pad | 0x01)
+            .await?;
+        // Standard I2C mode
+        self.write_u8(0x88, 0x00).await?;
+        self.write_pairs(&[(0x80, 0x01), (0xFF, 0x01), (0x00, 0x00)])
+            .await?;
+        self.stop_variable = self.read_u8(0x91).await?;
+        self.write_pairs(&[(0x00, 0x01), (0xFF, 0x00), (0x80, 0x00)])
+            .await?;
+
+        // No signal rate limit checks on MSRC and pre-range
+        let msrc = self.read_u8(VL53L0X_MSRC_CONFIG_CONTROL).await?;
+        self.write_u8(VL53L0X_MSRC_CONFIG_CONTROL, msrc | 0x12)
+            .await?;
+        // 0.25 MCPS, in 9.7 fixed point
+        self.write_regs(VL53L0X_FINAL_RANGE_MIN_COUNT_RATE_RTN_LIMIT, &[0x00, 0x20])
+            .await?;
+        self.write_u8(VL53L0X_SYSTEM_SEQUENCE_CONFIG, 0xFF).await?;
+
+        self.set_reference_spads().await?;
+        self.write_pairs(&VL53L0X_TUNING).await?;
+
+        // Interrupt on new sample ready, active low
+        self.write_u8(VL53L0X_SYSTEM_INTERRUPT_CONFIG_GPIO, 0x04)
+            .await?;
+        let mux = self.read_u8(VL53L0X_GPIO_HV_MUX_ACTIVE_HIGH).await?;
+        self.write_u8(VL53L0X_GPIO_HV_MUX_ACTIVE_HIGH, mux & !0x10)
+            .await?;
+        self.clear_interrupt().await?;
+
+        self.start_calibration(0x01, 0x40).await
+    }
+
+    /// `None` until the calibration measurements are done after `init`.
+    async fn read(&mut self) -> Result<Option<u16>, DistanceError<Self::Error>> {
+        if !self.data_ready().await? {
+            return if self.step != Step::Ranging && Instant::now() > self.deadline {
+                Err(DistanceError::NotBooted)
+            } else {
+                Ok(None)
+            };
+        }
+        if self.step != Step::Ranging {
+            self.next_step().await?;
+            return Ok(None);
+        }
+        // Range status up to the distance, at 0x1E
+        let mut result = [0u8; 12];
+        self.read_regs(VL53L0X_RESULT_RANGE_STATUS, &mut result)
+            .await?;
+        self.clear_interrupt().await?;
+
+        let distance = u16::from_be_bytes([result[10], result[11]]);
+        if distance >= NO_TARGET {
+            return Ok(Some(VL53L0X_MAX_DISTANCE));
+        }
+        match (result[0] >> 3) & 0x0F {
+            RANGE_STATUS_VALID => Ok(Some(distance.min(VL53L0X_MAX_DISTANCE))),
+            // No target, or too far to tell
+            RANGE_STATUS_MSRC_NO_TARGET | RANGE_STATUS_SNR | RANGE_STATUS_PHASE => {
+                Ok(Some(VL53L0X_MAX_DISTANCE))
+            }
+            _ => Err(DistanceError::OutOfRange),
+        }
+    }
+
+    async fn health(&mut self) -> Result<(), DistanceError<Self::Error>> {
+        if self.read_u8(VL53L0X_IDENTIFICATION_MODEL_ID).await? != VL53L0X_ID {
+            return Err(DistanceError::WrongDevice);
+        }
+        Ok(())
+    }
+
+    fn max_distance(&self) -> u16 {
+        VL53L0X_MAX_DISTANCE
+    }
+}
@@ -0,0 +1,207 @@
+//! ST VL53L1X time-of-flight distance sensor, ranging continuously.
+//!
+//! The start up follows the ST ultra lite driver: the default configuration
+//! block is written, then one measurement calibrates the sensor. That
+//! measurement is finished by `read`, so that `init` never waits for it and
+//! the other channels keep being scanned. Distance mode and timing budget are
+//! left at their defaults (long range, 100ms).
+//! The VL53L0X has a different register map, see `vl53l0x`: `init` fails
+//! with `DistanceError::WrongDevice` on one.
+
+use embassy_time::{Duration, Instant};
+use embedded_hal_async::i2c::I2c;
+
+use crate::{
+    distance::{DistanceError, DistanceSensor},
+    lasers::LaserSettings,
+};
+
+pub const VL53L1X_ADDR: u8 = 0x29;
+/// Longest distance in long range mode.
+pub const VL53L1X_MAX_DISTANCE: u16 = 4000;
+
+pub const VL53L1X_VHV_CONFIG_TIMEOUT_MACROP_LOOP_BOUND: u16 = 0x0008;
+pub const VL53L1X_VHV_CONFIG_INIT: u16 = 0x000B;
+pub const VL53L1X_DEFAULT_CONFIG_START: u16 = 0x002D;
+pub const VL53L1X_GPIO_HV_MUX_CTRL: u16 = 0x0030;
+pub const VL53L1X_GPIO_TIO_HV_STATUS: u16 = 0x0031;
+pub const VL53L1X_SYSTEM_INTERRUPT_CLEAR: u16 = 0x0086;
+pub const VL53L1X_SYSTEM_MODE_START: u16 = 0x0087;
+pub const VL53L1X_RESULT_RANGE_STATUS: u16 = 0x0089;
+pub const VL53L1X_FIRMWARE_SYSTEM_STATUS: u16 = 0x00E5;
+pub const VL53L1X_MODEL_ID: u16 = 0x010F;
+
+/// Model and module type, as read from `VL53L1X_MODEL_ID`.
+pub const VL53L1X_ID: u16 = 0xEACC;
+
+const MODE_START_RANGING: u8 = 0x40;
+const MODE_STOP_RANGING: u8 = 0x00;
+
+/// Raw range status values (the ultra lite driver maps them to its own).
+const RANGE_STATUS_VALID: u8 = 9;
+const RANGE_STATUS_SIGNAL_FAIL: u8 = 4;
+const RANGE_STATUS_PHASE_OUT_OF_LIMITS: u8 = 5;
+const RANGE_STATUS_WRAP_AROUND: u8 = 7;
+
+/// Longest wait for the calibration measurement.
+const CALIBRATION_TIMEOUT: Duration = Duration::from_millis(200);
+
+/// Registers 0x2D to 0x87 of the ultra lite driver, with ranging stopped.
+pub const VL53L1X_DEFAULT_CONFIG: [u8; 91] = [
+    0x00, 0x00, 0x00, 0x01, 0x02, 0x00, 0x02, 0x08, // 0x2D
+    0x00, 0x08, 0x10, 0x01, 0x01, 0x00, 0x00, 0x00, // 0x35
+    0x00, 0xFF, 0x00, 0x0F, 0x00, 0x00, 0x00, 0x00, // 0x3D
+    0x00, 0x20, 0x0B, 0x00, 0x00, 0x02, 0x0A, 0x21, // 0x45
+    0x00, 0x00, 0x05, 0x00, 0x00, 0x00, 0x00, 0xC8, // 0x4D
+    0x00, 0x00, 0x38, 0xFF, 0x01, 0x00, 0x08, 0x00, // 0x55
+    0x00, 0x01, 0xCC, 0x0F, 0x01, 0xF1, 0x0D, 0x01, // 0x5D
+    0x68, 0x00, 0x80, 0x08, 0xB8, 0x00, 0x00, 0x00, // 0x65
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // 0x6D
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x0F, 0x89, 0x00, // 0x75
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // 0x7D
+    0x00, 0x00, 0x00, // 0x85
+];
+
+pub struct Vl53l1x<I2C> {
+    i2c: I2C,
+    /// Level of `VL53L1X_GPIO_TIO_HV_STATUS` bit 0 meaning "data ready"
+    ready_level: u8,
+    /// Deadline of the calibration measurement, while it runs
+    calibrating: Option<Instant>,
+}
+
+impl<I2C: I2c> Vl53l1x<I2C> {
+    pub fn new(i2c: I2C) -> Self {
+        Self {
+            i2c,
+            ready_level: 1,
+            calibrating: None,
+        }
+    }
+
+    async fn read_regs(
+        &mut self,
+        reg: u16,
+        buf: &mut [u8],
+    ) -> Result<(), DistanceError<I2C::Error>> {
+        self.i2c
+            .write_read(VL53L1X_ADDR, &reg.to_be_bytes(), buf)
+            .await
+            .map_err(DistanceError::I2c)
+    }
+
+    async fn read_u8(&mut self, reg: u16) -> Result<u8, DistanceError<I2C::Error>> {
+        let mut buf = [0u8; 1];
+        self.read_regs(reg, &mut buf).await?;
+        Ok(buf[0])
+    }
+
+    async fn read_u16(&mut self, reg: u16) -> Result<u16, DistanceError<I2C::Error>> {
+        let mut buf = [0u8; 2];
+        self.read_regs(reg, &mut buf).await?;
+        Ok(u16::from_be_bytes(buf))
+    }
+
+    async fn write_u8(&mut self, reg: u16, value: u8) -> Result<(), DistanceError<I2C::Error>> {
+        let [high, low] = reg.to_be_bytes();
+        self.i2c
+            .write(VL53L1X_ADDR, &[high, low, value])
+            .await
+            .map_err(DistanceError::I2c)
+    }
+
+    async fn data_ready(&mut self) -> Result<bool, DistanceError<I2C::Error>> {
+        let status = self.read_u8(VL53L1X_GPIO_TIO_HV_STATUS).await?;
+        Ok(status & 1 == self.ready_level)
+    }
+
+    async fn clear_interrupt(&mut self) -> Result<(), DistanceError<I2C::Error>> {
+        self.write_u8(VL53L1X_SYSTEM_INTERRUPT_CLEAR, 0x01).await
+    }
+
+    /// Starts ranging for real once the calibration measurement is done.
+    async fn end_calibration(&mut self) -> Result<(), DistanceError<I2C::Error>> {
+        self.clear_interrupt().await?;
+        self.write_u8(VL53L1X_SYSTEM_MODE_START, MODE_STOP_RANGING)
+            .await?;
+        self.write_u8(VL53L1X_VHV_CONFIG_TIMEOUT_MACROP_LOOP_BOUND, 0x09)
+            .await?;
+        self.write_u8(VL53L1X_VHV_CONFIG_INIT, 0x00).await?;
+        self.write_u8(VL53L1X_SYSTEM_MODE_START, MODE_START_RANGING)
+            .await
+    }
+}
+
+impl<I2C: I2c> DistanceSensor for Vl53l1x<I2C> {
+    type Error = I2C::Error;
+
+    /// The infrared sensor settings do not apply.
+    async fn init(&mut self, _settings: &LaserSettings) -> Result<(), DistanceError<Self::Error>> {
+        self.calibrating = None;
+        self.health().await?;
+
+        let [high, low] = VL53L1X_DEFAULT_CONFIG_START.to_be_bytes();
+        let mut block = [0u8; 2 + VL53L1X_DEFAULT_CONFIG.len()];
+        block[0] = high;
+        block[1] = low;
+        block[2..].copy_from_slice(&VL53L1X_DEFAULT_CONFIG);
+        self.i2c
+            .write(VL53L1X_ADDR, &block)
+            .await
+            .map_err(DistanceError::I2c)?;
+
+        // Data ready reads 0 instead of 1 when bit 4 is set
+        let mux = self.read_u8(VL53L1X_GPIO_HV_MUX_CTRL).await?;
+        self.ready_level = if mux & 0x10 != 0 { 0 } else { 1 };
+
+        // The first measurement runs the VHV calibration
+        self.write_u8(VL53L1X_SYSTEM_MODE_START, MODE_START_RANGING)
+            .await?;
+        self.calibrating = Some(Instant::now() + CALIBRATION_TIMEOUT);
+        Ok(())
+    }
+
+    /// `None` until the calibration measurement is done after `init`.
+    async fn read(&mut self) -> Result<Option<u16>, DistanceError<Self::Error>> {
+        if !self.data_ready().await? {
+            return match self.calibrating {
+                Some(deadline) if Instant::now() > deadline => Err(DistanceError::NotBooted),
+                _ => Ok(None),
+            };
+        }
+        if self.calibrating.is_some() {
+            self.calibrating = None;
+            self.end_calibration().await?;
+            return Ok(None);
+        }
+        // Range status up to the corrected distance, at 0x96
+        let mut result = [0u8; 17];
+        self.read_regs(VL53L1X_RESULT_RANGE_STATUS, &mut result)
+            .await?;
+        self.clear_interrupt().await?;
+
+        let distance = u16::from_be_bytes([result[13], result[14]]);
+        match result[0] & 0x1F {
+            RANGE_STATUS_VALID => Ok(Some(distance.min(VL53L1X_MAX_DISTANCE))),
+            // No target, or too far to tell
+            RANGE_STATUS_SIGNAL_FAIL
+            | RANGE_STATUS_PHASE_OUT_OF_LIMITS
+            | RANGE_STATUS_WRAP_AROUND => Ok(Some(VL53L1X_MAX_DISTANCE)),
+            _ => Err(DistanceError::OutOfRange),
+        }
+    }
+
+    async fn health(&mut self) -> Result<(), DistanceError<Self::Error>> {
+        if self.read_u8(VL53L1X_FIRMWARE_SYSTEM_STATUS).await? == 0 {
+            return Err(DistanceError::NotBooted);
+        }
+        if self.read_u16(VL53L1X_MODEL_ID).await? != VL53L1X_ID {
+            return Err(DistanceError::WrongDevice);
+        }
+        Ok(())
+    }
+
+    fn max_distance(&self) -> u16 {
+        VL53L1X_MAX_DISTANCE
+    }
+}
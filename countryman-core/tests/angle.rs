@@ -0,0 +1,62 @@
+use countryman_core::configuration::RaceConfig;
+use countryman_core::race::Angle;
+
+#[test]
+fn degrees_and_centidegrees() {
+    assert_eq!(Angle::MAX_STEER.cdeg(), 3500);
+    assert_eq!(Angle::from(-35), Angle::MIN_STEER);
+    assert_eq!(Angle::from(190), Angle::L170);
+    assert_eq!(Angle::from(-180), -Angle::BACK);
+
+    // IMU values keep their resolution
+    let angle = Angle::from_imu_value(1249);
+    assert_eq!(angle.cdeg(), 1249);
+    assert_eq!(angle.value(), 12);
+    assert_eq!(Angle::from_imu_value(-1250).value(), -13);
+    assert_eq!(Angle::from_imu_value(i16::MAX), Angle::BACK);
+    assert!(Angle::from_imu_value(50) > Angle::ZERO);
+}
+
+#[test]
+fn differences_take_the_shortest_arc() {
+    assert_eq!(Angle::R170.diff(Angle::L170), Angle::from(-20));
+    assert_eq!(Angle::L170 - Angle::R170, Angle::from(20));
+    assert_eq!(
+        Angle::from_cdeg(17950) + Angle::from_cdeg(100),
+        Angle::from_cdeg(-17950)
+    );
+    assert_eq!(
+        Angle::from_cdeg(1025).diff(Angle::from_cdeg(1000)).cdeg(),
+        25
+    );
+}
+
+#[test]
+fn circular_mean_wraps() {
+    assert_eq!(Angle::circular_mean([]), None);
+    assert_eq!(Angle::circular_mean([Angle::R90, Angle::L90]), None);
+    let mean = Angle::circular_mean([Angle::R170, Angle::L170]).unwrap();
+    assert_eq!(mean.abs(), Angle::BACK);
+    assert_eq!(
+        Angle::circular_mean([Angle::from_cdeg(1000), Angle::from_cdeg(1050)]),
+        Some(Angle::from_cdeg(1025))
+    );
+}
+
+#[test]
+fn scaling_and_servo_units() {
+    assert_eq!(Angle::from(20).scale(2, 3).cdeg(), 1333);
+    assert_eq!(Angle::from(-20).scale(2, 3).cdeg(), -1333);
+    assert_eq!(Angle::R90.scale(3, 1), Angle::L90);
+
+    assert_eq!(Angle::MAX_STEER.servo_units(), 350);
+    assert_eq!(Angle::from_cdeg(-1234).servo_units(), -123);
+
+    let mut config = RaceConfig::init();
+    config.steer_bias = -2;
+    assert_eq!(config.servo_steer(Angle::from_cdeg(1050)), 85);
+
+    // Sub-degree steering changes the speed too
+    config.safe_angle = 10;
+    assert!(config.turn_speed(Angle::from_cdeg(2050)) < config.turn_speed(Angle::from_cdeg(2000)));
+}
@@ -0,0 +1,188 @@
+use std::collections::VecDeque;
+
+use countryman_core::bno08x::{
+    quaternion_to_ypr, Bno08x, Bno08xEvent, BNO08X_REPORT_GAME_ROTATION_VECTOR,
+};
+use countryman_core::imu::{Bno080RawRvcData, ImuStability};
+use countryman_core::shtp::{
+    shtp_i2c_read, shtp_uart_frame, ShtpPacket, ShtpUartDecoder, ShtpWriter, SHTP_I2C_ADDR,
+};
+use embassy_futures::block_on;
+use embassy_time::Duration;
+use embedded_hal_async::i2c::{ErrorKind, ErrorType, I2c, Operation};
+
+fn packet(channel: u8, sequence: u8, payload: &[u8]) -> Vec<u8> {
+    let mut packet = ((payload.len() + 4) as u16).to_le_bytes().to_vec();
+    packet.push(channel);
+    packet.push(sequence);
+    packet.extend_from_slice(payload);
+    packet
+}
+
+fn report(id: u8, values: &[i16], length: usize) -> Vec<u8> {
+    let mut report = vec![id, 0, 0, 0];
+    for v in values {
+        report.extend_from_slice(&v.to_le_bytes());
+    }
+    report.resize(length, 0);
+    report
+}
+
+#[test]
+fn uart_frames_are_stuffed_and_decoded() {
+    let mut writer = ShtpWriter::new();
+    // 0x7E and 0x7D inside the packet need escaping
+    let sent = writer.packet(2, &[0x7E, 0x01, 0x7D]);
+    let frame = shtp_uart_frame(&sent);
+    assert_eq!(
+        frame.as_slice(),
+        &[0x7E, 0x01, 0x07, 0x00, 0x02, 0x00, 0x7D, 0x5E, 0x01, 0x7D, 0x5D, 0x7E]
+    );
+    assert_eq!(writer.packet(2, &[]).as_slice(), &[4, 0, 2, 1]);
+
+    let mut decoder = ShtpUartDecoder::new();
+    let mut stream = vec![0x55, 0x7E, 0x02, 0x33, 0x7E];
+    // A frame shorter than its header says
+    stream.extend_from_slice(&[0x7E, 0x01, 0x09, 0x00, 0x02, 0x00, 0x7E]);
+    stream.extend_from_slice(&frame);
+    let mut complete = vec![];
+    for b in stream {
+        if decoder.update(b) {
+            complete.push(decoder.packet().to_vec());
+        }
+    }
+    assert_eq!(complete, vec![sent.to_vec()]);
+    assert_eq!(decoder.dropped, 1);
+}
+
+#[test]
+fn features_and_calibration_save_commands() {
+    let mut bno = Bno08x::new();
+    let feature = bno.set_feature(
+        BNO08X_REPORT_GAME_ROTATION_VECTOR,
+        Duration::from_millis(10),
+    );
+    assert_eq!(
+        feature.as_slice(),
+        &[21, 0, 2, 0, 0xFD, 0x08, 0, 0, 0, 0x10, 0x27, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]
+    );
+    let save = bno.save_calibration();
+    assert_eq!(
+        save.as_slice(),
+        &[16, 0, 2, 1, 0xF2, 0, 0x06, 0, 0, 0, 0, 0, 0, 0, 0, 0]
+    );
+
+    let mut response = vec![0u8; 16];
+    response[0] = 0xF1;
+    response[2] = 0x06;
+    assert_eq!(
+        bno.process(&packet(2, 0, &response)),
+        Some(Bno08xEvent::CalibrationSaved(true))
+    );
+    response[5] = 1;
+    assert_eq!(
+        bno.process(&packet(2, 1, &response)),
+        Some(Bno08xEvent::CalibrationSaved(false))
+    );
+    assert_eq!(
+        bno.process(&packet(1, 0, &[0x01])),
+        Some(Bno08xEvent::Reset)
+    );
+}
+
+#[test]
+fn rotation_vector_packets_become_samples() {
+    let mut bno = Bno08x::new();
+    // Extras arrive in an earlier packet
+    let mut extras = vec![0xFB, 0, 0, 0, 0];
+    // 1 rad/s around the vertical axis
+    extras.extend(report(0x02, &[0, 0, 512], 10));
+    extras.extend(report(0x04, &[251, -502, 0], 10));
+    extras.extend(report(0x13, &[3], 6));
+    extras.extend(report(0x10, &[], 5));
+    assert_eq!(bno.process(&packet(3, 10, &extras)), None);
+
+    // Yaw 90 degrees, 1g downwards
+    let mut sample = vec![0xFB, 0, 0, 0, 0];
+    sample.extend(report(0x01, &[0, 0, 2511], 10));
+    sample.extend(report(0x08, &[0, 0, 11585, 11585], 12));
+    let event = bno.process(&packet(3, 13, &sample));
+    let Some(Bno08xEvent::Sample(raw, extras)) = event else {
+        panic!("no sample: {:?}", event);
+    };
+    assert_eq!(raw, Bno080RawRvcData::new(9000, 0, 0, 0, 0, 1000, 13));
+    assert_eq!(extras.yaw_rate, 5729);
+    assert_eq!((extras.linear_side, extras.linear_forward), (99, -199));
+    assert_eq!(extras.stability, ImuStability::Stable);
+    assert_eq!(extras.taps, 1);
+
+    let stats = bno.stats();
+    assert_eq!((stats.good, stats.lost), (2, 2));
+}
+
+#[test]
+fn quaternions_convert_like_rvc_angles() {
+    assert_eq!(quaternion_to_ypr(0, 0, 0, 16384), (0, 0, 0));
+    // 30 degrees of pitch
+    assert_eq!(quaternion_to_ypr(0, 4240, 0, 15826), (0, 3000, 0));
+    // Turned right, past 90 degrees
+    assert_eq!(quaternion_to_ypr(0, 0, -15137, 6270), (-13500, 0, 0));
+}
+
+struct MockI2c {
+    reads: VecDeque<Vec<u8>>,
+}
+
+impl ErrorType for MockI2c {
+    type Error = ErrorKind;
+}
+
+impl I2c for MockI2c {
+    async fn read(&mut self, address: u8, read: &mut [u8]) -> Result<(), Self::Error> {
+        assert_eq!(address, SHTP_I2C_ADDR);
+        let data = self.reads.pop_front().expect("unexpected read");
+        read.copy_from_slice(&data[..read.len()]);
+        Ok(())
+    }
+
+    async fn write(&mut self, _address: u8, _write: &[u8]) -> Result<(), Self::Error> {
+        panic!("unexpected write");
+    }
+
+    async fn write_read(
+        &mut self,
+        _address: u8,
+        _write: &[u8],
+        _read: &mut [u8],
+    ) -> Result<(), Self::Error> {
+        panic!("unexpected write_read");
+    }
+
+    async fn transaction(
+        &mut self,
+        _address: u8,
+        _operations: &mut [Operation<'_>],
+    ) -> Result<(), Self::Error> {
+        panic!("unexpected transaction");
+    }
+}
+
+#[test]
+fn i2c_reads_the_header_then_the_packet() {
+    let reset = packet(1, 0, &[0x01]);
+    let mut i2c = MockI2c {
+        reads: vec![vec![0, 0, 0, 0], reset.clone(), reset.clone()].into(),
+    };
+    let mut received = ShtpPacket::new();
+    block_on(async {
+        let idle = shtp_i2c_read(&mut i2c, SHTP_I2C_ADDR, &mut received)
+            .await
+            .unwrap();
+        assert_eq!(idle.length, 0);
+        let header = shtp_i2c_read(&mut i2c, SHTP_I2C_ADDR, &mut received)
+            .await
+            .unwrap();
+        assert_eq!((header.length, header.channel), (5, 1));
+    });
+    assert_eq!(received.as_slice(), reset.as_slice());
+}
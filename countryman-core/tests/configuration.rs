@@ -0,0 +1,102 @@
+use countryman_core::configuration::{
+    ConfigValueKind, ConfigViolation, RaceConfig, RaceConfigEntry, RACE_CONFIG_ENTRY_END,
+    RACE_CONFIG_SCHEMA,
+};
+use countryman_core::profiles::RaceProfiles;
+use countryman_core::vision::LaserSidePosition;
+
+#[test]
+fn schema_covers_every_entry_in_order() {
+    assert_eq!(RaceConfigEntry::iter().count(), RACE_CONFIG_ENTRY_END);
+    for (index, entry) in RaceConfigEntry::iter().enumerate() {
+        assert_eq!(entry.index(), index);
+        assert_eq!(RaceConfigEntry::from(index), entry);
+        assert_eq!(entry.schema().entry, entry);
+    }
+}
+
+#[test]
+fn keys_are_unique_and_found() {
+    for schema in RACE_CONFIG_SCHEMA.iter() {
+        assert_eq!(RaceConfigEntry::from_key(schema.key), Some(schema.entry));
+        assert_eq!(
+            RACE_CONFIG_SCHEMA
+                .iter()
+                .filter(|other| other.key == schema.key)
+                .count(),
+            1
+        );
+    }
+    assert_eq!(
+        RaceConfigEntry::from_key("MAX_SPEED"),
+        Some(RaceConfigEntry::MaxSpeed)
+    );
+    assert_eq!(RaceConfigEntry::from_key("warp_speed"), None);
+}
+
+#[test]
+fn defaults_come_from_the_schema() {
+    let mut config = RaceConfig::init();
+    for (schema, value) in RaceConfig::init().values() {
+        assert_eq!(value, schema.default);
+        config.set(schema.entry, value + 1);
+        config.reset(schema.entry);
+        assert_eq!(config.get(schema.entry), schema.default);
+    }
+}
+
+#[test]
+fn bool_entries_have_labels() {
+    let entry = RaceConfigEntry::UseStillness;
+    assert!(entry.schema().is_bool());
+    assert_eq!(entry.value_name(0), Some("NO"));
+    assert_eq!(entry.value_name(1), Some("YES"));
+    assert_eq!(RaceConfigEntry::MaxSpeed.value_name(1), None);
+    assert_eq!(
+        RaceConfigEntry::MaxSpeed.schema().kind,
+        ConfigValueKind::Number
+    );
+}
+
+#[test]
+fn defaults_and_built_in_profiles_are_valid() {
+    assert!(RaceConfig::init().validate().is_empty());
+    for profile in RaceProfiles::init().iter() {
+        assert!(profile.config.is_valid(), "{}", profile.name());
+    }
+}
+
+#[test]
+fn cross_field_violations() {
+    let mut config = RaceConfig::init();
+    config.min_speed = config.max_speed + 500;
+    config.climbing_ignore = 8;
+    config.climbing_angle = 8;
+    config.back_distance_side_30 = config.alert_distance_side_30 as i16;
+    let violations = config.validate();
+    assert_eq!(
+        violations.as_slice(),
+        [
+            ConfigViolation::MinSpeedAboveMaxSpeed,
+            ConfigViolation::ClimbingAngleNotAboveIgnore,
+            ConfigViolation::BackNotBelowAlert(LaserSidePosition::Side30),
+        ]
+    );
+    assert!(violations[0].involves(RaceConfigEntry::MaxSpeed));
+    assert!(violations[2].involves(RaceConfigEntry::AlertDistanceSide30));
+    assert!(!violations[2].involves(RaceConfigEntry::AlertDistanceSide60));
+}
+
+#[test]
+fn out_of_range_and_division_guards() {
+    let mut config = RaceConfig::init();
+    config.safe_angle = 35;
+    config.set(RaceConfigEntry::SprintTime, -1);
+    assert_eq!(
+        config.validate().as_slice(),
+        [
+            ConfigViolation::OutOfRange(RaceConfigEntry::SprintTime),
+            ConfigViolation::SafeAngleTooWide,
+        ]
+    );
+}
@@ -0,0 +1,110 @@
+use countryman_core::cmd::Cmd;
+use countryman_core::configuration::RaceConfigEntry;
+use countryman_core::console::{ConsoleCommand, ConsoleError, ConsoleInput, ConsoleReader};
+
+fn feed(reader: &mut ConsoleReader, text: &str) -> Vec<ConsoleInput> {
+    text.bytes().filter_map(|b| reader.update(b)).collect()
+}
+
+#[test]
+fn parses_line_commands() {
+    assert_eq!(
+        ConsoleCommand::parse("config get max_speed"),
+        Ok(ConsoleCommand::ConfigGet(RaceConfigEntry::MaxSpeed))
+    );
+    assert_eq!(
+        ConsoleCommand::parse("  config   set steer_bias -3 "),
+        Ok(ConsoleCommand::ConfigSet(RaceConfigEntry::SteerBias, -3))
+    );
+    assert_eq!(
+        ConsoleCommand::parse("config set use_stillness yes"),
+        Ok(ConsoleCommand::ConfigSet(RaceConfigEntry::UseStillness, 1))
+    );
+    assert_eq!(
+        ConsoleCommand::parse("config dump"),
+        Ok(ConsoleCommand::ConfigDump)
+    );
+    assert_eq!(
+        ConsoleCommand::parse("config save"),
+        Ok(ConsoleCommand::ConfigSave)
+    );
+    assert_eq!(
+        ConsoleCommand::parse("race start"),
+        Ok(ConsoleCommand::RaceStart)
+    );
+    assert_eq!(
+        ConsoleCommand::parse("race stop"),
+        Ok(ConsoleCommand::RaceStop)
+    );
+    assert_eq!(
+        ConsoleCommand::parse("trace dump"),
+        Ok(ConsoleCommand::TraceDump)
+    );
+    assert_eq!(
+        ConsoleCommand::parse("reboot bootsel"),
+        Ok(ConsoleCommand::RebootBootsel)
+    );
+}
+
+#[test]
+fn rejects_bad_lines() {
+    assert_eq!(
+        ConsoleCommand::parse("race"),
+        Err(ConsoleError::MissingArgument)
+    );
+    assert_eq!(
+        ConsoleCommand::parse("race go"),
+        Err(ConsoleError::UnknownCommand)
+    );
+    assert_eq!(
+        ConsoleCommand::parse("config get nope"),
+        Err(ConsoleError::UnknownKey)
+    );
+    assert_eq!(
+        ConsoleCommand::parse("config set max_speed fast"),
+        Err(ConsoleError::BadValue)
+    );
+    assert_eq!(
+        ConsoleCommand::parse("config set steer_bias 100"),
+        Err(ConsoleError::OutOfRange(RaceConfigEntry::SteerBias))
+    );
+    assert_eq!(
+        ConsoleCommand::parse("race stop now"),
+        Err(ConsoleError::TooManyArguments)
+    );
+}
+
+#[test]
+fn keys_at_line_start_are_commands() {
+    let mut reader = ConsoleReader::new();
+    assert_eq!(
+        feed(&mut reader, "az+"),
+        vec![
+            ConsoleInput::Cmd(Cmd::Next),
+            ConsoleInput::Cmd(Cmd::Ok),
+            ConsoleInput::Cmd(Cmd::Plus)
+        ]
+    );
+    // Inside a line the same keys are just text
+    assert_eq!(
+        feed(&mut reader, "race stap\x08\x08op\r\n"),
+        vec![ConsoleInput::Command(Ok(ConsoleCommand::RaceStop))]
+    );
+    assert!(reader.is_empty());
+}
+
+#[test]
+fn long_lines_are_dropped() {
+    let mut reader = ConsoleReader::new();
+    let mut line = String::from("config get ");
+    line.push_str(&"x".repeat(100));
+    line.push('\n');
+    assert_eq!(
+        feed(&mut reader, &line),
+        vec![ConsoleInput::Command(Err(ConsoleError::LineTooLong))]
+    );
+    assert_eq!(
+        feed(&mut reader, "trace dump\n"),
+        vec![ConsoleInput::Command(Ok(ConsoleCommand::TraceDump))]
+    );
+}
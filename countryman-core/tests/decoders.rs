@@ -0,0 +1,102 @@
+use embassy_time::{Duration, Instant};
+
+use countryman_core::esp32c3::{AtReply, CwState};
+use countryman_core::imu::{Bno080Decoder, ImuLinkStats, ImuStillnessDetector, StillnessConfig};
+use countryman_core::rgb::rgb2hsv;
+
+fn rvc_frame(counter: u8, values: [i16; 6]) -> [u8; 19] {
+    let mut frame = [0u8; 19];
+    frame[0] = 0xaa;
+    frame[1] = 0xaa;
+    frame[2] = counter;
+    for (i, v) in values.iter().enumerate() {
+        let [lsb, msb] = v.to_le_bytes();
+        frame[3 + i * 2] = lsb;
+        frame[4 + i * 2] = msb;
+    }
+    frame[18] = frame[2..18].iter().fold(0u8, |sum, b| sum.wrapping_add(*b));
+    frame
+}
+
+#[test]
+fn bno080_decodes_rvc_frame() {
+    let mut decoder = Bno080Decoder::init();
+    let frame = rvc_frame(7, [-9000, 150, -25, 10, -20, 980]);
+    let decoded: Vec<_> = frame.iter().filter_map(|b| decoder.update(*b)).collect();
+    assert_eq!(decoded.len(), 1);
+}
+
+#[test]
+fn rgb2hsv_primary_colors() {
+    assert_eq!(rgb2hsv(200, 0, 0).0, 0);
+    assert_eq!(rgb2hsv(0, 200, 0).0, 120);
+    assert_eq!(rgb2hsv(0, 0, 200).0, 240);
+    assert_eq!(rgb2hsv(50, 50, 50), (-1, 0, 50));
+}
+
+#[test]
+fn at_reply_parses_status_lines() {
+    assert!(matches!(AtReply::parse("OK", &[]), AtReply::Ok));
+    assert!(matches!(AtReply::parse("ERROR", &[]), AtReply::Error));
+    assert!(matches!(
+        AtReply::parse("+CWSTATE:2,\"ssid\"", &[]),
+        AtReply::CwState(CwState::Connected)
+    ));
+    assert!(matches!(AtReply::parse("garbage", &[]), AtReply::Empty));
+}
+
+#[test]
+fn stillness_detector_follows_config() {
+    let detect = |config: StillnessConfig| {
+        let mut decoder = Bno080Decoder::init();
+        let mut detector = ImuStillnessDetector::new(config);
+        let dt = Duration::from_millis(10);
+        for step in 0..60u64 {
+            let frame = rvc_frame(step as u8, [0, 0, 0, 100, 100, 980]);
+            let now = Instant::from_millis(step * 10);
+            for raw in frame.iter().filter_map(|b| decoder.update(*b)) {
+                detector.process_data(&raw, now, dt);
+            }
+        }
+        detector.last_stillness.is_some()
+    };
+
+    assert!(detect(StillnessConfig::init()));
+    assert!(!detect(StillnessConfig {
+        delta: 50,
+        ..StillnessConfig::init()
+    }));
+    assert!(!detect(StillnessConfig {
+        window: Duration::from_millis(1000),
+        ..StillnessConfig::init()
+    }));
+}
+
+#[test]
+fn bno080_drops_corrupt_frames_and_counts_lost_ones() {
+    let mut decoder = Bno080Decoder::init();
+    let mut feed =
+        |frame: &[u8]| -> usize { frame.iter().filter_map(|b| decoder.update(*b)).count() };
+
+    assert_eq!(feed(&rvc_frame(10, [100, 200, 300, 0, 0, 980])), 1);
+    let mut corrupt = rvc_frame(11, [100, 200, 300, 0, 0, 980]);
+    corrupt[6] ^= 0x40;
+    assert_eq!(feed(&corrupt), 0);
+    // Frames 12 and 13 never arrive
+    assert_eq!(feed(&rvc_frame(14, [100, 200, 300, 0, 0, 980])), 1);
+    // A header in the middle of a frame starts a new one
+    let cut = rvc_frame(15, [100, 200, 300, 0, 0, 980]);
+    assert_eq!(feed(&cut[..9]), 0);
+    assert_eq!(feed(&rvc_frame(16, [100, 200, 300, 0, 0, 980])), 1);
+
+    assert_eq!(
+        decoder.stats(),
+        ImuLinkStats {
+            good: 3,
+            bad: 1,
+            resyncs: 1,
+            lost: 2,
+        }
+    );
+    assert_eq!(decoder.stats().quality_since(&ImuLinkStats::new()), 42);
+}
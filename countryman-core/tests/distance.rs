@@ -0,0 +1,454 @@
+use std::collections::VecDeque;
+
+use countryman_core::distance::{DistanceError, DistanceSensor, DistanceSensorKind, LaserSensor};
+use countryman_core::geometry::{sensor_kind, LaserBeam, LaserTier, LASER_GEOMETRY};
+use countryman_core::gp2y0e02b::{GP2Y0E02B_ADDR, GP2Y0E02B_SETTINGS_CHECK_READS};
+use countryman_core::lasers::{
+    Gp2Y0E02bAcc, Gp2Y0E02bMedian, Gp2Y0E02bShift, LaserChannelState, LaserSettings,
+};
+use countryman_core::race::Angle;
+use countryman_core::vl53l0x::{VL53L0X_ADDR, VL53L0X_ID, VL53L0X_MAX_DISTANCE, VL53L0X_TUNING};
+use countryman_core::vl53l1x::{
+    VL53L1X_ADDR, VL53L1X_DEFAULT_CONFIG, VL53L1X_ID, VL53L1X_MAX_DISTANCE,
+};
+use embassy_futures::block_on;
+use embedded_hal_async::i2c::{ErrorKind, ErrorType, I2c, Operation};
+
+#[derive(Debug, PartialEq, Eq)]
+enum Expect {
+    Write(u8, Vec<u8>),
+    WriteRead(u8, Vec<u8>, Vec<u8>),
+    /// The next transaction fails
+    Fail,
+}
+
+/// Replays the expected transactions, in order.
+struct MockI2c {
+    expected: VecDeque<Expect>,
+}
+
+impl MockI2c {
+    fn new(expected: Vec<Expect>) -> Self {
+        Self {
+            expected: expected.into(),
+        }
+    }
+
+    fn next(&mut self) -> Expect {
+        self.expected.pop_front().expect("unexpected transaction")
+    }
+}
+
+impl ErrorType for MockI2c {
+    type Error = ErrorKind;
+}
+
+impl I2c for MockI2c {
+    async fn read(&mut self, _address: u8, _read: &mut [u8]) -> Result<(), Self::Error> {
+        panic!("unexpected read");
+    }
+
+    async fn write(&mut self, address: u8, write: &[u8]) -> Result<(), Self::Error> {
+        match self.next() {
+            Expect::Write(a, w) => {
+                assert_eq!((a, w.as_slice()), (address, write));
+                Ok(())
+            }
+            Expect::Fail => Err(ErrorKind::Other),
+            other => panic!("write {:x?}, expected {:x?}", write, other),
+        }
+    }
+
+    async fn write_read(
+        &mut self,
+        address: u8,
+        write: &[u8],
+        read: &mut [u8],
+    ) -> Result<(), Self::Error> {
+        match self.next() {
+            Expect::WriteRead(a, w, r) => {
+                assert_eq!((a, w.as_slice()), (address, write));
+                read.copy_from_slice(&r);
+                Ok(())
+            }
+            Expect::Fail => Err(ErrorKind::Other),
+            other => panic!("write_read {:x?}, expected {:x?}", write, other),
+        }
+    }
+
+    async fn transaction(
+        &mut self,
+        _address: u8,
+        _operations: &mut [Operation<'_>],
+    ) -> Result<(), Self::Error> {
+        panic!("unexpected transaction");
+    }
+}
+
+fn gp2y_reading(distance_x16: u16) -> Vec<u8> {
+    vec![(distance_x16 >> 4) as u8, (distance_x16 & 0xf) as u8]
+}
+
+/// Reading back the shift, median and accumulation registers.
+fn gp2y_settings(shift: u8, median: u8, accumulation: u8) -> Vec<Expect> {
+    vec![
+        Expect::WriteRead(GP2Y0E02B_ADDR, vec![0x35], vec![shift]),
+        Expect::WriteRead(GP2Y0E02B_ADDR, vec![0x3F], vec![median]),
+        Expect::WriteRead(GP2Y0E02B_ADDR, vec![0xA8], vec![accumulation]),
+    ]
+}
+
+#[test]
+fn gp2y0e02b_reads_millimetres() {
+    let settings = LaserSettings::init();
+    let reading =
+        |distance_x16| Expect::WriteRead(GP2Y0E02B_ADDR, vec![0x5E], gp2y_reading(distance_x16));
+    let mut expected = vec![
+        Expect::Write(GP2Y0E02B_ADDR, vec![0x35, 0x01]),
+        Expect::Write(GP2Y0E02B_ADDR, vec![0x3F, 0x10]),
+        Expect::Write(GP2Y0E02B_ADDR, vec![0xA8, 0x01]),
+        // 320 / 32 * 10 = 100mm with the 128cm shift
+        reading(320),
+        // Closer than the sensor minimum
+        reading(0),
+        Expect::Fail,
+    ];
+    for _ in 3..GP2Y0E02B_SETTINGS_CHECK_READS - 1 {
+        expected.push(reading(320));
+    }
+    // The settings are checked every few reads
+    expected.extend(gp2y_settings(0x01, 0x10, 0x01));
+    expected.push(reading(320));
+    for _ in 1..GP2Y0E02B_SETTINGS_CHECK_READS {
+        expected.push(reading(320));
+    }
+    // Back to the default shift after a reset
+    expected.extend(gp2y_settings(0x02, 0x00, 0x01));
+    let mut sensor = LaserSensor::new(DistanceSensorKind::Gp2Y0E02b, MockI2c::new(expected));
+    block_on(async {
+        sensor.init(&settings).await.unwrap();
+        assert_eq!(sensor.max_distance(), 1279);
+        assert_eq!(sensor.read().await, Ok(Some(100)));
+        assert_eq!(sensor.read().await, Ok(Some(20)));
+        assert_eq!(
+            sensor.read().await.unwrap_err().state(),
+            LaserChannelState::I2cError
+        );
+        for _ in 3..GP2Y0E02B_SETTINGS_CHECK_READS {
+            assert_eq!(sensor.read().await, Ok(Some(100)));
+        }
+        for _ in 1..GP2Y0E02B_SETTINGS_CHECK_READS {
+            assert_eq!(sensor.read().await, Ok(Some(100)));
+        }
+        let reset = sensor.read().await.unwrap_err();
+        assert_eq!(reset, DistanceError::Reset);
+        assert_eq!(reset.state(), LaserChannelState::Reset);
+    });
+}
+
+#[test]
+fn gp2y0e02b_reset_with_the_default_shift() {
+    let settings = LaserSettings {
+        shift: Gp2Y0E02bShift::Cm64,
+        median: Gp2Y0E02bMedian::Med1,
+        accumulation: Gp2Y0E02bAcc::Acc10,
+    };
+    let mut expected = vec![
+        Expect::Write(GP2Y0E02B_ADDR, vec![0x35, 0x02]),
+        Expect::Write(GP2Y0E02B_ADDR, vec![0x3F, 0x30]),
+        Expect::Write(GP2Y0E02B_ADDR, vec![0xA8, 0x03]),
+    ];
+    for _ in 1..GP2Y0E02B_SETTINGS_CHECK_READS {
+        expected.push(Expect::WriteRead(
+            GP2Y0E02B_ADDR,
+            vec![0x5E],
+            gp2y_reading(640),
+        ));
+    }
+    // Only the median and accumulation show the reset
+    expected.extend(gp2y_settings(0x02, 0x00, 0x00));
+    let mut sensor = LaserSensor::new(DistanceSensorKind::Gp2Y0E02b, MockI2c::new(expected));
+    block_on(async {
+        sensor.init(&settings).await.unwrap();
+        for _ in 1..GP2Y0E02B_SETTINGS_CHECK_READS {
+            assert_eq!(sensor.read().await, Ok(Some(100)));
+        }
+        assert_eq!(sensor.read().await, Err(DistanceError::Reset));
+    });
+}
+
+fn vl53l1x_reg(reg: u16) -> Vec<u8> {
+    reg.to_be_bytes().to_vec()
+}
+
+fn vl53l1x_result(status: u8, distance: u16) -> Vec<u8> {
+    let mut result = vec![0u8; 17];
+    result[0] = status;
+    result[13..15].copy_from_slice(&distance.to_be_bytes());
+    result
+}
+
+fn vl53l1x_health() -> Vec<Expect> {
+    vec![
+        Expect::WriteRead(VL53L1X_ADDR, vl53l1x_reg(0x00E5), vec![0x01]),
+        Expect::WriteRead(
+            VL53L1X_ADDR,
+            vl53l1x_reg(0x010F),
+            VL53L1X_ID.to_be_bytes().to_vec(),
+        ),
+    ]
+}
+
+#[test]
+fn vl53l1x_starts_ranging_and_reads_millimetres() {
+    let mut config = vl53l1x_reg(0x002D);
+    config.extend_from_slice(&VL53L1X_DEFAULT_CONFIG);
+    let clear = || Expect::Write(VL53L1X_ADDR, vec![0x00, 0x86, 0x01]);
+    let ready = || Expect::WriteRead(VL53L1X_ADDR, vl53l1x_reg(0x0031), vec![0x01]);
+    let mut expected = vl53l1x_health();
+    expected.extend([
+        Expect::Write(VL53L1X_ADDR, config),
+        Expect::WriteRead(VL53L1X_ADDR, vl53l1x_reg(0x0030), vec![0x02]),
+        Expect::Write(VL53L1X_ADDR, vec![0x00, 0x87, 0x40]),
+        // Calibrating
+        Expect::WriteRead(VL53L1X_ADDR, vl53l1x_reg(0x0031), vec![0x00]),
+        ready(),
+        clear(),
+        Expect::Write(VL53L1X_ADDR, vec![0x00, 0x87, 0x00]),
+        Expect::Write(VL53L1X_ADDR, vec![0x00, 0x08, 0x09]),
+        Expect::Write(VL53L1X_ADDR, vec![0x00, 0x0B, 0x00]),
+        Expect::Write(VL53L1X_ADDR, vec![0x00, 0x87, 0x40]),
+        // No new measurement yet
+        Expect::WriteRead(VL53L1X_ADDR, vl53l1x_reg(0x0031), vec![0x00]),
+        ready(),
+        Expect::WriteRead(VL53L1X_ADDR, vl53l1x_reg(0x0089), vl53l1x_result(9, 1830)),
+        clear(),
+        // Nothing in sight
+        ready(),
+        Expect::WriteRead(VL53L1X_ADDR, vl53l1x_reg(0x0089), vl53l1x_result(4, 0)),
+        clear(),
+        // Sigma failure
+        ready(),
+        Expect::WriteRead(VL53L1X_ADDR, vl53l1x_reg(0x0089), vl53l1x_result(6, 500)),
+        clear(),
+    ]);
+
+    let mut sensor = LaserSensor::new(DistanceSensorKind::Vl53l1x, MockI2c::new(expected));
+    block_on(async {
+        sensor.init(&LaserSettings::init()).await.unwrap();
+        assert_eq!(sensor.read().await, Ok(None));
+        // The calibration ends without a distance
+        assert_eq!(sensor.read().await, Ok(None));
+        assert_eq!(sensor.read().await, Ok(None));
+        assert_eq!(sensor.read().await, Ok(Some(1830)));
+        assert_eq!(sensor.read().await, Ok(Some(VL53L1X_MAX_DISTANCE)));
+        assert_eq!(sensor.read().await, Err(DistanceError::OutOfRange));
+    });
+}
+
+#[test]
+fn vl53l1x_calibration_times_out() {
+    let mut config = vl53l1x_reg(0x002D);
+    config.extend_from_slice(&VL53L1X_DEFAULT_CONFIG);
+    let mut expected = vl53l1x_health();
+    expected.extend([
+        Expect::Write(VL53L1X_ADDR, config),
+        Expect::WriteRead(VL53L1X_ADDR, vl53l1x_reg(0x0030), vec![0x02]),
+        Expect::Write(VL53L1X_ADDR, vec![0x00, 0x87, 0x40]),
+        Expect::WriteRead(VL53L1X_ADDR, vl53l1x_reg(0x0031), vec![0x00]),
+    ]);
+    let mut sensor = LaserSensor::new(DistanceSensorKind::Vl53l1x, MockI2c::new(expected));
+    block_on(async {
+        sensor.init(&LaserSettings::init()).await.unwrap();
+        std::thread::sleep(std::time::Duration::from_millis(250));
+        assert_eq!(sensor.read().await, Err(DistanceError::NotBooted));
+    });
+}
+
+#[test]
+fn vl53l1x_rejects_other_devices() {
+    let mut sensor = LaserSensor::new(
+        DistanceSensorKind::Vl53l1x,
+        MockI2c::new(vec![
+            Expect::WriteRead(VL53L1X_ADDR, vl53l1x_reg(0x00E5), vec![0x01]),
+            // A VL53L0X does not know 16 bit register addresses
+            Expect::WriteRead(VL53L1X_ADDR, vl53l1x_reg(0x010F), vec![0xEE, 0xAA]),
+        ]),
+    );
+    assert_eq!(
+        block_on(sensor.init(&LaserSettings::init())),
+        Err(DistanceError::WrongDevice)
+    );
+}
+
+/// Writes of `(register, value)` pairs to the VL53L0X.
+fn vl53l0x_writes(steps: &[(u8, u8)]) -> Vec<Expect> {
+    steps
+        .iter()
+        .map(|&(reg, value)| Expect::Write(VL53L0X_ADDR, vec![reg, value]))
+        .collect()
+}
+
+fn vl53l0x_read(reg: u8, values: &[u8]) -> Expect {
+    Expect::WriteRead(VL53L0X_ADDR, vec![reg], values.to_vec())
+}
+
+fn vl53l0x_result(status: u8, distance: u16) -> Vec<u8> {
+    let mut result = vec![0u8; 12];
+    result[0] = status << 3;
+    result[10..12].copy_from_slice(&distance.to_be_bytes());
+    result
+}
+
+#[test]
+fn vl53l0x_calibrates_and_reads_millimetres() {
+    let mut expected = vec![
+        vl53l0x_read(0xC0, &[VL53L0X_ID]),
+        vl53l0x_read(0x89, &[0x00]),
+        Expect::Write(VL53L0X_ADDR, vec![0x89, 0x01]),
+        Expect::Write(VL53L0X_ADDR, vec![0x88, 0x00]),
+    ];
+    expected.extend(vl53l0x_writes(&[(0x80, 0x01), (0xFF, 0x01), (0x00, 0x00)]));
+    expected.push(vl53l0x_read(0x91, &[0x3C]));
+    expected.extend(vl53l0x_writes(&[(0x00, 0x01), (0xFF, 0x00), (0x80, 0x00)]));
+    expected.extend([
+        vl53l0x_read(0x60, &[0x00]),
+        Expect::Write(VL53L0X_ADDR, vec![0x60, 0x12]),
+        Expect::Write(VL53L0X_ADDR, vec![0x44, 0x00, 0x20]),
+        Expect::Write(VL53L0X_ADDR, vec![0x01, 0xFF]),
+    ]);
+    // Factory SPAD values: 5 aperture SPADs
+    expected.extend(vl53l0x_writes(&[
+        (0x80, 0x01),
+        (0xFF, 0x01),
+        (0x00, 0x00),
+        (0xFF, 0x06),
+    ]));
+    expected.extend([
+        vl53l0x_read(0x83, &[0x00]),
+        Expect::Write(VL53L0X_ADDR, vec![0x83, 0x04]),
+    ]);
+    expected.extend(vl53l0x_writes(&[
+        (0xFF, 0x07),
+        (0x81, 0x01),
+        (0x80, 0x01),
+        (0x94, 0x6B),
+        (0x83, 0x00),
+    ]));
+    expected.extend([
+        vl53l0x_read(0x83, &[0x00]),
+        vl53l0x_read(0x83, &[0x10]),
+        Expect::Write(VL53L0X_ADDR, vec![0x83, 0x01]),
+        vl53l0x_read(0x92, &[0x85]),
+    ]);
+    expected.extend(vl53l0x_writes(&[(0x81, 0x00), (0xFF, 0x06)]));
+    expected.extend([
+        vl53l0x_read(0x83, &[0x14]),
+        Expect::Write(VL53L0X_ADDR, vec![0x83, 0x10]),
+    ]);
+    expected.extend(vl53l0x_writes(&[
+        (0xFF, 0x01),
+        (0x00, 0x01),
+        (0xFF, 0x00),
+        (0x80, 0x00),
+    ]));
+    expected.push(vl53l0x_read(0xB0, &[0xFF; 6]));
+    expected.extend(vl53l0x_writes(&[
+        (0xFF, 0x01),
+        (0x4F, 0x00),
+        (0x4E, 0x2C),
+        (0xFF, 0x00),
+        (0xB6, 0xB4),
+    ]));
+    // SPADs 12 to 16
+    expected.push(Expect::Write(
+        VL53L0X_ADDR,
+        vec![0xB0, 0x00, 0xF0, 0x01, 0x00, 0x00, 0x00],
+    ));
+    expected.extend(vl53l0x_writes(&VL53L0X_TUNING));
+    expected.extend([
+        Expect::Write(VL53L0X_ADDR, vec![0x0A, 0x04]),
+        vl53l0x_read(0x84, &[0x11]),
+        Expect::Write(VL53L0X_ADDR, vec![0x84, 0x01]),
+        Expect::Write(VL53L0X_ADDR, vec![0x0B, 0x01]),
+        // VHV calibration
+        Expect::Write(VL53L0X_ADDR, vec![0x01, 0x01]),
+        Expect::Write(VL53L0X_ADDR, vec![0x00, 0x41]),
+        vl53l0x_read(0x13, &[0x00]),
+        vl53l0x_read(0x13, &[0x04]),
+        Expect::Write(VL53L0X_ADDR, vec![0x0B, 0x01]),
+        Expect::Write(VL53L0X_ADDR, vec![0x00, 0x00]),
+        // Phase calibration
+        Expect::Write(VL53L0X_ADDR, vec![0x01, 0x02]),
+        Expect::Write(VL53L0X_ADDR, vec![0x00, 0x01]),
+        vl53l0x_read(0x13, &[0x04]),
+        Expect::Write(VL53L0X_ADDR, vec![0x0B, 0x01]),
+        Expect::Write(VL53L0X_ADDR, vec![0x00, 0x00]),
+        Expect::Write(VL53L0X_ADDR, vec![0x01, 0xE8]),
+    ]);
+    expected.extend(vl53l0x_writes(&[
+        (0x80, 0x01),
+        (0xFF, 0x01),
+        (0x00, 0x00),
+        (0x91, 0x3C),
+        (0x00, 0x01),
+        (0xFF, 0x00),
+        (0x80, 0x00),
+        (0x00, 0x02),
+    ]));
+    let clear = || Expect::Write(VL53L0X_ADDR, vec![0x0B, 0x01]);
+    expected.extend([
+        vl53l0x_read(0x13, &[0x04]),
+        vl53l0x_read(0x14, &vl53l0x_result(11, 735)),
+        clear(),
+        // Nothing in sight
+        vl53l0x_read(0x13, &[0x04]),
+        vl53l0x_read(0x14, &vl53l0x_result(11, 8190)),
+        clear(),
+        // VCSEL failure
+        vl53l0x_read(0x13, &[0x04]),
+        vl53l0x_read(0x14, &vl53l0x_result(1, 500)),
+        clear(),
+    ]);
+
+    let mut sensor = LaserSensor::new(DistanceSensorKind::Vl53l0x, MockI2c::new(expected));
+    block_on(async {
+        sensor.init(&LaserSettings::init()).await.unwrap();
+        assert_eq!(sensor.max_distance(), VL53L0X_MAX_DISTANCE);
+        // Both calibrations end without a distance
+        assert_eq!(sensor.read().await, Ok(None));
+        assert_eq!(sensor.read().await, Ok(None));
+        assert_eq!(sensor.read().await, Ok(None));
+        assert_eq!(sensor.read().await, Ok(Some(735)));
+        assert_eq!(sensor.read().await, Ok(Some(VL53L0X_MAX_DISTANCE)));
+        assert_eq!(sensor.read().await, Err(DistanceError::OutOfRange));
+    });
+}
+
+#[test]
+fn vl53l0x_rejects_other_devices() {
+    let mut sensor = LaserSensor::new(
+        DistanceSensorKind::Vl53l0x,
+        MockI2c::new(vec![vl53l0x_read(0xC0, &[0xEA])]),
+    );
+    assert_eq!(
+        block_on(sensor.init(&LaserSettings::init())),
+        Err(DistanceError::WrongDevice)
+    );
+}
+
+#[test]
+fn sensor_models_come_from_the_beam_table() {
+    assert_eq!(
+        sensor_kind(LASER_GEOMETRY, 2),
+        DistanceSensorKind::Gp2Y0E02b
+    );
+    let beams = [
+        LaserBeam::new(2, LaserTier::Upper, Angle::ZERO).sensor(DistanceSensorKind::Vl53l1x),
+        LaserBeam::new(3, LaserTier::Upper, Angle::from(30)),
+    ];
+    assert_eq!(sensor_kind(&beams, 2), DistanceSensorKind::Vl53l1x);
+    assert_eq!(sensor_kind(&beams, 3), DistanceSensorKind::Gp2Y0E02b);
+    // Channels without a beam
+    assert_eq!(sensor_kind(&beams, 7), DistanceSensorKind::Gp2Y0E02b);
+}
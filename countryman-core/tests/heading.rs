@@ -0,0 +1,88 @@
+use countryman_core::heading::{wrap_cdeg, DriftMeter, HeadingTracker, DRIFT_MIN_WINDOW};
+use countryman_core::imu::{ImuData, ImuExtras, ImuStability};
+use countryman_core::race::Angle;
+use embassy_time::{Duration, Instant};
+
+fn at(ms: u64) -> Instant {
+    Instant::from_millis(1000 + ms)
+}
+
+fn sample(yaw: i16, ms: u64) -> ImuData {
+    ImuData {
+        yaw,
+        timestamp: at(ms),
+        ..ImuData::init()
+    }
+}
+
+/// Feeds a yaw drifting by `rate` centidegrees per minute from `yaw` at
+/// `from_ms`, every 100ms.
+fn drift(meter: &mut DriftMeter, yaw: i16, rate: i32, from_ms: u64, for_ms: u64) -> i16 {
+    let mut current = yaw;
+    for ms in (0..=for_ms).step_by(100) {
+        current = wrap_cdeg(yaw as i32 + rate * ms as i32 / 60_000) as i16;
+        meter.update(&sample(current, from_ms + ms));
+    }
+    current
+}
+
+#[test]
+fn drift_meter_measures_a_still_car() {
+    let mut meter = DriftMeter::new();
+    // Across the wrap around
+    let yaw = drift(&mut meter, 17990, 120, 0, 9_000);
+    assert_eq!(meter.rate(), None);
+    drift(&mut meter, yaw, 120, 9_000, 3_000);
+    assert_eq!(meter.elapsed(), Duration::from_millis(12_000));
+    assert_eq!(meter.rate(), Some(120));
+}
+
+#[test]
+fn drift_meter_restarts_when_the_car_moves() {
+    let mut meter = DriftMeter::new();
+    let yaw = drift(&mut meter, 0, 60, 0, 12_000);
+    assert!(meter.rate().is_some());
+
+    // Turned by hand
+    meter.update(&sample(yaw + 500, 12_100));
+    assert_eq!(meter.elapsed(), Duration::from_ticks(0));
+    assert_eq!(meter.rate(), None);
+
+    drift(
+        &mut meter,
+        yaw + 500,
+        60,
+        12_200,
+        DRIFT_MIN_WINDOW.as_millis(),
+    );
+    assert!(meter.rate().is_some());
+    let mut moving = sample(yaw + 500, 30_000);
+    moving.extras = Some(ImuExtras {
+        stability: ImuStability::Motion,
+        ..ImuExtras::new()
+    });
+    meter.update(&moving);
+    assert_eq!(meter.rate(), None);
+}
+
+#[test]
+fn heading_tracker_removes_drift_and_reanchors() {
+    let mut heading = HeadingTracker::new(-17000, at(0));
+    assert_eq!(heading.heading(-17000, at(0)), Angle::ZERO);
+    // Across the wrap around
+    assert_eq!(heading.heading(17000, at(0)), Angle::from(-20));
+
+    heading.set_drift_rate(600, at(0));
+    // 10 degrees of drift after a minute
+    assert_eq!(heading.drift(at(60_000)), 600);
+    assert_eq!(heading.heading_cdeg(-16400, at(60_000)), 0);
+
+    heading.anchor(-16400, at(60_000), Angle::R90);
+    assert_eq!(heading.heading(-16400, at(60_000)), Angle::R90);
+    assert_eq!(heading.heading_cdeg(-16340, at(66_000)), 9000);
+    assert_eq!(heading.anchors(), 1);
+
+    heading.rezero(5000, at(70_000));
+    assert_eq!(heading.heading(5000, at(70_000)), Angle::ZERO);
+    assert_eq!((heading.drift_rate(), heading.anchors()), (600, 0));
+}
@@ -0,0 +1,228 @@
+use countryman_core::configuration::{RaceConfig, RaceConfigEntry};
+use countryman_core::geometry::{calibration_targets, LaserBeam, LaserTier};
+use countryman_core::lasers::{
+    Gp2Y0E02bAcc, Gp2Y0E02bMedian, Gp2Y0E02bShift, I2cBusHealth, LaserCalibration,
+    LaserCalibrationSampler, LaserChannelHealth, LaserChannelState, LaserFault, LaserSettings,
+    LasersCalibration, RawLaserReadings, I2C_STUCK_TIMEOUTS, LASER_CALIBRATION_DISTANCES,
+    LASER_CALIBRATION_SAMPLES, LASER_CALIBRATION_TIMEOUT, LASER_MAX_CONSECUTIVE_ERRORS,
+    LASER_STUCK_FRAMES, RAW_LASERS_COUNT,
+};
+use countryman_core::race::Angle;
+use countryman_core::vision::LASER_OVERFLOW;
+use embassy_time::{Duration, Instant};
+
+#[test]
+fn channel_health_detects_errors_and_stuck_values() {
+    let mut health = LaserChannelHealth::new();
+    for _ in 1..LASER_MAX_CONSECUTIVE_ERRORS {
+        assert_eq!(health.update(Err(LaserChannelState::Timeout), 1279), None);
+    }
+    assert_eq!(
+        health.update(Err(LaserChannelState::OutOfRange), 1279),
+        Some(LaserFault::OutOfRange)
+    );
+    assert_eq!(health.out_of_range, 1);
+    health.reinitialized();
+    assert_eq!(health.reinits, 1);
+
+    // Nothing in sight always reads the same value
+    for _ in 0..(LASER_STUCK_FRAMES * 2) {
+        assert_eq!(health.update(Ok(1279), 1279), None);
+    }
+    // A real distance does not
+    let mut fault = None;
+    for _ in 0..=LASER_STUCK_FRAMES {
+        fault = fault.or(health.update(Ok(345), 1279));
+    }
+    assert_eq!(fault, Some(LaserFault::Stuck));
+    assert_eq!(health.update(Ok(346), 1279), None);
+
+    // A reset sensor is re-initialized at once
+    assert_eq!(
+        health.update(Err(LaserChannelState::Reset), 1279),
+        Some(LaserFault::Reset)
+    );
+    assert_eq!(health.last_fault, Some(LaserFault::Reset));
+}
+
+#[test]
+fn channel_health_ignores_obstacles_closer_than_the_minimum() {
+    let mut health = LaserChannelHealth::new();
+    // The nose of the car on a wall
+    let minimum = Gp2Y0E02bShift::Cm128.clamp_reading(0);
+    for _ in 0..(LASER_STUCK_FRAMES * 2) {
+        assert_eq!(health.update(Ok(minimum), 1279), None);
+    }
+    assert_eq!(health.last_fault, None);
+}
+
+#[test]
+fn laser_settings_come_from_the_config() {
+    let mut config = RaceConfig::init();
+    assert_eq!(config.laser_settings(), LaserSettings::init());
+
+    config.set(RaceConfigEntry::LaserShift, 1);
+    config.set(RaceConfigEntry::LaserMedian, 0);
+    config.set(RaceConfigEntry::LaserAccumulation, 3);
+    let settings = config.laser_settings();
+    assert_eq!(settings.shift, Gp2Y0E02bShift::Cm64);
+    assert_eq!(settings.median, Gp2Y0E02bMedian::Med1);
+    assert_eq!(settings.accumulation, Gp2Y0E02bAcc::Acc30);
+    assert_eq!(
+        RaceConfigEntry::LaserAccumulation.value_name(3),
+        Some("ACC30")
+    );
+
+    assert_eq!(settings.shift.max_distance(), 639);
+    assert_eq!(settings.shift.clamp_reading(639), 639);
+    assert_eq!(settings.shift.clamp_reading(700), 639);
+    // Closer than the sensor minimum
+    assert_eq!(settings.shift.clamp_reading(5), 20);
+}
+
+#[test]
+fn calibration_fits_and_corrects_readings() {
+    // A sensor reading 8% long with a 20mm offset
+    let readings: Vec<u16> = LASER_CALIBRATION_DISTANCES
+        .iter()
+        .map(|d| d * 108 / 100 + 20)
+        .collect();
+    let calibration = LaserCalibration::fit(&readings, &LASER_CALIBRATION_DISTANCES).unwrap();
+    for (reading, distance) in readings.iter().zip(LASER_CALIBRATION_DISTANCES.iter()) {
+        assert!((calibration.apply(*reading) as i32 - *distance as i32).abs() <= 2);
+    }
+
+    // Blocked sensors read the same at every distance
+    assert_eq!(
+        LaserCalibration::fit(&[50, 50, 50], &LASER_CALIBRATION_DISTANCES),
+        None
+    );
+
+    let mut calibrations = LasersCalibration::init();
+    calibrations.channels[1] = calibration;
+    let mut raw = RawLaserReadings::new(
+        [324, 344, 1279, 0, 0, 0, 0, 0],
+        Instant::from_millis(0),
+        Duration::from_millis(10),
+    );
+    raw.calibrate(&calibrations, &[1279; RAW_LASERS_COUNT]);
+    assert_eq!(raw.values[0], 324);
+    assert!((raw.values[1] as i32 - 300).abs() <= 2);
+    assert_eq!(raw.raw[1], 344);
+    // Nothing in sight reads as the vision overflow
+    assert_eq!(raw.values[2], LASER_OVERFLOW);
+
+    // Also with the 64cm shift
+    let mut raw = RawLaserReadings::new(
+        [639, 500, 0, 0, 0, 0, 0, 0],
+        Instant::from_millis(0),
+        Duration::from_millis(10),
+    );
+    raw.calibrate(
+        &LasersCalibration::init(),
+        &[Gp2Y0E02bShift::Cm64.max_distance(); RAW_LASERS_COUNT],
+    );
+    assert_eq!(raw.values[0], LASER_OVERFLOW);
+    assert_eq!(raw.values[1], 500);
+}
+
+#[test]
+fn calibration_targets_follow_the_beam_angles() {
+    // A flat board is further away for the side beams
+    let center = LaserBeam::new(7, LaserTier::Lower, Angle::SC).offset(0, -20);
+    let side = LaserBeam::new(4, LaserTier::Upper, Angle::SLL);
+    assert_eq!(center.board_distance(300), 320);
+    assert_eq!(side.board_distance(300), 600);
+
+    let targets = calibration_targets(&[center, side], 600, LASER_OVERFLOW);
+    assert_eq!(targets[7], Some(620));
+    // Beyond the sensor range
+    assert_eq!(targets[4], None);
+    assert_eq!(targets[0], None);
+
+    // A side sensor reading its real distance is left alone
+    let points: Vec<Option<(u16, u16)>> = LASER_CALIBRATION_DISTANCES
+        .iter()
+        .map(|d| Some(side.board_distance(*d)).filter(|d| *d < LASER_OVERFLOW))
+        .map(|target| target.map(|t| (t, t)))
+        .collect();
+    assert_eq!(points[2], None);
+    assert_eq!(
+        LaserCalibration::fit_points(&points),
+        Some(LaserCalibration::init())
+    );
+    assert_eq!(
+        LaserCalibration::fit_points(&[Some((200, 200)), None]),
+        None
+    );
+}
+
+#[test]
+fn calibration_sampler_averages_valid_channels() {
+    let mut sampler = LaserCalibrationSampler::new(u8::MAX);
+    let mut raw = RawLaserReadings::new(
+        [100; RAW_LASERS_COUNT],
+        Instant::from_millis(0),
+        Duration::from_millis(10),
+    );
+    raw.set(3, Err(LaserChannelState::Timeout), raw.timestamp);
+    for sample in 0..LASER_CALIBRATION_SAMPLES {
+        raw.raw[0] = 100 + (sample % 2) * 2;
+        sampler.add(&raw);
+    }
+    assert_eq!(sampler.missing(), 1);
+    assert!(!sampler.is_done());
+    assert_eq!(sampler.average(0), Some(101));
+    assert_eq!(sampler.average(3), None);
+    // A dead channel does not hold the sampling forever
+    raw.timestamp += LASER_CALIBRATION_TIMEOUT;
+    sampler.add(&raw);
+    assert!(sampler.is_done());
+    assert_eq!(sampler.average(3), None);
+
+    // Channels not read for a frame are not sampled again
+    let mut sampler = LaserCalibrationSampler::new(u8::MAX);
+    raw.updated = 0;
+    raw.set(1, Ok(100), raw.timestamp);
+    for _ in 0..LASER_CALIBRATION_SAMPLES {
+        sampler.add(&raw);
+    }
+    assert_eq!(sampler.missing(), RAW_LASERS_COUNT - 1);
+    assert_eq!(sampler.average(1), Some(100));
+
+    // Only the selected channels, and not where nothing is in sight
+    let mut sampler = LaserCalibrationSampler::new(0b111);
+    raw.updated = 0;
+    raw.set(0, Ok(1279), raw.timestamp);
+    raw.set(1, Ok(100), raw.timestamp);
+    // Beyond LASER_OVERFLOW once calibrated, but in sight of the sensor
+    raw.set(2, Ok(1250), raw.timestamp);
+    raw.calibrate(&LasersCalibration::init(), &[1279; RAW_LASERS_COUNT]);
+    assert!(raw.is_overflowing(0));
+    assert!(raw.values[2] >= LASER_OVERFLOW);
+    for _ in 0..LASER_CALIBRATION_SAMPLES {
+        sampler.add(&raw);
+    }
+    assert_eq!(sampler.missing(), 1);
+    assert_eq!(sampler.average(0), None);
+    assert_eq!(sampler.average(1), Some(100));
+    assert_eq!(sampler.average(2), Some(1250));
+    assert_eq!(sampler.average(3), None);
+}
+
+#[test]
+fn consecutive_timeouts_mean_a_stuck_bus() {
+    let mut bus = I2cBusHealth::new();
+    for _ in 1..I2C_STUCK_TIMEOUTS {
+        assert!(!bus.update(Err(LaserChannelState::Timeout)));
+    }
+    // A sensor that does not acknowledge is not a stuck bus
+    assert!(!bus.update(Err(LaserChannelState::I2cError)));
+    for _ in 1..I2C_STUCK_TIMEOUTS {
+        assert!(!bus.update(Err(LaserChannelState::Timeout)));
+    }
+    assert!(bus.update(Err(LaserChannelState::Timeout)));
+    bus.recovered();
+    assert_eq!(bus.recoveries, 1);
+    assert!(!bus.update(Ok(300)));
+}
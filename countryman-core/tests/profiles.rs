@@ -0,0 +1,49 @@
+use countryman_core::configuration::RaceConfig;
+use countryman_core::profiles::{RaceProfile, RaceProfiles, MAX_PROFILES};
+
+#[test]
+fn built_in_profiles() {
+    let profiles = RaceProfiles::init();
+    let names: Vec<&str> = profiles.iter().map(|p| p.name()).collect();
+    assert_eq!(names, ["SAFE", "FAST", "RAMP"]);
+    assert_eq!(profiles.active().name(), "SAFE");
+    assert!(*profiles.config() == RaceConfig::init());
+}
+
+#[test]
+fn selection_wraps_around() {
+    let mut profiles = RaceProfiles::init();
+    profiles.select_prev();
+    assert_eq!(profiles.active().name(), "RAMP");
+    profiles.select_next();
+    assert_eq!(profiles.active().name(), "SAFE");
+}
+
+#[test]
+fn clone_gets_a_unique_name_until_full() {
+    let mut profiles = RaceProfiles::init();
+    profiles.select(1);
+    assert!(profiles.clone_active());
+    assert_eq!(profiles.active().name(), "FAST2");
+    assert!(profiles.active().config == profiles.get(1).unwrap().config);
+    profiles.select(1);
+    assert!(profiles.clone_active());
+    assert_eq!(profiles.active().name(), "FAST3");
+    assert!(profiles.clone_active());
+    assert_eq!(profiles.len(), MAX_PROFILES);
+    assert!(!profiles.clone_active());
+}
+
+#[test]
+fn rename_by_characters() {
+    let mut profile = RaceProfile::new("ramp", RaceConfig::init());
+    assert_eq!(profile.name(), "RAMP");
+    profile.change_name_char(5, 1);
+    assert_eq!(profile.name(), "RAMP A");
+    profile.change_name_char(5, -2);
+    assert_eq!(profile.name(), "RAMP -");
+    profile.change_name_char(5, 1);
+    assert_eq!(profile.name(), "RAMP");
+    profile.rename("much too long");
+    assert_eq!(profile.name(), "MUCH TOO");
+}
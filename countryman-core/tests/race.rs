@@ -0,0 +1,179 @@
+use countryman_core::configuration::RaceConfig;
+use countryman_core::heading::HeadingTracker;
+use countryman_core::imu::{ImuData, ImuLinkStats};
+use countryman_core::lasers::{RawLaserReadings, RAW_LASERS_COUNT};
+use countryman_core::race::{Angle, RaceColor, RaceController, RaceInputs};
+use countryman_core::rgb::RgbEvent;
+use countryman_core::vision::LASER_OVERFLOW;
+use embassy_time::{Duration, Instant};
+
+const STEP: Duration = Duration::from_millis(10);
+
+fn at(ms: u64) -> Instant {
+    Instant::from_millis(1000 + ms)
+}
+
+fn open_track() -> RaceInputs {
+    RaceInputs {
+        lasers: RawLaserReadings::new([LASER_OVERFLOW; RAW_LASERS_COUNT], at(0), STEP),
+        imu: ImuData {
+            yaw: 0,
+            pitch: 0,
+            roll: 0,
+            side: 1000,
+            forward: 1000,
+            vertical: 0,
+            timestamp: at(0),
+            dt: STEP,
+            last_stillness: None,
+            link: ImuLinkStats::new(),
+            extras: None,
+        },
+        rgb: RgbEvent::empty(),
+    }
+}
+
+fn after_sprint(config: &RaceConfig, inputs: &RaceInputs) -> (RaceController, u64) {
+    let mut controller = RaceController::new(config, HeadingTracker::new(0, at(0)), false, at(0));
+    let mut ms = 0;
+    while ms <= config.sprint_time as u64 {
+        ms += 10;
+        controller.step(inputs, at(ms));
+    }
+    (controller, ms)
+}
+
+#[test]
+fn sprint_then_regular_driving() {
+    let config = RaceConfig::init();
+    let inputs = open_track();
+    let mut controller = RaceController::new(&config, HeadingTracker::new(0, at(0)), false, at(0));
+
+    let output = controller.step(&inputs, at(10));
+    assert_eq!(output.color, RaceColor::White);
+    assert_eq!(output.action.power, config.sprint_speed);
+
+    let (mut controller, ms) = after_sprint(&config, &inputs);
+    let output = controller.step(&inputs, at(ms + 10));
+    assert_eq!(output.color, RaceColor::Green);
+    assert_eq!(output.action.power, config.max_speed);
+}
+
+#[test]
+fn back_panic_reverses() {
+    let config = RaceConfig::init();
+    let mut inputs = open_track();
+    let mut controller = RaceController::new(&config, HeadingTracker::new(0, at(0)), false, at(0));
+    // center beams, upper and lower
+    inputs.lasers.values[2] = 40;
+    inputs.lasers.values[7] = 40;
+
+    let output = controller.step(&inputs, at(10));
+    assert_eq!(output.color, RaceColor::Red);
+    assert_eq!(output.action.power, -config.back_speed);
+    assert!(output.trace.has_back_panic);
+}
+
+#[test]
+fn tilt_alert_stops() {
+    let config = RaceConfig::init();
+    let mut inputs = open_track();
+    inputs.imu.roll = 6000;
+    let mut controller = RaceController::new(&config, HeadingTracker::new(0, at(0)), false, at(0));
+
+    let output = controller.step(&inputs, at(10));
+    assert_eq!(output.color, RaceColor::Black);
+    assert_eq!(output.action.power, 0);
+}
+
+#[test]
+fn stillness_escape_backs_away() {
+    let config = RaceConfig {
+        use_stillness: 1,
+        ..RaceConfig::init()
+    };
+    let mut inputs = open_track();
+    let (mut controller, ms) = after_sprint(&config, &inputs);
+
+    inputs.imu.last_stillness = Some(at(ms));
+    let output = controller.step(&inputs, at(ms + 10));
+    assert_eq!(output.color, RaceColor::Blue);
+    assert!(output.action.power < 0);
+    assert!(output.trace.has_target);
+}
+
+#[test]
+fn climb_redirects_towards_climb_direction() {
+    let config = RaceConfig::init();
+    let mut inputs = open_track();
+    let mut controller = RaceController::new(&config, HeadingTracker::new(0, at(0)), false, at(0));
+
+    inputs.imu.pitch = config.climbing_angle * 100;
+    let output = controller.step(&inputs, at(10));
+    assert_eq!(output.color, RaceColor::Yellow);
+    assert!(output.trace.target == config.climb_direction());
+}
+
+#[test]
+fn color_inversion_turns_around() {
+    let config = RaceConfig {
+        use_color_inversion: 1,
+        ..RaceConfig::init()
+    };
+    let mut inputs = open_track();
+    let (mut controller, ms) = after_sprint(&config, &inputs);
+
+    inputs.rgb = RgbEvent {
+        not_red_for: Duration::from_ticks(0),
+        last_red_for: Duration::from_millis(20),
+        not_green_for: Duration::from_millis(100),
+        last_green_for: Duration::from_millis(20),
+        ..RgbEvent::empty()
+    };
+    let output = controller.step(&inputs, at(ms + 10));
+    assert_eq!(output.color, RaceColor::Yellow);
+    assert!(output.trace.target.abs() == Angle::R170);
+}
+
+#[test]
+fn good_marker_crossing_reanchors_the_heading() {
+    let config = RaceConfig {
+        use_marker_anchor: 1,
+        marker_heading: 90,
+        ..RaceConfig::init()
+    };
+    let mut inputs = open_track();
+    inputs.imu.yaw = 8000;
+    let (mut controller, ms) = after_sprint(&config, &inputs);
+    assert_eq!(
+        controller.step(&inputs, at(ms + 10)).trace.track_heading,
+        Angle::from(80)
+    );
+
+    // Green right after red
+    inputs.rgb = RgbEvent {
+        not_green_for: Duration::from_ticks(0),
+        last_green_for: Duration::from_millis(20),
+        not_red_for: Duration::from_millis(100),
+        last_red_for: Duration::from_millis(20),
+        ..RgbEvent::empty()
+    };
+    let output = controller.step(&inputs, at(ms + 20));
+    assert_eq!(output.trace.track_heading, Angle::R90);
+    assert_eq!(controller.heading().anchors(), 1);
+    // Still on the same marker
+    controller.step(&inputs, at(ms + 30));
+    assert_eq!(controller.heading().anchors(), 1);
+
+    // Crossing far from the marker heading is not trusted
+    inputs.rgb = RgbEvent::empty();
+    inputs.imu.yaw = -9000;
+    controller.step(&inputs, at(ms + 40));
+    inputs.rgb.not_green_for = Duration::from_ticks(0);
+    inputs.rgb.last_green_for = Duration::from_millis(20);
+    inputs.rgb.not_red_for = Duration::from_millis(100);
+    inputs.rgb.last_red_for = Duration::from_millis(20);
+    let output = controller.step(&inputs, at(ms + 50));
+    assert_eq!(output.trace.track_heading, Angle::from(-80));
+    assert_eq!(controller.heading().anchors(), 1);
+}
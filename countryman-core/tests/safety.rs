@@ -0,0 +1,55 @@
+use embassy_time::{Duration, Instant};
+
+use countryman_core::safety::{FaultCode, SafetyLimits, SafetySupervisor};
+
+fn at(ms: u64) -> Instant {
+    Instant::from_millis(1000 + ms)
+}
+
+#[test]
+fn stale_inputs_are_ignored_while_stopped() {
+    let mut supervisor = SafetySupervisor::new(SafetyLimits::init(), at(0));
+    supervisor.motors_commanded(0, at(0));
+    assert_eq!(supervisor.check(at(5000)), None);
+    assert_eq!(supervisor.fault(), None);
+}
+
+#[test]
+fn stale_imu_latches_a_fault_until_acknowledged() {
+    let mut supervisor = SafetySupervisor::new(SafetyLimits::init(), at(0));
+    for ms in (0..=150).step_by(10) {
+        supervisor.lasers_seen(at(ms));
+        assert_eq!(supervisor.motors_commanded(5000, at(ms)), 5000);
+        if ms < 50 {
+            supervisor.imu_seen(at(ms));
+        }
+        let fault = supervisor.check(at(ms));
+        if ms == 150 {
+            assert_eq!(fault, Some(FaultCode::ImuStale));
+        } else {
+            assert_eq!(fault, None);
+        }
+    }
+
+    // Latched: reported once, and the motors stay stopped
+    assert_eq!(supervisor.check(at(160)), None);
+    assert_eq!(supervisor.fault(), Some(FaultCode::ImuStale));
+    assert_eq!(supervisor.motors_commanded(5000, at(170)), 0);
+
+    supervisor.acknowledge(at(200));
+    assert_eq!(supervisor.fault(), None);
+    assert_eq!(supervisor.motors_commanded(5000, at(210)), 5000);
+}
+
+#[test]
+fn missing_motor_commands_are_a_fault() {
+    let limits = SafetyLimits {
+        motors: Duration::from_millis(50),
+        ..SafetyLimits::init()
+    };
+    let mut supervisor = SafetySupervisor::new(limits, at(0));
+    supervisor.motors_commanded(3000, at(0));
+    supervisor.lasers_seen(at(60));
+    supervisor.imu_seen(at(60));
+    assert_eq!(supervisor.check(at(60)), Some(FaultCode::MotorsStale));
+}
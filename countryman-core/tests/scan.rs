@@ -0,0 +1,65 @@
+use countryman_core::geometry::{LaserBeam, LaserTier, LASER_GEOMETRY};
+use countryman_core::lasers::RAW_LASERS_COUNT;
+use countryman_core::race::Angle;
+use countryman_core::scan::{
+    LaserScanHints, LaserScanScheduler, LASER_SCAN_FAST_POWER, LASER_SCAN_MAX_PERIOD,
+};
+
+/// Reads per channel over `LASER_SCAN_MAX_PERIOD` frames.
+fn reads(scheduler: &mut LaserScanScheduler, hints: &LaserScanHints) -> [u8; RAW_LASERS_COUNT] {
+    let mut reads = [0; RAW_LASERS_COUNT];
+    for _ in 0..LASER_SCAN_MAX_PERIOD {
+        let mask = scheduler.next(hints);
+        for (channel, count) in reads.iter_mut().enumerate() {
+            if mask & (1 << channel) != 0 {
+                *count += 1;
+            }
+        }
+    }
+    reads
+}
+
+#[test]
+fn slow_scans_read_the_lower_tier_less_often() {
+    let mut scheduler = LaserScanScheduler::new(LASER_GEOMETRY);
+    let hints = LaserScanHints::init();
+    // Lower beams on channels 0, 6 and 7
+    assert_eq!(reads(&mut scheduler, &hints), [2, 4, 4, 4, 4, 4, 2, 2]);
+
+    let slope = LaserScanHints {
+        slope: true,
+        ..hints
+    };
+    assert_eq!(reads(&mut scheduler, &slope), [4; RAW_LASERS_COUNT]);
+}
+
+#[test]
+fn fast_scans_favour_the_center() {
+    let mut scheduler = LaserScanScheduler::new(LASER_GEOMETRY);
+    let hints = LaserScanHints {
+        power: LASER_SCAN_FAST_POWER,
+        slope: true,
+    };
+    // Center on 2 and 7, 30 degrees on 1, 3, 6 and 0, 60 degrees on 4 and 5
+    assert_eq!(reads(&mut scheduler, &hints), [2, 2, 4, 2, 1, 1, 2, 4]);
+
+    let flat = LaserScanHints {
+        slope: false,
+        ..hints
+    };
+    assert_eq!(reads(&mut scheduler, &flat), [1, 2, 4, 2, 1, 1, 1, 2]);
+}
+
+#[test]
+fn every_frame_reads_something() {
+    // A single lower beam at 60 degrees, read every fourth frame at most
+    let mut scheduler =
+        LaserScanScheduler::new(&[LaserBeam::new(3, LaserTier::Lower, Angle::from(60))]);
+    let hints = LaserScanHints {
+        power: LASER_SCAN_FAST_POWER,
+        slope: false,
+    };
+    for _ in 0..10 {
+        assert_eq!(scheduler.next(&hints), 1 << 3);
+    }
+}
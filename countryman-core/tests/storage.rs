@@ -0,0 +1,164 @@
+use countryman_core::configuration::RaceConfigEntry;
+use countryman_core::lasers::{LaserCalibration, LasersCalibration};
+use countryman_core::profiles::RaceProfiles;
+use countryman_core::storage::{
+    encode_profiles, ConfigStorage, ConfigStorageError, CONFIG_RECORD_SIZE,
+};
+use embedded_storage::nor_flash::{
+    ErrorType, NorFlash, NorFlashError, NorFlashErrorKind, ReadNorFlash,
+};
+
+const SECTOR: usize = 4096;
+
+#[derive(Debug)]
+struct MockFlashError;
+
+impl NorFlashError for MockFlashError {
+    fn kind(&self) -> NorFlashErrorKind {
+        NorFlashErrorKind::Other
+    }
+}
+
+/// Two erased sectors; writes can only clear bits, like real NOR flash.
+struct MockFlash<const WRITE_SIZE: usize = 4> {
+    data: Vec<u8>,
+    erases: usize,
+}
+
+impl<const WRITE_SIZE: usize> MockFlash<WRITE_SIZE> {
+    fn erased() -> Self {
+        Self {
+            data: vec![0xff; SECTOR * 2],
+            erases: 0,
+        }
+    }
+}
+
+impl MockFlash {
+    fn new() -> Self {
+        Self::erased()
+    }
+}
+
+impl<const WRITE_SIZE: usize> ErrorType for MockFlash<WRITE_SIZE> {
+    type Error = MockFlashError;
+}
+
+impl<const WRITE_SIZE: usize> ReadNorFlash for MockFlash<WRITE_SIZE> {
+    const READ_SIZE: usize = 1;
+
+    fn read(&mut self, offset: u32, bytes: &mut [u8]) -> Result<(), Self::Error> {
+        let offset = offset as usize;
+        bytes.copy_from_slice(&self.data[offset..offset + bytes.len()]);
+        Ok(())
+    }
+
+    fn capacity(&self) -> usize {
+        self.data.len()
+    }
+}
+
+impl<const WRITE_SIZE: usize> NorFlash for MockFlash<WRITE_SIZE> {
+    const WRITE_SIZE: usize = WRITE_SIZE;
+    const ERASE_SIZE: usize = SECTOR;
+
+    fn erase(&mut self, from: u32, to: u32) -> Result<(), Self::Error> {
+        self.data[from as usize..to as usize].fill(0xff);
+        self.erases += 1;
+        Ok(())
+    }
+
+    fn write(&mut self, offset: u32, bytes: &[u8]) -> Result<(), Self::Error> {
+        if offset as usize % Self::WRITE_SIZE != 0 || bytes.len() % Self::WRITE_SIZE != 0 {
+            return Err(MockFlashError);
+        }
+        for (i, b) in bytes.iter().enumerate() {
+            self.data[offset as usize + i] &= *b;
+        }
+        Ok(())
+    }
+}
+
+fn tuned() -> RaceProfiles {
+    let mut profiles = RaceProfiles::init();
+    profiles.select(1);
+    profiles.clone_active();
+    profiles.active_mut().rename("NARROW");
+    let config = profiles.config_mut();
+    config.set(RaceConfigEntry::MaxSpeed, 6000);
+    config.set(RaceConfigEntry::SteerBias, 3);
+    config.set(RaceConfigEntry::ClimbDirection, -90);
+    let mut calibration = LasersCalibration::init();
+    calibration.channels[5] = LaserCalibration {
+        offset: -17,
+        scale: 940,
+    };
+    profiles.set_calibration(calibration);
+    profiles
+}
+
+#[test]
+fn blank_flash_gives_defaults() {
+    let mut storage = ConfigStorage::new(MockFlash::new(), SECTOR as u32);
+    assert!(storage.load() == Err(ConfigStorageError::BadMagic));
+    assert!(storage.load_or_default() == RaceProfiles::init());
+}
+
+#[test]
+fn saved_profiles_survive_reload() {
+    let mut storage = ConfigStorage::new(MockFlash::new(), SECTOR as u32);
+    storage.save(&RaceProfiles::init()).unwrap();
+    storage.save(&tuned()).unwrap();
+    let loaded = storage.load().unwrap();
+    assert!(loaded == tuned());
+    assert_eq!(loaded.len(), 4);
+    assert_eq!(loaded.active().name(), "NARROW");
+    assert_eq!(loaded.config().max_speed, 6000);
+    assert_eq!(loaded.calibration().channels[5].offset, -17);
+    assert_eq!(loaded.calibration().channels[5].scale, 940);
+}
+
+#[test]
+fn large_write_sizes_pad_the_record() {
+    let mut flash = MockFlash::<64>::erased();
+    ConfigStorage::new(&mut flash, 0).save(&tuned()).unwrap();
+    assert!(flash.data[CONFIG_RECORD_SIZE..].iter().all(|b| *b == 0xff));
+    assert!(ConfigStorage::new(&mut flash, 0).load().unwrap() == tuned());
+}
+
+#[test]
+#[should_panic]
+fn write_sizes_above_the_pad_buffer_are_refused() {
+    ConfigStorage::new(MockFlash::<1024>::erased(), 0);
+}
+
+#[test]
+fn unchanged_profiles_are_not_rewritten() {
+    let mut flash = MockFlash::new();
+    let mut storage = ConfigStorage::new(&mut flash, SECTOR as u32);
+    storage.save(&tuned()).unwrap();
+    storage.save(&tuned()).unwrap();
+    assert_eq!(flash.erases, 1);
+}
+
+#[test]
+fn corrupt_record_falls_back_to_defaults() {
+    let mut flash = MockFlash::new();
+    ConfigStorage::new(&mut flash, 0).save(&tuned()).unwrap();
+    flash.data[10] ^= 0x01;
+    let mut storage = ConfigStorage::new(&mut flash, 0);
+    assert!(storage.load() == Err(ConfigStorageError::BadCrc));
+    assert!(storage.load_or_default() == RaceProfiles::init());
+}
+
+#[test]
+fn older_layout_falls_back_to_defaults() {
+    let mut flash = MockFlash::new();
+    let mut record = encode_profiles(&tuned());
+    record[4] = 0;
+    record[5] = 0;
+    flash.data[..CONFIG_RECORD_SIZE].copy_from_slice(&record);
+    let mut storage = ConfigStorage::new(&mut flash, 0);
+    assert!(storage.load() == Err(ConfigStorageError::BadVersion(0)));
+    assert!(storage.load_or_default() == RaceProfiles::init());
+}
@@ -0,0 +1,174 @@
+use countryman_core::configuration::RaceConfig;
+use countryman_core::geometry::{laser_positions, GeometryError, LaserBeam, LaserTier};
+use countryman_core::lasers::{LaserChannelState, RawLaserReadings, RAW_LASERS_COUNT};
+use countryman_core::race::Angle;
+use countryman_core::vision::{LaserStatus, Vision, LASER_OVERFLOW};
+use embassy_time::{Duration, Instant};
+
+fn readings(values: [u16; RAW_LASERS_COUNT]) -> RawLaserReadings {
+    RawLaserReadings::new(values, Instant::from_ticks(0), Duration::from_millis(10))
+}
+
+#[test]
+fn open_track_goes_straight() {
+    let config = RaceConfig::init();
+    let mut vision = Vision::new();
+    vision.update(
+        &readings([LASER_OVERFLOW; RAW_LASERS_COUNT]),
+        &config,
+        Angle::ZERO,
+    );
+    let (target, _, status, _) = vision.compute_target();
+    assert!(target == Angle::ZERO);
+    assert_eq!(status, LaserStatus::Overflow);
+    assert!(!vision.detect_back_panic(&config));
+}
+
+#[test]
+fn wall_on_the_left_steers_right() {
+    let config = RaceConfig::init();
+    let mut vision = Vision::new();
+    let mut values = [LASER_OVERFLOW; RAW_LASERS_COUNT];
+    // upper and lower beams at -30 and -60 degrees
+    for index in [1, 4, 6] {
+        values[index] = 100;
+    }
+    vision.update(&readings(values), &config, Angle::ZERO);
+    let (target, _, _, _) = vision.compute_target();
+    assert!(target > Angle::ZERO);
+}
+
+#[test]
+fn failing_channels_are_held_then_replaced() {
+    let config = RaceConfig::init();
+    let mut vision = Vision::new();
+    let mut raw = readings([LASER_OVERFLOW; RAW_LASERS_COUNT]);
+    // upper and lower beams at +60 degrees
+    raw.values[5] = 300;
+    vision.update(&raw, &config, Angle::ZERO);
+    assert_eq!(vision.lasers[4].value(), 300);
+
+    // A broken wire must not look like an obstacle: the last value is held
+    raw.timestamp = Instant::from_millis(50);
+    raw.set(5, Err(LaserChannelState::I2cError), raw.timestamp);
+    vision.update(&raw, &config, Angle::ZERO);
+    assert_eq!(vision.lasers[4].value(), 300);
+    assert!(!vision.lasers[4].missing);
+
+    // Then the +30 degrees laser stands in for it
+    raw.timestamp = Instant::from_millis(200);
+    vision.update(&raw, &config, Angle::ZERO);
+    assert!(vision.lasers[4].missing);
+    assert_eq!(vision.lasers[4].value(), LASER_OVERFLOW);
+    assert_eq!(vision.missing_count(), 1);
+    assert_eq!(raw.errors[5], 1);
+    assert!(!vision.detect_back_panic(&config));
+
+    // A single failing channel of a pair falls back to the other one
+    raw.timestamp = Instant::from_millis(400);
+    raw.set(5, Ok(LASER_OVERFLOW), raw.timestamp);
+    raw.values[2] = 150;
+    raw.set(7, Err(LaserChannelState::Timeout), raw.timestamp);
+    vision.update(&raw, &config, Angle::ZERO);
+    assert!(!vision.lasers[4].missing);
+    assert_eq!(vision.lasers[2].value(), 150);
+}
+
+#[test]
+fn geometry_table_drives_vision() {
+    let config = RaceConfig::init();
+    // Seven single beams, listed out of order
+    let beams: Vec<LaserBeam> = [
+        (0, 0),
+        (1, -20),
+        (2, 20),
+        (3, -40),
+        (4, 40),
+        (5, -70),
+        (6, 70),
+    ]
+    .iter()
+    .map(|(channel, angle)| LaserBeam::new(*channel, LaserTier::Lower, Angle::from(*angle)))
+    .collect();
+    let mut vision = Vision::with_geometry(&beams);
+    assert_eq!(vision.lasers.len(), 7);
+    assert_eq!(vision.center(), 3);
+    assert!(vision.sensor_angle(0) == Angle::from(-70));
+    assert!(vision.sensor_angle(6) == Angle::from(70));
+
+    // Only the rightmost beam is open
+    let mut values = [200; RAW_LASERS_COUNT];
+    values[6] = LASER_OVERFLOW;
+    vision.update(&readings(values), &config, Angle::ZERO);
+    let (target, index, _, _) = vision.compute_target();
+    assert_eq!(index, 6);
+    assert!(target > Angle::from(30));
+
+    // A sensor mounted behind the bumper reads longer than the real distance
+    let mut beams = beams;
+    beams[0] = beams[0].offset(0, -30);
+    let mut vision = Vision::with_geometry(&beams);
+    let mut values = [LASER_OVERFLOW; RAW_LASERS_COUNT];
+    values[0] = 330;
+    vision.update(&readings(values), &config, Angle::ZERO);
+    assert_eq!(vision.lasers[3].value(), 300);
+}
+
+#[test]
+fn bad_geometry_tables_are_rejected() {
+    let beam = LaserBeam::new(1, LaserTier::Upper, Angle::ZERO);
+    assert_eq!(laser_positions(&[]).err(), Some(GeometryError::NoBeams));
+    assert_eq!(
+        laser_positions(&[LaserBeam::new(8, LaserTier::Upper, Angle::ZERO)]).err(),
+        Some(GeometryError::BadChannel(8))
+    );
+    assert_eq!(
+        laser_positions(&[beam, beam]).err(),
+        Some(GeometryError::DuplicateChannel(1))
+    );
+    assert_eq!(
+        laser_positions(&[beam, LaserBeam::new(2, LaserTier::Upper, Angle::ZERO)]).err(),
+        Some(GeometryError::DuplicateBeam(2))
+    );
+    let beams: Vec<LaserBeam> = (0..8)
+        .map(|channel| LaserBeam::new(channel, LaserTier::Lower, Angle::from(channel as i32 * 10)))
+        .collect();
+    assert_eq!(
+        laser_positions(&beams).err(),
+        Some(GeometryError::TooManyPositions)
+    );
+}
+
+#[test]
+fn partial_scans_use_the_freshest_beams() {
+    let config = RaceConfig::init();
+    let mut vision = Vision::new();
+    let mut raw = readings([LASER_OVERFLOW; RAW_LASERS_COUNT]);
+    vision.update(&raw, &config, Angle::ZERO);
+
+    // Only the upper center beam is read: it sees a wall, the lower one
+    // still holds its last reading
+    raw.updated = 0;
+    raw.timestamp = Instant::from_millis(20);
+    raw.set(2, Ok(300), raw.timestamp);
+    vision.update(&raw, &config, Angle::ZERO);
+    let center = vision.center();
+    assert_eq!(vision.lasers[center].value(), 300);
+    assert_eq!(
+        vision.lasers[center].upper_valid_at,
+        Some(Instant::from_millis(20))
+    );
+    assert_eq!(
+        vision.lasers[center].lower_valid_at,
+        Some(Instant::from_ticks(0))
+    );
+
+    // A lower beam read long before the upper one is ignored, even if the
+    // pair would look like a slope
+    raw.timestamp = Instant::from_millis(300);
+    raw.set(7, Ok(250), Instant::from_millis(100));
+    raw.set(2, Ok(350), raw.timestamp);
+    vision.update(&raw, &config, Angle::ZERO);
+    assert!(!vision.lasers[center].slope);
+    assert_eq!(vision.lasers[center].value(), 350);
+}
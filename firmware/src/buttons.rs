@@ -4,7 +4,7 @@ use embassy_rp::{
 };
 use embassy_time::{Duration, Instant};
 
-use crate::cmd::{Cmd, CMD};
+use crate::cmd::{Cmd, CmdSource, CMD};
 
 pub type LeftButton = Input<'static, PIN_6>;
 pub type RightButton = Input<'static, PIN_7>;
@@ -39,14 +39,14 @@ pub async fn buttons_task(mut left_button: LeftButton, mut right_button: RightBu
                         // no cmd: cancel double press
                         forget_right = true;
                     } else if elapsed > DEBOUNCE {
-                        CMD.signal(Cmd::Ok);
+                        CMD.send(Cmd::Ok, CmdSource::Button);
                         forget_right = true;
                     }
                 } else {
                     if elapsed > HOLD {
-                        CMD.signal(Cmd::Minus);
+                        CMD.send(Cmd::Minus, CmdSource::Button);
                     } else if elapsed > DEBOUNCE {
-                        CMD.signal(Cmd::Previous);
+                        CMD.send(Cmd::Previous, CmdSource::Button);
                     }
                 }
             }
@@ -62,14 +62,14 @@ pub async fn buttons_task(mut left_button: LeftButton, mut right_button: RightBu
                         // no cmd: cancel double press
                         forget_left = true;
                     } else if elapsed > DEBOUNCE {
-                        CMD.signal(Cmd::Exit);
+                        CMD.send(Cmd::Exit, CmdSource::Button);
                         forget_left = true;
                     }
                 } else {
                     if elapsed > HOLD {
-                        CMD.signal(Cmd::Plus);
+                        CMD.send(Cmd::Plus, CmdSource::Button);
                     } else if elapsed > DEBOUNCE {
-                        CMD.signal(Cmd::Next);
+                        CMD.send(Cmd::Next, CmdSource::Button);
                     }
                 }
             }
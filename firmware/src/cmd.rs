@@ -0,0 +1,68 @@
+use core::sync::atomic::{AtomicU32, Ordering};
+
+use embassy_sync::{blocking_mutex::raw::CriticalSectionRawMutex, channel::Channel};
+use embassy_time::{Duration, Instant};
+
+pub use countryman_core::cmd::*;
+
+/// Commands raised while no screen is listening wait here, in order.
+const CMD_QUEUE_SIZE: usize = 8;
+/// Commands that waited longer than this in the queue are logged.
+const CMD_LATE: Duration = Duration::from_millis(100);
+
+pub struct CmdQueue {
+    channel: Channel<CriticalSectionRawMutex, CmdEvent, CMD_QUEUE_SIZE>,
+    overflows: AtomicU32,
+}
+
+impl CmdQueue {
+    pub const fn new() -> Self {
+        Self {
+            channel: Channel::new(),
+            overflows: AtomicU32::new(0),
+        }
+    }
+
+    /// Queues a command; when the queue is full the command is dropped and
+    /// counted as an overflow.
+    pub fn send(&self, cmd: Cmd, source: CmdSource) {
+        if self
+            .channel
+            .try_send(CmdEvent::new(cmd, source, Instant::now()))
+            .is_err()
+        {
+            let overflows = self.overflows.fetch_add(1, Ordering::Relaxed) + 1;
+            log::error!(
+                "cmd queue full, dropped {} from {} ({} overflows)",
+                cmd.name(),
+                source.name(),
+                overflows
+            );
+        }
+    }
+
+    /// Waits for the oldest queued command.
+    pub async fn wait_event(&self) -> CmdEvent {
+        let event = self.channel.recv().await;
+        let queued = Instant::now() - event.timestamp;
+        if queued > CMD_LATE {
+            log::info!(
+                "cmd {} from {} queued for {}ms",
+                event.cmd.name(),
+                event.source.name(),
+                queued.as_millis()
+            );
+        }
+        event
+    }
+
+    pub async fn wait(&self) -> Cmd {
+        self.wait_event().await.cmd
+    }
+
+    pub fn overflows(&self) -> u32 {
+        self.overflows.load(Ordering::Relaxed)
+    }
+}
+
+pub static CMD: CmdQueue = CmdQueue::new();
@@ -1,11 +1,11 @@
 #![allow(dead_code)]
 #![allow(unused_variables)]
 
-use core::{num::ParseIntError, str::FromStr};
+use core::str::FromStr;
 
 use crate::uformat;
 use crate::uformat::FormattedText;
-use arrayvec::{ArrayString, ArrayVec, CapacityError};
+use arrayvec::{ArrayString, ArrayVec};
 use embassy_rp::{
     peripherals::{PIN_8, PIN_9, UART1},
     uart::{BufferedUart, BufferedUartTx, Config},
@@ -15,9 +15,9 @@ use embassy_time::{with_timeout, Duration, Instant};
 use embedded_io_async::{Read, Write};
 use static_cell::make_static;
 
+pub use countryman_core::esp32c3::*;
+
 pub const HOSTNAME: &'static str = "countryman";
-pub const MAX_NETWORK_BUFFER_SIZE: usize = 256;
-pub type NetworkBuffer = ArrayVec<u8, MAX_NETWORK_BUFFER_SIZE>;
 
 pub static OUT_DATA: Signal<CriticalSectionRawMutex, FormattedText> = Signal::new();
 
@@ -60,254 +60,6 @@ pub async fn esp32c3_task(uart1: UART1, pin_8: PIN_8, pin_9: PIN_9) {
     }
 }
 
-#[derive(Clone, Copy, PartialEq, Eq)]
-pub enum CwState {
-    Idle,
-    ConnectedWithoutIpAddress,
-    Connected,
-    Connecting,
-    Disconnected,
-}
-
-#[derive(Clone, Copy)]
-enum IpSta {
-    Ip(ArrayString<16>),
-    Gateway(ArrayString<16>),
-    Netmask(ArrayString<16>),
-    Ip6Ll,
-    Ip6Gl,
-}
-
-#[derive(Clone, Copy, PartialEq, Eq)]
-struct ConnectionIpState {
-    link_id: u8,
-    link_type: ArrayString<16>,
-    remote_ip: ArrayString<16>,
-}
-
-#[derive(Clone, Copy, PartialEq, Eq)]
-pub enum AccessPointEncryption {
-    Open,
-    Wep,
-    WpaPsk,
-    Wpa2Psk,
-    WpaWpa2Psk,
-    Wpa2Enterprise,
-    Wpa3Psk,
-    Wps2Wps3Psk,
-    WapiPsk,
-    Owe,
-}
-
-//+IPD,<length>:<data> will be outputted if AT+CIPMUX=0, AT+CIPRECVMODE=0, and AT+CIPDINFO=0.
-//+IPD,<link_id>,<length>:<data> will be outputted if AT+CIPMUX=1, AT+CIPRECVMODE=0, and AT+CIPDINFO=0.
-#[derive(Clone)]
-struct IpData {
-    #[allow(unused)]
-    link_id: u8,
-    #[allow(unused)]
-    length: u16,
-    data: NetworkBuffer,
-}
-
-// +CWLAP:<ecn>,<ssid>,<rssi>,<mac>,<channel>,<freq_offset>,<freqcal_val>,<pairwise_cipher>,<group_cipher>,<bgn>,<wps>
-#[derive(Clone, Copy)]
-pub struct AccessPointInfo {
-    pub encryption: AccessPointEncryption,
-    pub ssid: ArrayString<32>,
-    pub rssi: u16,
-}
-
-#[derive(Clone, Copy)]
-enum AddressInfo {
-    AccessPointIp(ArrayString<16>),
-    AccessPointIpV6Ll,
-    AccessPointIpV6Gl,
-    AccessPointMac(ArrayString<24>),
-    StationIp(ArrayString<16>),
-    StationIpV6Ll,
-    StationIpV6Gl,
-    StationMac(ArrayString<24>),
-    EthIp,
-    EthIpV6Ll,
-    EthIpV6Gl,
-    EthMac,
-}
-
-#[derive(Clone)]
-enum AtReply {
-    Empty,
-    Ok,
-    Error,
-    WifiConnected,
-    WifiGotIp,
-    CwState(CwState),
-    IpSta(IpSta),
-    CIpState(ConnectionIpState),
-    CWLAP(AccessPointInfo),
-    SendOk,
-    IpData(IpData),
-    CIFSR(AddressInfo),
-}
-
-impl AtReply {
-    pub fn description(&self) -> &'static str {
-        match self {
-            AtReply::Empty => "EMPTY",
-            AtReply::Ok => "OK",
-            AtReply::Error => "ERROR",
-            AtReply::WifiConnected => "WIFI-CONNECTED",
-            AtReply::WifiGotIp => "WIFI-GOT_IP",
-            AtReply::CwState(_) => "CWSTATE",
-            AtReply::IpSta(_) => "IPSTA",
-            AtReply::CIpState(_) => "CIPSTATE",
-            AtReply::CWLAP(_) => "CWLAP",
-            AtReply::SendOk => "SEND-OK",
-            AtReply::IpData(_) => "IPDATA",
-            AtReply::CIFSR(_) => "CIFSR",
-        }
-    }
-}
-
-struct AtReplyParseError;
-impl Into<AtReplyParseError> for ParseIntError {
-    fn into(self) -> AtReplyParseError {
-        AtReplyParseError
-    }
-}
-impl Into<AtReplyParseError> for CapacityError<&str> {
-    fn into(self) -> AtReplyParseError {
-        AtReplyParseError
-    }
-}
-
-impl AtReply {
-    pub fn parse(s: &str, d: &[u8]) -> Self {
-        Self::try_parse(s, d).unwrap_or(Self::Empty)
-    }
-    fn try_parse(s: &str, d: &[u8]) -> Result<Self, AtReplyParseError> {
-        if s.len() == 0 {
-            Ok(Self::Empty)
-        } else if let Some(s) = s.strip_prefix("+") {
-            if let Some(args) = s.strip_prefix("IPD,") {
-                let (link_id, args) = args.split_once(",").ok_or(AtReplyParseError)?;
-                let (length, data) = args.split_once(":").ok_or(AtReplyParseError)?;
-                if data.len() > 0 {
-                    return Ok(Self::Empty);
-                }
-
-                let link_id = link_id.parse::<u8>().map_err(|err| err.into())?;
-                let length = length.parse::<u16>().map_err(|err| err.into())?;
-                if length as usize != d.len() {
-                    return Err(AtReplyParseError);
-                }
-
-                return Ok(Self::IpData(IpData {
-                    link_id,
-                    length,
-                    data: ArrayVec::try_from(d).unwrap(),
-                }));
-            } else if let Some(args) = s.strip_prefix("CWSTATE:") {
-                let state = args.split_once(",").map(|(s, _)| s).unwrap_or(args);
-                let cw_state = match state {
-                    "0" => CwState::Idle,
-                    "1" => CwState::ConnectedWithoutIpAddress,
-                    "2" => CwState::Connected,
-                    "3" => CwState::Connecting,
-                    "4" => CwState::Disconnected,
-                    _ => return Ok(Self::Empty),
-                };
-                Ok(Self::CwState(cw_state))
-            } else if let Some(args) = s.strip_prefix("CIPSTATE:") {
-                let (link_id, args) = args.split_once(",").ok_or(AtReplyParseError)?;
-                let (link_type, args) = args.split_once(",").ok_or(AtReplyParseError)?;
-                let (remote_ip, _) = args.split_once(",").ok_or(AtReplyParseError)?;
-
-                let link_id = link_id.parse::<u8>().map_err(|err| err.into())?;
-                let link_type = ArrayString::try_from(link_type).map_err(|err| err.into())?;
-                let remote_ip = ArrayString::try_from(remote_ip).map_err(|err| err.into())?;
-                Ok(Self::CIpState(ConnectionIpState {
-                    link_id,
-                    link_type,
-                    remote_ip,
-                }))
-            } else if let Some(args) = s.strip_prefix("CWLAP:") {
-                let (enc, args) = args.split_once(",").ok_or(AtReplyParseError)?;
-                let (ssid, args) = args.split_once(",").ok_or(AtReplyParseError)?;
-                let (rssi, _) = args.split_once(",").ok_or(AtReplyParseError)?;
-                let encryption = match enc {
-                    "0" => AccessPointEncryption::Open,
-                    "1" => AccessPointEncryption::Wep,
-                    "2" => AccessPointEncryption::WpaPsk,
-                    "3" => AccessPointEncryption::Wpa2Psk,
-                    "4" => AccessPointEncryption::WpaWpa2Psk,
-                    "5" => AccessPointEncryption::Wpa2Enterprise,
-                    "6" => AccessPointEncryption::Wpa3Psk,
-                    "7" => AccessPointEncryption::WapiPsk,
-                    "8" => AccessPointEncryption::Owe,
-                    _ => return Ok(Self::Empty),
-                };
-                let ssid = ArrayString::try_from(ssid).unwrap();
-                let rssi = rssi.parse::<u16>().map_err(|err| err.into())?;
-                Ok(Self::CWLAP(AccessPointInfo {
-                    encryption,
-                    ssid,
-                    rssi,
-                }))
-            } else if let Some(args) = s.strip_prefix("CIPSTA:") {
-                let (param, arg) = args.split_once(":").ok_or(AtReplyParseError)?;
-                let info = match param {
-                    "ip" => IpSta::Ip(ArrayString::try_from(arg).map_err(|_| AtReplyParseError)?),
-                    "gateway" => {
-                        IpSta::Gateway(ArrayString::try_from(arg).map_err(|_| AtReplyParseError)?)
-                    }
-                    "netmask" => {
-                        IpSta::Netmask(ArrayString::try_from(arg).map_err(|_| AtReplyParseError)?)
-                    }
-                    "ip6ll" => IpSta::Ip6Ll,
-                    "ip6gl" => IpSta::Ip6Gl,
-                    _ => return Ok(Self::Empty),
-                };
-                Ok(Self::IpSta(info))
-            } else if let Some(args) = s.strip_prefix("CIFSR:") {
-                let (param, arg) = args.split_once(",").ok_or(AtReplyParseError)?;
-                let info = match param {
-                    "APIP" => AddressInfo::AccessPointIp(ArrayString::try_from(arg).unwrap()),
-                    "APIP6LL" => AddressInfo::AccessPointIpV6Ll,
-                    "APIP6GL" => AddressInfo::AccessPointIpV6Gl,
-                    "APMAC" => AddressInfo::AccessPointMac(ArrayString::try_from(arg).unwrap()),
-                    "STAIP" => AddressInfo::StationIp(ArrayString::try_from(arg).unwrap()),
-                    "STAIP6LL" => AddressInfo::StationIpV6Ll,
-                    "STAIP6GL" => AddressInfo::StationIpV6Gl,
-                    "STAMAC" => AddressInfo::StationMac(ArrayString::try_from(arg).unwrap()),
-                    "ETHIP" => AddressInfo::EthIp,
-                    "ETHIP6LL" => AddressInfo::EthIpV6Ll,
-                    "ETHIP6GL" => AddressInfo::EthIpV6Gl,
-                    "ETHMAC" => AddressInfo::EthMac,
-                    _ => return Ok(Self::Empty),
-                };
-                Ok(Self::CIFSR(info))
-            } else {
-                Ok(Self::Empty)
-            }
-        } else if s == "OK" {
-            Ok(Self::Ok)
-        } else if s == "SEND OK" {
-            Ok(Self::SendOk)
-        } else if s == "SEND ERROR" {
-            Ok(Self::Error)
-        } else if s == "WIFI CONNECTED" {
-            Ok(Self::WifiConnected)
-        } else if s == "WIFI GOT IP" {
-            Ok(Self::WifiGotIp)
-        } else if s.starts_with("ERROR") {
-            Ok(Self::Error)
-        } else {
-            Ok(Self::Empty)
-        }
-    }
-}
-
 #[allow(unused)]
 #[derive(Clone, Copy)]
 enum CwMode {
@@ -0,0 +1,254 @@
+//! I2C buses shared between tasks.
+//!
+//! A `SharedI2c` keeps the bus driver behind an async mutex and hands out
+//! `I2cDevice` handles implementing the `embedded_hal_async` I2C trait: each
+//! transaction locks the bus, so several drivers can use the same wires.
+//!
+//! A device behind the TCA9548A multiplexer selects its channel while holding
+//! the same lock as the transaction itself, so another user of the bus cannot
+//! switch the channel in between. The selected channel is cached and only
+//! written when it changes.
+//!
+//! Devices on the main bus must not share an address with a device behind the
+//! multiplexer, since the last selected channel stays connected.
+
+use embassy_rp::i2c::{AbortReason as I2cAbortReason, Error as I2cError};
+use embassy_sync::{
+    blocking_mutex::raw::CriticalSectionRawMutex,
+    mutex::{Mutex, MutexGuard},
+};
+use embedded_hal_1::i2c::{Error, ErrorKind, ErrorType, I2c as I2cBlocking, Operation};
+use embedded_hal_async::i2c::I2c as I2cAsync;
+
+pub const TCA9548A_ADDR: u8 = 0x70;
+pub const TCA9548A_CHANNELS: u8 = 8;
+
+struct BusState<BUS> {
+    bus: BUS,
+    /// `None` when unknown, e.g. after a failed select
+    mux_channel: Option<u8>,
+}
+
+pub struct SharedI2c<BUS> {
+    state: Mutex<CriticalSectionRawMutex, BusState<BUS>>,
+}
+
+impl<BUS> SharedI2c<BUS> {
+    pub const fn new(bus: BUS) -> Self {
+        Self {
+            state: Mutex::new(BusState {
+                bus,
+                mux_channel: None,
+            }),
+        }
+    }
+
+    /// Runs `recover` on the bus driver with the bus locked, so that it can
+    /// release the peripheral, unstick the bus and re-create the driver. The
+    /// multiplexer channel is selected again by the next transaction.
+    pub async fn recover(&self, recover: impl FnOnce(&mut BUS)) {
+        let mut state = self.state.lock().await;
+        recover(&mut state.bus);
+        state.mux_channel = None;
+    }
+
+    /// A device on the main bus.
+    pub fn device(&self) -> I2cDevice<'_, BUS> {
+        I2cDevice {
+            shared: self,
+            mux_channel: None,
+        }
+    }
+
+    /// A device behind channel `channel` of the multiplexer.
+    pub fn mux_device(&self, channel: u8) -> I2cDevice<'_, BUS> {
+        assert!(channel < TCA9548A_CHANNELS, "bad TCA9548A channel");
+        I2cDevice {
+            shared: self,
+            mux_channel: Some(channel),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum SharedI2cError<E> {
+    Bus(E),
+    /// Selecting the multiplexer channel failed
+    Mux(E),
+    /// A blocking transaction found the bus in use
+    Busy,
+}
+
+impl<E: Error> Error for SharedI2cError<E> {
+    fn kind(&self) -> ErrorKind {
+        match self {
+            SharedI2cError::Bus(err) | SharedI2cError::Mux(err) => err.kind(),
+            SharedI2cError::Busy => ErrorKind::Other,
+        }
+    }
+}
+
+impl SharedI2cError<I2cError> {
+    pub fn description(&self) -> &'static str {
+        let err = match self {
+            SharedI2cError::Bus(err) => err,
+            SharedI2cError::Mux(_) => return "mux select failed",
+            SharedI2cError::Busy => return "bus busy",
+        };
+        match err {
+            I2cError::Abort(reason) => match reason {
+                I2cAbortReason::NoAcknowledge => "abort: no acknowledge",
+                I2cAbortReason::ArbitrationLoss => "abort: arbitration loss",
+                I2cAbortReason::Other(_) => "abort: other",
+            },
+            I2cError::InvalidReadBufferLength => "invalid read buffer length",
+            I2cError::InvalidWriteBufferLength => "invalid write buffer length",
+            I2cError::AddressOutOfRange(_) => "address out of range",
+            I2cError::AddressReserved(_) => "address reserved",
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct I2cDevice<'a, BUS> {
+    shared: &'a SharedI2c<BUS>,
+    mux_channel: Option<u8>,
+}
+
+impl<BUS: ErrorType> ErrorType for I2cDevice<'_, BUS> {
+    type Error = SharedI2cError<BUS::Error>;
+}
+
+impl<'a, BUS: I2cAsync> I2cDevice<'a, BUS> {
+    /// Locks the bus, with the multiplexer on the channel of this device.
+    async fn lock(
+        &self,
+    ) -> Result<MutexGuard<'a, CriticalSectionRawMutex, BusState<BUS>>, SharedI2cError<BUS::Error>>
+    {
+        let mut state = self.shared.state.lock().await;
+        if let Some(channel) = self.mux_channel {
+            if state.mux_channel != Some(channel) {
+                state.mux_channel = None;
+                state
+                    .bus
+                    .write(TCA9548A_ADDR, &[1 << channel])
+                    .await
+                    .map_err(SharedI2cError::Mux)?;
+                state.mux_channel = Some(channel);
+            }
+        }
+        Ok(state)
+    }
+}
+
+impl<BUS: I2cAsync> I2cAsync for I2cDevice<'_, BUS> {
+    async fn read(&mut self, address: u8, read: &mut [u8]) -> Result<(), Self::Error> {
+        let mut state = self.lock().await?;
+        state
+            .bus
+            .read(address, read)
+            .await
+            .map_err(SharedI2cError::Bus)
+    }
+
+    async fn write(&mut self, address: u8, write: &[u8]) -> Result<(), Self::Error> {
+        let mut state = self.lock().await?;
+        state
+            .bus
+            .write(address, write)
+            .await
+            .map_err(SharedI2cError::Bus)
+    }
+
+    async fn write_read(
+        &mut self,
+        address: u8,
+        write: &[u8],
+        read: &mut [u8],
+    ) -> Result<(), Self::Error> {
+        let mut state = self.lock().await?;
+        state
+            .bus
+            .write_read(address, write, read)
+            .await
+            .map_err(SharedI2cError::Bus)
+    }
+
+    async fn transaction(
+        &mut self,
+        address: u8,
+        operations: &mut [Operation<'_>],
+    ) -> Result<(), Self::Error> {
+        let mut state = self.lock().await?;
+        state
+            .bus
+            .transaction(address, operations)
+            .await
+            .map_err(SharedI2cError::Bus)
+    }
+}
+
+impl<'a, BUS: I2cBlocking> I2cDevice<'a, BUS> {
+    /// Like `lock`, without waiting: blocking drivers cannot wait for the
+    /// async users of the bus, so they get `SharedI2cError::Busy` instead.
+    fn try_lock(
+        &self,
+    ) -> Result<MutexGuard<'a, CriticalSectionRawMutex, BusState<BUS>>, SharedI2cError<BUS::Error>>
+    {
+        let mut state = self
+            .shared
+            .state
+            .try_lock()
+            .map_err(|_| SharedI2cError::Busy)?;
+        if let Some(channel) = self.mux_channel {
+            if state.mux_channel != Some(channel) {
+                state.mux_channel = None;
+                state
+                    .bus
+                    .write(TCA9548A_ADDR, &[1 << channel])
+                    .map_err(SharedI2cError::Mux)?;
+                state.mux_channel = Some(channel);
+            }
+        }
+        Ok(state)
+    }
+}
+
+impl<BUS: I2cBlocking> I2cBlocking for I2cDevice<'_, BUS> {
+    fn read(&mut self, address: u8, read: &mut [u8]) -> Result<(), Self::Error> {
+        self.try_lock()?
+            .bus
+            .read(address, read)
+            .map_err(SharedI2cError::Bus)
+    }
+
+    fn write(&mut self, address: u8, write: &[u8]) -> Result<(), Self::Error> {
+        self.try_lock()?
+            .bus
+            .write(address, write)
+            .map_err(SharedI2cError::Bus)
+    }
+
+    fn write_read(
+        &mut self,
+        address: u8,
+        write: &[u8],
+        read: &mut [u8],
+    ) -> Result<(), Self::Error> {
+        self.try_lock()?
+            .bus
+            .write_read(address, write, read)
+            .map_err(SharedI2cError::Bus)
+    }
+
+    fn transaction(
+        &mut self,
+        address: u8,
+        operations: &mut [Operation<'_>],
+    ) -> Result<(), Self::Error> {
+        self.try_lock()?
+            .bus
+            .transaction(address, operations)
+            .map_err(SharedI2cError::Bus)
+    }
+}
@@ -0,0 +1,191 @@
+use embassy_rp::{
+    peripherals::{PIN_16, PIN_17, UART0},
+    uart::{BufferedUart, BufferedUartRx, BufferedUartTx, Config},
+};
+use embassy_sync::{blocking_mutex::raw::CriticalSectionRawMutex, signal::Signal};
+use embassy_time::{Duration, Instant, Timer};
+use embedded_io_async::{Read, Write};
+use static_cell::make_static;
+
+pub use countryman_core::bno08x::*;
+pub use countryman_core::imu::*;
+pub use countryman_core::shtp::*;
+
+use crate::topic::Topic;
+
+pub static IMU_DATA: Topic<ImuData> = Topic::new();
+pub static IMU_STILLNESS_CONFIG: Signal<CriticalSectionRawMutex, StillnessConfig> = Signal::new();
+pub static IMU_COMMAND: Signal<CriticalSectionRawMutex, ImuCommand> = Signal::new();
+
+/// How the BNO08x is strapped (PS0 and PS1).
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum ImuMode {
+    /// UART-RVC: angles and accelerations only, sent without asking
+    Rvc,
+    /// SHTP over UART: also gyro rate, linear acceleration, stability and
+    /// taps (see `ImuExtras`)
+    Shtp,
+}
+
+pub const IMU_MODE: ImuMode = ImuMode::Rvc;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum ImuCommand {
+    /// Stores the dynamic calibration in the IMU flash (SHTP mode only)
+    SaveCalibration,
+}
+
+const BUF_SIZE: usize = 256;
+const SHTP_BAUDRATE: u32 = 3_000_000;
+/// The hub needs this much time between the bytes it receives.
+const SHTP_BYTE_SPACING: Duration = Duration::from_micros(100);
+const READ_TIMEOUT: Duration = Duration::from_secs(5);
+
+pub async fn imu_task(uart0: UART0, pin_16: PIN_16, pin_17: PIN_17) {
+    let tx_buf = &mut make_static!([0u8; BUF_SIZE])[..];
+    let rx_buf = &mut make_static!([0u8; BUF_SIZE])[..];
+    let mut config = Config::default();
+    if IMU_MODE == ImuMode::Shtp {
+        config.baudrate = SHTP_BAUDRATE;
+    }
+    let uart = BufferedUart::new(uart0, super::Irqs, pin_16, pin_17, tx_buf, rx_buf, config);
+    let (rx, tx) = uart.split();
+
+    match IMU_MODE {
+        ImuMode::Rvc => rvc_loop(rx).await,
+        ImuMode::Shtp => shtp_loop(rx, tx).await,
+    }
+}
+
+async fn rvc_loop(mut rx: BufferedUartRx<'_, UART0>) {
+    let mut decoder = Bno080Decoder::init();
+    let mut data = ImuData::init();
+    let mut stillness_detector = ImuStillnessDetector::new(StillnessConfig::init());
+
+    let mut timestamp = Instant::now();
+    loop {
+        let mut buf = [0; 1];
+
+        match embassy_time::with_timeout(READ_TIMEOUT, rx.read_exact(&mut buf)).await {
+            Ok(result) => match result {
+                Ok(_) => {
+                    let now = Instant::now();
+                    let dt = now - timestamp;
+                    timestamp = now;
+
+                    if let Some(config) = IMU_STILLNESS_CONFIG.try_take() {
+                        stillness_detector.set_config(config);
+                    }
+
+                    let received = buf[0];
+                    if let Some(raw) = decoder.update(received) {
+                        stillness_detector.process_data(&raw, now, dt);
+                        // log::info!(
+                        //     "IMU [R {} P {} Y {}] [F {} S {} V {}]",
+                        //     raw.roll,
+                        //     raw.pitch,
+                        //     raw.yaw,
+                        //     raw.forward,
+                        //     raw.side,
+                        //     raw.vertical
+                        // );
+
+                        data.update(&raw, now, dt, stillness_detector.last_stillness);
+                        data.link = decoder.stats();
+                        IMU_DATA.publish(data);
+                    }
+                }
+                Err(err) => {
+                    log::info!("IMU uart read error: {}", err);
+                }
+            },
+            Err(_) => {
+                log::error!("timeout reading IMU uart");
+            }
+        }
+    }
+}
+
+async fn send_packet(tx: &mut BufferedUartTx<'_, UART0>, packet: &[u8]) {
+    for byte in shtp_uart_frame(packet) {
+        if let Err(err) = tx.write_all(&[byte]).await {
+            log::info!("IMU uart write error: {}", err);
+            return;
+        }
+        Timer::after(SHTP_BYTE_SPACING).await;
+    }
+}
+
+async fn enable_features(tx: &mut BufferedUartTx<'_, UART0>, bno: &mut Bno08x) {
+    for (report, interval) in BNO08X_FEATURES {
+        send_packet(tx, &bno.set_feature(report, interval)).await;
+    }
+}
+
+async fn shtp_loop(mut rx: BufferedUartRx<'_, UART0>, mut tx: BufferedUartTx<'_, UART0>) {
+    let mut decoder = ShtpUartDecoder::new();
+    let mut bno = Bno08x::new();
+    let mut data = ImuData::init();
+    let mut stillness_detector = ImuStillnessDetector::new(StillnessConfig::init());
+
+    enable_features(&mut tx, &mut bno).await;
+
+    let mut timestamp = Instant::now();
+    loop {
+        if let Some(command) = IMU_COMMAND.try_take() {
+            match command {
+                ImuCommand::SaveCalibration => {
+                    send_packet(&mut tx, &bno.save_calibration()).await;
+                }
+            }
+        }
+
+        let mut buf = [0; 1];
+        match embassy_time::with_timeout(READ_TIMEOUT, rx.read_exact(&mut buf)).await {
+            Ok(Ok(_)) => {
+                if !decoder.update(buf[0]) {
+                    continue;
+                }
+                match bno.process(decoder.packet()) {
+                    Some(Bno08xEvent::Sample(raw, extras)) => {
+                        let now = Instant::now();
+                        let dt = now - timestamp;
+                        timestamp = now;
+
+                        if let Some(config) = IMU_STILLNESS_CONFIG.try_take() {
+                            stillness_detector.set_config(config);
+                        }
+                        stillness_detector.process_data(&raw, now, dt);
+
+                        data.update(&raw, now, dt, stillness_detector.last_stillness);
+                        data.extras = Some(extras);
+                        data.link = ImuLinkStats {
+                            bad: decoder.dropped,
+                            ..bno.stats()
+                        };
+                        IMU_DATA.publish(data);
+                    }
+                    Some(Bno08xEvent::Reset) => {
+                        log::info!("IMU reset, enabling reports");
+                        enable_features(&mut tx, &mut bno).await;
+                    }
+                    Some(Bno08xEvent::CalibrationSaved(true)) => {
+                        log::info!("IMU calibration saved");
+                    }
+                    Some(Bno08xEvent::CalibrationSaved(false)) => {
+                        log::error!("IMU calibration save failed");
+                    }
+                    None => {}
+                }
+            }
+            Ok(Err(err)) => {
+                log::info!("IMU uart read error: {}", err);
+            }
+            Err(_) => {
+                // The reset notice may have been missed
+                log::error!("timeout reading IMU uart, enabling reports");
+                enable_features(&mut tx, &mut bno).await;
+            }
+        }
+    }
+}
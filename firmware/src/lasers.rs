@@ -0,0 +1,254 @@
+use embassy_rp::gpio::{Flex, Pull};
+use embassy_rp::i2c::{Async, Config as I2cConfig, Error as I2cError, I2c as RpI2c};
+use embassy_rp::peripherals::{I2C0, PIN_12, PIN_13};
+use embassy_sync::{blocking_mutex::raw::CriticalSectionRawMutex, signal::Signal};
+use embassy_time::{Duration, Instant};
+
+pub use countryman_core::lasers::*;
+
+use crate::distance::{DistanceError, DistanceSensor, LaserSensor};
+use crate::geometry::{sensor_kind, LASER_GEOMETRY};
+use crate::i2c_bus::{I2cDevice, SharedI2c, SharedI2cError};
+use crate::scan::{LaserScanHints, LaserScanScheduler};
+use crate::topic::Topic;
+
+pub type I2cBus0 = RpI2c<'static, I2C0, Async>;
+/// A sensor behind its TCA9548A channel on I2C0.
+type LaserI2c = I2cDevice<'static, I2cBus0>;
+
+pub static RAW_LASER_READINGS: Topic<RawLaserReadings> = Topic::new();
+/// Sensor settings, re-applied to every channel by `lasers_task`.
+pub static LASER_SETTINGS: Signal<CriticalSectionRawMutex, LaserSettings> = Signal::new();
+/// Distance corrections, applied by `lasers_task` to every frame.
+pub static LASER_CALIBRATION: Signal<CriticalSectionRawMutex, LasersCalibration> = Signal::new();
+/// Speed and slopes seen by the race logic, driving the scan scheduler.
+pub static LASER_SCAN_HINTS: Signal<CriticalSectionRawMutex, LaserScanHints> = Signal::new();
+
+const I2C_TIMEOUT: Duration = Duration::from_secs(1);
+/// Half of an SCL period while clocking a stuck bus free (100kHz).
+const RECOVERY_HALF_PERIOD: Duration = Duration::from_micros(5);
+
+pub fn i2c_config() -> I2cConfig {
+    let mut config = I2cConfig::default();
+    config.frequency = 400_000;
+    config
+}
+
+/// Drives a line low, or releases it to the pull-up.
+fn set_line(line: &mut Flex<'_, impl embassy_rp::gpio::Pin>, high: bool) {
+    if high {
+        line.set_as_input();
+    } else {
+        line.set_low();
+        line.set_as_output();
+    }
+    embassy_time::block_for(RECOVERY_HALF_PERIOD);
+}
+
+/// Clocks out up to nine bits, until the device holding SDA low lets it go,
+/// then sends a STOP. Returns `true` if SDA is free.
+fn unstick_bus(scl: &mut Flex<'_, PIN_13>, sda: &mut Flex<'_, PIN_12>) -> bool {
+    scl.set_pull(Pull::Up);
+    sda.set_pull(Pull::Up);
+    set_line(sda, true);
+    set_line(scl, true);
+    for _ in 0..9 {
+        if sda.is_high() {
+            break;
+        }
+        set_line(scl, false);
+        set_line(scl, true);
+    }
+    // STOP: SDA going high while SCL is high
+    set_line(scl, false);
+    set_line(sda, false);
+    set_line(scl, true);
+    set_line(sda, true);
+    sda.is_high()
+}
+
+/// Takes I2C0 back from its driver, clocks the bus free and creates a new
+/// driver.
+fn recover_bus(i2c: &mut I2cBus0) {
+    // Safety: the pins and the peripheral belong to the driver being
+    // replaced, which is not used again.
+    let released = {
+        let mut scl = Flex::new(unsafe { PIN_13::steal() });
+        let mut sda = Flex::new(unsafe { PIN_12::steal() });
+        unstick_bus(&mut scl, &mut sda)
+    };
+    if !released {
+        log::error!("I2C bus recovery: SDA still held low");
+    }
+    *i2c = unsafe {
+        RpI2c::new_async(
+            I2C0::steal(),
+            PIN_13::steal(),
+            PIN_12::steal(),
+            crate::Irqs,
+            i2c_config(),
+        )
+    };
+}
+
+fn error_description(err: &DistanceError<SharedI2cError<I2cError>>) -> &'static str {
+    match err {
+        DistanceError::I2c(err) => err.description(),
+        err => err.description(),
+    }
+}
+
+/// Reads a sensor, `None` when it has no new measurement.
+async fn read_distance(
+    sensor: &mut LaserSensor<LaserI2c>,
+) -> Option<Result<u16, LaserChannelState>> {
+    match embassy_time::with_timeout(I2C_TIMEOUT, sensor.read()).await {
+        Ok(result) => match result {
+            Ok(distance) => distance.map(Ok),
+            Err(err) => {
+                log::error!(
+                    "{} read distance error: {}",
+                    sensor.kind().name(),
+                    error_description(&err)
+                );
+                Some(Err(err.state()))
+            }
+        },
+        Err(_) => {
+            log::error!("I2C sensor read timeout");
+            Some(Err(LaserChannelState::Timeout))
+        }
+    }
+}
+
+async fn init_channel(chan: usize, sensor: &mut LaserSensor<LaserI2c>, settings: LaserSettings) {
+    match embassy_time::with_timeout(I2C_TIMEOUT, sensor.init(&settings)).await {
+        Ok(result) => match result {
+            Ok(_) => {}
+            Err(err) => {
+                log::error!(
+                    "laser {} {} init error: {}",
+                    chan,
+                    sensor.kind().name(),
+                    error_description(&err)
+                );
+            }
+        },
+        Err(_) => {
+            log::error!("laser {} {} init timeout", chan, sensor.kind().name());
+        }
+    }
+}
+
+/// Logs why a faulty sensor misbehaves, before it is re-initialized.
+async fn check_health(chan: usize, sensor: &mut LaserSensor<LaserI2c>) {
+    if let Ok(Err(err)) = embassy_time::with_timeout(I2C_TIMEOUT, sensor.health()).await {
+        log::error!(
+            "laser {} {} health: {}",
+            chan,
+            sensor.kind().name(),
+            error_description(&err)
+        );
+    }
+}
+
+pub async fn lasers_task(bus: &'static SharedI2c<I2cBus0>) {
+    let mut sensors: [LaserSensor<LaserI2c>; RAW_LASERS_COUNT] = core::array::from_fn(|chan| {
+        LaserSensor::new(
+            sensor_kind(LASER_GEOMETRY, chan as u8),
+            bus.mux_device(chan as u8),
+        )
+    });
+    let mut settings = LaserSettings::init();
+    let mut calibration = LasersCalibration::init();
+    let mut scheduler = LaserScanScheduler::new(LASER_GEOMETRY);
+    let mut hints = LaserScanHints::init();
+    for (chan, sensor) in sensors.iter_mut().enumerate() {
+        init_channel(chan, sensor, settings).await;
+    }
+    // Frames left before logging the scan rate after a settings change
+    let mut report_in: Option<u8> = None;
+
+    let mut raw_readings = RawLaserReadings::new(
+        [0u16; RAW_LASERS_COUNT],
+        Instant::now(),
+        Duration::from_micros(100),
+    );
+    loop {
+        if let Some(new_settings) = LASER_SETTINGS.try_take() {
+            if new_settings != settings {
+                settings = new_settings;
+                for (chan, sensor) in sensors.iter_mut().enumerate() {
+                    init_channel(chan, sensor, settings).await;
+                }
+                // The first frame includes the re-initialization
+                report_in = Some(2);
+                raw_readings.timestamp = Instant::now();
+            }
+        }
+
+        if let Some(new_calibration) = LASER_CALIBRATION.try_take() {
+            calibration = new_calibration;
+        }
+
+        if let Some(new_hints) = LASER_SCAN_HINTS.try_take() {
+            hints = new_hints;
+        }
+
+        let scan = scheduler.next(&hints);
+        raw_readings.updated = 0;
+        let mut stuck = false;
+        for (chan, sensor) in sensors.iter_mut().enumerate() {
+            if scan & (1 << chan) == 0 {
+                continue;
+            }
+            let Some(reading) = read_distance(sensor).await else {
+                continue;
+            };
+            raw_readings.set(chan, reading, Instant::now());
+            if raw_readings.bus_health.update(reading) {
+                stuck = true;
+                break;
+            }
+            if let Some(fault) = raw_readings.health[chan].update(reading, sensor.max_distance()) {
+                log::error!("laser {} {}: re-initializing", chan, fault.name());
+                check_health(chan, sensor).await;
+                init_channel(chan, sensor, settings).await;
+                raw_readings.health[chan].reinitialized();
+            }
+        }
+        if stuck {
+            raw_readings.bus_health.recovered();
+            log::error!(
+                "I2C bus stuck: recovering ({} so far)",
+                raw_readings.bus_health.recoveries
+            );
+            bus.recover(recover_bus).await;
+            for (chan, sensor) in sensors.iter_mut().enumerate() {
+                init_channel(chan, sensor, settings).await;
+            }
+        }
+
+        let overflow = core::array::from_fn(|chan| sensors[chan].max_distance());
+        raw_readings.calibrate(&calibration, &overflow);
+        let now = Instant::now();
+        raw_readings.dt = now - raw_readings.timestamp;
+        raw_readings.timestamp = now;
+        RAW_LASER_READINGS.publish(raw_readings);
+
+        report_in = match report_in {
+            Some(1) => {
+                log::info!(
+                    "lasers {:?} {:?} {:?}: scan {}ms",
+                    settings.shift,
+                    settings.median,
+                    settings.accumulation,
+                    raw_readings.dt.as_millis()
+                );
+                None
+            }
+            Some(frames) => Some(frames - 1),
+            None => None,
+        };
+    }
+}
@@ -1,8 +1,10 @@
-use crate::race::Angle;
+use crate::race::{Angle, RaceColor};
 use crate::rgb::RgbEvent;
+use crate::topic::Topic;
 use crate::uformat;
 use crate::uformat::FormattedText;
 use crate::vision::{is_in_window, LaserData, LaserStatus, Vision, LASER_OVERFLOW};
+use arrayvec::ArrayString;
 use core::convert::Infallible;
 use display_interface_spi::SPIInterface;
 use embassy_rp::spi::{self, Spi};
@@ -10,8 +12,6 @@ use embassy_rp::{
     gpio::{Level, Output},
     peripherals::{PIN_0, PIN_1, PIN_2, PIN_3, PIN_4, PIN_5, SPI0},
 };
-use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
-use embassy_sync::signal::Signal;
 use embassy_time::{Delay, Duration, Instant};
 use embedded_graphics::mono_font::iso_8859_9::FONT_10X20;
 use embedded_graphics::mono_font::MonoTextStyle;
@@ -28,6 +28,8 @@ use embedded_hal_0::digital::v2::OutputPin;
 use mipidsi::Builder;
 
 pub const STATES_COUNT: usize = 5;
+/// FONT_10X20 fits 13 characters in a row.
+pub const LABEL_SIZE: usize = 13;
 
 const VISUAL_STATE_H_HEIGHT: i32 = 240 / 10;
 const VISUAL_STATE_H_WIDTH: i32 = 135;
@@ -42,6 +44,11 @@ pub enum VisualStateH {
         text: &'static str,
         color: Rgb565,
     },
+    Label {
+        text: ArrayString<LABEL_SIZE>,
+        color: Rgb565,
+        cursor: Option<u8>,
+    },
     Value {
         value: i16,
         color: Rgb565,
@@ -117,6 +124,38 @@ impl VisualStateH {
         }
     }
 
+    /// Like `text`, for strings that are not `'static` (truncated to `LABEL_SIZE`).
+    pub fn label(&mut self, text: &str) {
+        self.set_label(text, Rgb565::WHITE, None)
+    }
+
+    pub fn label_red(&mut self, text: &str) {
+        self.set_label(text, Rgb565::RED, None)
+    }
+
+    pub fn label_green(&mut self, text: &str) {
+        self.set_label(text, Rgb565::GREEN, None)
+    }
+
+    /// A label with the character at `cursor` underlined, for text editing.
+    pub fn label_edit(&mut self, text: &str, cursor: usize) {
+        self.set_label(text, Rgb565::GREEN, Some(cursor))
+    }
+
+    fn set_label(&mut self, text: &str, color: Rgb565, cursor: Option<usize>) {
+        let mut label = ArrayString::new();
+        for c in text.chars() {
+            if label.try_push(c).is_err() {
+                break;
+            }
+        }
+        *self = Self::Label {
+            text: label,
+            color,
+            cursor: cursor.map(|c| c.min(LABEL_SIZE - 1) as u8),
+        }
+    }
+
     pub fn value(&mut self, value: i16) {
         *self = Self::Value {
             value,
@@ -235,6 +274,7 @@ impl VisualStateH {
         match self {
             VisualStateH::Empty { .. } => false,
             VisualStateH::Text { .. } => false,
+            VisualStateH::Label { .. } => false,
             VisualStateH::Value { .. } => false,
             VisualStateH::Value2 { .. } => false,
             VisualStateH::Gauge { .. } => false,
@@ -249,6 +289,7 @@ impl VisualStateH {
         match self {
             VisualStateH::Empty { .. } => true,
             VisualStateH::Text { .. } => true,
+            VisualStateH::Label { .. } => true,
             VisualStateH::Value { .. } => true,
             VisualStateH::Value2 { .. } => true,
             VisualStateH::Gauge { .. } => true,
@@ -295,6 +336,34 @@ impl VisualStateH {
                 .draw(target)
                 .ok();
             }
+            VisualStateH::Label {
+                text,
+                color,
+                cursor,
+            } => {
+                let style = MonoTextStyle::new(&FONT_10X20, color);
+                Text::new(
+                    text.as_str(),
+                    Self::position(index)
+                        + Point {
+                            x: 2,
+                            y: VISUAL_STATE_H_HEIGHT - 5,
+                        },
+                    style,
+                )
+                .draw(target)
+                .ok();
+                if let Some(cursor) = cursor {
+                    let x = 2 + 10 * cursor as i32;
+                    Line::new(
+                        Self::position(index) + Point::new(x, VISUAL_STATE_H_HEIGHT - 2),
+                        Self::position(index) + Point::new(x + 9, VISUAL_STATE_H_HEIGHT - 2),
+                    )
+                    .into_styled(PrimitiveStyle::<Rgb565>::with_stroke(color, 2))
+                    .draw(target)
+                    .ok();
+                }
+            }
             VisualStateH::Value { value, color } => {
                 let text = uformat!("{}", value);
                 let style = MonoTextStyle::new(&FONT_10X20, color);
@@ -698,9 +767,20 @@ impl VisualState {
         }
     }
 
+    /// One bar per laser position; with more positions than bars the
+    /// outermost ones and those evenly spread between them are shown.
     pub fn update_vision(&mut self, v: &Vision, window: Option<(usize, usize)>) {
+        let count = v.lasers.len();
         for (i, vv) in self.values_v.iter_mut().enumerate() {
-            vv.laser(&v.lasers[i], is_in_window(i, window));
+            let index = if count > STATES_COUNT {
+                i * (count - 1) / (STATES_COUNT - 1)
+            } else {
+                i
+            };
+            match v.lasers.get(index) {
+                Some(laser) => vv.laser(laser, is_in_window(index, window)),
+                None => vv.empty(),
+            }
         }
     }
 
@@ -730,9 +810,20 @@ impl VisualState {
     pub fn white(&mut self) {
         self.solid(Rgb565::WHITE);
     }
+
+    pub fn race_color(&mut self, color: RaceColor) {
+        match color {
+            RaceColor::Black => self.black(),
+            RaceColor::Red => self.red(),
+            RaceColor::Blue => self.blue(),
+            RaceColor::Yellow => self.yellow(),
+            RaceColor::White => self.white(),
+            RaceColor::Green => self.green(),
+        }
+    }
 }
 
-pub static VISUAL_STATE: Signal<CriticalSectionRawMutex, VisualState> = Signal::new();
+pub static VISUAL_STATE: Topic<VisualState> = Topic::new();
 
 struct TftPin<'a, PIN: embassy_rp::gpio::Pin> {
     pin: Output<'a, PIN>,
@@ -796,10 +887,11 @@ pub async fn tft_task(
     display.clear(Rgb565::BLACK).unwrap();
 
     let mut current_state = VisualState::init();
+    let mut visual_state = VISUAL_STATE.subscribe();
 
     loop {
         const MIN_FRAME_DT: Duration = Duration::from_millis(50);
-        let new_state = VISUAL_STATE.wait().await;
+        let new_state = visual_state.wait().await;
         let start = Instant::now();
 
         for (i, s) in new_state.values_h.iter().copied().enumerate() {
@@ -16,13 +16,14 @@ use embassy_rp::peripherals::{
 };
 use embassy_rp::uart::BufferedInterruptHandler;
 use embassy_rp::usb::{Driver, InterruptHandler as InterruptHandlerUsb};
+use i2c_bus::SharedI2c;
 use rp2040_panic_usb_boot as _;
-use static_cell::StaticCell;
+use static_cell::{make_static, StaticCell};
 
 pub mod buttons;
 pub mod cmd;
-pub mod configuration;
 pub mod esp32c3;
+pub mod i2c_bus;
 pub mod imu;
 pub mod lasers;
 pub mod lcd;
@@ -30,10 +31,14 @@ pub mod motors;
 pub mod race;
 pub mod rgb;
 pub mod screens;
+pub mod storage;
 pub mod tcs3472;
+pub mod topic;
 pub mod trace;
 pub mod uformat;
-pub mod vision;
+pub mod usb;
+
+pub use countryman_core::{configuration, distance, geometry, heading, profiles, scan, vision};
 
 bind_interrupts!(struct Irqs {
     USBCTRL_IRQ => InterruptHandlerUsb<USB>;
@@ -44,17 +49,17 @@ bind_interrupts!(struct Irqs {
 });
 
 #[embassy_executor::task]
-async fn logger_task(driver: Driver<'static, USB>) {
-    embassy_usb_logger::run!(1024, log::LevelFilter::Info, driver);
+async fn usb_task(driver: Driver<'static, USB>) {
+    usb::usb_task(driver).await
 }
 
 #[embassy_executor::task]
-async fn lasers_task(i2c: lasers::I2cBus0) {
+async fn lasers_task(i2c: &'static SharedI2c<lasers::I2cBus0>) {
     lasers::lasers_task(i2c).await
 }
 
 #[embassy_executor::task]
-async fn rgb_task(i2c: rgb::I2cBus1) {
+async fn rgb_task(i2c: &'static SharedI2c<rgb::I2cBus1>) {
     rgb::rgb_task(i2c).await
 }
 
@@ -94,10 +99,10 @@ static EXECUTOR0: StaticCell<Executor> = StaticCell::new();
 static EXECUTOR1: StaticCell<Executor> = StaticCell::new();
 
 #[embassy_executor::task]
-async fn main_task() -> ! {
+async fn main_task(mut storage: storage::RaceConfigStorage) -> ! {
     log::info!("Hello from main task (core 0)");
     loop {
-        screens::run().await;
+        screens::run(&mut storage).await;
     }
 }
 
@@ -169,18 +174,22 @@ fn main() -> ! {
     let _ = Input::new(p.PIN_24, Pull::None);
 
     log::info!("set up i2c0 ");
-    let mut config = I2cConfig::default();
-    config.frequency = 400_000;
-    let i2c0: lasers::I2cBus0 = RpI2c::new_async(p.I2C0, p.PIN_13, p.PIN_12, Irqs, config);
+    let i2c0: lasers::I2cBus0 =
+        RpI2c::new_async(p.I2C0, p.PIN_13, p.PIN_12, Irqs, lasers::i2c_config());
+    let i2c0 = make_static!(SharedI2c::new(i2c0));
 
     log::info!("set up i2c1 ");
     let mut config = I2cConfig::default();
     config.frequency = 400_000;
     let i2c1: rgb::I2cBus1 = RpI2c::new_async(p.I2C1, p.PIN_19, p.PIN_18, Irqs, config);
+    let i2c1 = make_static!(SharedI2c::new(i2c1));
+
+    log::info!("set up config storage");
+    let storage = storage::init(p.FLASH);
 
     let executor0 = EXECUTOR0.init(Executor::new());
     executor0.run(|spawner| {
-        spawner.spawn(logger_task(driver)).unwrap();
+        spawner.spawn(usb_task(driver)).unwrap();
         spawner.spawn(lasers_task(i2c0)).unwrap();
         spawner.spawn(rgb_task(i2c1)).unwrap();
         spawner
@@ -198,6 +207,6 @@ fn main() -> ! {
         spawner
             .spawn(buttons_task(left_button, right_button))
             .unwrap();
-        spawner.spawn(main_task()).unwrap();
+        spawner.spawn(main_task(storage)).unwrap();
     });
 }
@@ -1,17 +1,27 @@
+use embassy_futures::select::{select4, Either4};
 use embassy_rp::{
     peripherals::{PIN_27, PIN_28, PIN_29, PWM_CH5, PWM_CH6},
     pwm::{Config, Pwm},
 };
 use embassy_sync::{blocking_mutex::raw::CriticalSectionRawMutex, signal::Signal};
-use embassy_time::{Duration, Timer};
+use embassy_time::{Duration, Instant, Ticker, Timer};
 use fixed::traits::ToFixed;
 
+pub use countryman_core::safety::*;
+
+use crate::imu::IMU_DATA;
+use crate::lasers::RAW_LASER_READINGS;
+use crate::race::Angle;
+
 const MOTOR_DIV_INT: u8 = 250;
 const MOTOR_TOP: u16 = 10000;
 
 const SERVO_DIV_INT: u8 = 250;
 const SERVO_TOP: u16 = 10000;
 
+/// How often the safety supervisor checks the input ages.
+const SAFETY_CHECK_PERIOD: Duration = Duration::from_millis(10);
+
 const SERVO_CENTER_DUTY: u16 = 700;
 const SERVO_MAX_DELTA_DUTY: u16 = 350;
 const SERVO_MAX_DUTY: u16 = SERVO_CENTER_DUTY + SERVO_MAX_DELTA_DUTY;
@@ -38,8 +48,9 @@ fn pwm_config_motor(power: i16) -> Config {
     c
 }
 
+/// `steer` is the offset from the center, in duty units.
 fn pwm_config_servo(steer: i16) -> Config {
-    let duty_b = (((-steer * 10) + (SERVO_CENTER_DUTY as i16)) as u16)
+    let duty_b = ((-steer + (SERVO_CENTER_DUTY as i16)) as u16)
         .min(SERVO_MAX_DUTY)
         .max(SERVO_MIN_DUTY);
 
@@ -63,6 +74,14 @@ struct MotorsData {
 
 static MOTORS_DATA: Signal<CriticalSectionRawMutex, MotorsData> = Signal::new();
 
+/// Limits applied by the safety supervisor (set at race start).
+pub static SAFETY_LIMITS: Signal<CriticalSectionRawMutex, SafetyLimits> = Signal::new();
+/// Raised when the supervisor latches a fault and stops the motors.
+pub static SAFETY_FAULT: Signal<CriticalSectionRawMutex, FaultCode> = Signal::new();
+/// Clears the latched fault.
+pub static SAFETY_ACK: Signal<CriticalSectionRawMutex, ()> = Signal::new();
+
+/// `steer` is in servo duty units, see `RaceConfig::servo_steer`.
 pub fn motors_go(power: i16, steer: i16) {
     MOTORS_DATA.signal(MotorsData { power, steer })
 }
@@ -83,8 +102,8 @@ pub async fn motors_task(
 
     for s in 0..7i16 {
         let steer_sign = if s % 2 == 0 { -1 } else { 1 };
-        let steer_angle = ((s + 1) / 2) * 10;
-        let servo_config = pwm_config_servo(steer_angle * steer_sign);
+        let steer_angle = Angle::from(((s + 1) / 2) as i32 * 10 * steer_sign);
+        let servo_config = pwm_config_servo(steer_angle.servo_units());
         pwm_servo.set_config(&servo_config);
         Timer::after(Duration::from_millis(200)).await;
     }
@@ -100,11 +119,36 @@ pub async fn motors_task(
     let power_config = pwm_config_motor(0);
     pwm_motor.set_config(&power_config);
 
+    let mut lasers = RAW_LASER_READINGS.subscribe();
+    let mut imu = IMU_DATA.subscribe();
+    let mut supervisor = SafetySupervisor::new(SafetyLimits::init(), Instant::now());
+    let mut ticker = Ticker::every(SAFETY_CHECK_PERIOD);
+
     loop {
-        let data = MOTORS_DATA.wait().await;
-        let motor_config = pwm_config_motor(data.power);
-        let servo_config = pwm_config_servo(data.steer);
-        pwm_motor.set_config(&motor_config);
-        pwm_servo.set_config(&servo_config);
+        match select4(MOTORS_DATA.wait(), lasers.wait(), imu.wait(), ticker.next()).await {
+            Either4::First(data) => {
+                let power = supervisor.motors_commanded(data.power, Instant::now());
+                let motor_config = pwm_config_motor(power);
+                let servo_config = pwm_config_servo(data.steer);
+                pwm_motor.set_config(&motor_config);
+                pwm_servo.set_config(&servo_config);
+            }
+            Either4::Second(_) => supervisor.lasers_seen(Instant::now()),
+            Either4::Third(_) => supervisor.imu_seen(Instant::now()),
+            Either4::Fourth(_) => {
+                if let Some(limits) = SAFETY_LIMITS.try_take() {
+                    supervisor.set_limits(limits);
+                }
+                if SAFETY_ACK.try_take().is_some() {
+                    log::info!("safety fault acknowledged");
+                    supervisor.acknowledge(Instant::now());
+                }
+                if let Some(fault) = supervisor.check(Instant::now()) {
+                    pwm_motor.set_config(&pwm_config_motor(0));
+                    log::error!("safety fault {}: {}", fault.code(), fault.description());
+                    SAFETY_FAULT.signal(fault);
+                }
+            }
+        }
     }
 }
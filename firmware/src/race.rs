@@ -0,0 +1,152 @@
+use embassy_futures::join::join3;
+use embassy_futures::select::{select4, Either4};
+use embassy_time::Instant;
+
+pub use countryman_core::race::*;
+
+use crate::cmd::{Cmd, CMD};
+use crate::heading::HeadingTracker;
+use crate::imu::{IMU_DATA, IMU_STILLNESS_CONFIG};
+use crate::lasers::{LASER_SCAN_HINTS, RAW_LASER_READINGS};
+use crate::lcd::VISUAL_STATE;
+use crate::motors::{motors_go, motors_stop, SAFETY_LIMITS};
+use crate::rgb::RGB;
+use crate::scan::LaserScanHints;
+use crate::screens::Screen;
+use crate::trace::{TraceCommand, TRACE};
+use crate::{configuration::RaceConfig, lcd::VisualState};
+
+/// Puts the laser scan back to its default pattern when the race ends, also
+/// when the race is dropped by a console `race stop` or a safety fault.
+struct ScanHintsReset;
+
+impl Drop for ScanHintsReset {
+    fn drop(&mut self) {
+        LASER_SCAN_HINTS.signal(LaserScanHints::init());
+    }
+}
+
+/// `heading` must be zeroed on the start direction.
+pub async fn race(config: &RaceConfig, heading: HeadingTracker, simulate: bool) -> Screen {
+    let _scan_hints = ScanHintsReset;
+    let mut controller = RaceController::new(config, heading, simulate, Instant::now());
+    IMU_STILLNESS_CONFIG.signal(config.stillness());
+    SAFETY_LIMITS.signal(config.safety_limits());
+
+    let mut ui = VisualState::init();
+
+    if simulate {
+        ui.values_h[0].text_green("SYM");
+        ui.values_h[1].empty();
+        ui.values_h[2].empty();
+        ui.values_h[3].empty();
+        ui.values_h[4].empty();
+        ui.update_vision(controller.vision(), None);
+    } else {
+        ui.values_h[0].empty();
+        ui.values_h[1].empty();
+        ui.values_h[2].text_green("RACE");
+        ui.values_h[3].empty();
+        ui.values_h[4].empty();
+
+        ui.values_v[0].green();
+        ui.values_v[1].green();
+        ui.values_v[2].green();
+        ui.values_v[3].green();
+        ui.values_v[4].green();
+    }
+    VISUAL_STATE.publish(ui);
+
+    let mut lasers = RAW_LASER_READINGS.subscribe();
+    let mut imu = IMU_DATA.subscribe();
+    let mut rgb = RGB.subscribe();
+
+    let (lasers_data, imu_data, rgb_data) = join3(lasers.wait(), imu.wait(), rgb.wait()).await;
+    let mut inputs = RaceInputs {
+        lasers: lasers_data,
+        imu: imu_data,
+        rgb: rgb_data,
+    };
+    let screen = loop {
+        let now = Instant::now();
+        let output = controller.step(&inputs, now);
+        LASER_SCAN_HINTS.signal(LaserScanHints {
+            power: output.action.power,
+            slope: controller.vision().has_slope(),
+        });
+
+        if simulate {
+            if inputs.imu.is_still(now, config.still_for()) {
+                ui.values_h[0].text_blue("SYM");
+            } else {
+                ui.values_h[0].text_green("SYM");
+            }
+            ui.values_h[1].value(output.trace.track_heading.into());
+            ui.values_h[2].value(output.action.power);
+            ui.values_h[3].steer(output.action.steer.into());
+            ui.values_h[4].target(output.vision_target.into(), output.vision_status);
+            ui.update_vision(controller.vision(), output.vision_window);
+        } else {
+            ui.race_color(output.color);
+            TRACE.signal(TraceCommand::Push(output.trace));
+        }
+        VISUAL_STATE.publish(ui);
+
+        if simulate {
+            motors_stop();
+        } else {
+            motors_go(output.action.power, config.servo_steer(output.action.steer));
+        }
+
+        match select4(lasers.wait(), imu.wait(), rgb.wait(), CMD.wait()).await {
+            Either4::First(data) => {
+                inputs.lasers = data;
+            }
+            Either4::Second(data) => {
+                inputs.imu = data;
+            }
+            Either4::Third(data) => {
+                inputs.rgb = data;
+            }
+            Either4::Fourth(cmd) => {
+                if simulate {
+                    match cmd {
+                        Cmd::Previous => {
+                            break Screen::Ready;
+                        }
+                        Cmd::Next => {
+                            break Screen::Motors;
+                        }
+                        Cmd::Plus => {
+                            TRACE.signal(TraceCommand::Print);
+                        }
+                        Cmd::Minus => {
+                            TRACE.signal(TraceCommand::Clear);
+                        }
+                        Cmd::Ok | Cmd::Exit => {
+                            break Screen::Config;
+                        }
+                    }
+                } else {
+                    break Screen::Ready;
+                }
+            }
+        }
+    };
+
+    log::info!(
+        "race heading: drift {} cdeg/min, {} marker anchors",
+        controller.heading().drift_rate(),
+        controller.heading().anchors()
+    );
+    log::info!(
+        "race inputs: lasers {} lagged {}, imu {} lagged {}, rgb {} lagged {}",
+        lasers.received(),
+        lasers.lagged(),
+        imu.received(),
+        imu.lagged(),
+        rgb.received(),
+        rgb.lagged()
+    );
+    screen
+}
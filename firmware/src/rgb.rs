@@ -0,0 +1,93 @@
+use embassy_rp::i2c::{Async, I2c as RpI2c};
+use embassy_rp::peripherals::I2C1;
+use embassy_time::{with_timeout, Duration, Instant};
+
+pub use countryman_core::rgb::*;
+
+use crate::i2c_bus::{I2cDevice, SharedI2c};
+use crate::tcs3472::{RgbCGain, Tcs3472};
+use crate::topic::Topic;
+
+pub type I2cBus1 = RpI2c<'static, I2C1, Async>;
+
+const RETRY_SECS: u64 = 1;
+
+const MIN_DT: Duration = Duration::from_micros(2000);
+
+pub static RGB: Topic<RgbEvent> = Topic::new();
+
+pub async fn rgb_task(bus: &'static SharedI2c<I2cBus1>) {
+    let i2c: I2cDevice<'static, I2cBus1> = bus.device();
+    let mut tcs3472 = Tcs3472::new(i2c);
+    let mut init_error = false;
+
+    loop {
+        if let Err(_) = tcs3472.enable() {
+            init_error = true;
+            log::error!("tcs3472 enable error");
+        }
+        if let Err(_) = tcs3472.set_rgbc_gain(RgbCGain::_16x) {
+            init_error = true;
+            log::error!("tcs3472 set_rgbc_gain error");
+        }
+        if let Err(_) = tcs3472.set_integration_cycles(1) {
+            init_error = true;
+            log::error!("tcs3472 set_integration_cycles error");
+        }
+        if let Err(_) = tcs3472.set_wait_cycles(1) {
+            init_error = true;
+            log::error!("tcs3472 set_wait_cycles error");
+        }
+        if let Err(_) = tcs3472.enable_rgbc() {
+            init_error = true;
+            log::error!("tcs3472 enable_rgbc error");
+        }
+
+        if init_error {
+            embassy_time::Timer::after(Duration::from_secs(RETRY_SECS)).await;
+            log::info!("RGB init error: retrying");
+            RGB.publish(RgbEvent::empty());
+            continue;
+        } else {
+            break;
+        }
+    }
+
+    let mut last_timestamp = Instant::now();
+    let mut tracker = RgbTracker::new();
+
+    loop {
+        match with_timeout(Duration::from_secs(5), tcs3472.read_all_channels_async()).await {
+            Ok(Ok(rgbc)) => {
+                let now = Instant::now();
+                let dt = now - last_timestamp;
+                let event = tracker.update(rgbc.red, rgbc.green, rgbc.blue, rgbc.clear, dt);
+
+                // log::info!(
+                //     "RGBC: r {} g {} b {} c {}, NR {}, NG {}",
+                //     rgbc.red,
+                //     rgbc.green,
+                //     rgbc.blue,
+                //     rgbc.clear,
+                //     event.not_red_for.as_micros(),
+                //     event.not_green_for.as_micros(),
+                // );
+
+                RGB.publish(event);
+                last_timestamp = now;
+                if dt < MIN_DT {
+                    embassy_time::Timer::after(MIN_DT - dt).await;
+                }
+            }
+            Ok(Err(_)) => {
+                log::info!("RGB read error");
+                RGB.publish(RgbEvent::empty());
+                embassy_time::Timer::after(Duration::from_secs(RETRY_SECS)).await;
+            }
+            Err(_) => {
+                RGB.publish(RgbEvent::empty());
+                log::info!("RGB read timeout");
+            }
+        }
+    }
+}
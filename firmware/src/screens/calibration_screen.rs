@@ -0,0 +1,193 @@
+use embassy_futures::select::{select, Either};
+
+use crate::{
+    cmd::{Cmd, CMD},
+    geometry::{calibration_targets, LASER_GEOMETRY},
+    lasers::{
+        LaserCalibration, LaserCalibrationSampler, LASER_CALIBRATION_DISTANCES, RAW_LASERS_COUNT,
+        RAW_LASER_READINGS,
+    },
+    lcd::{VisualState, VISUAL_STATE},
+    motors::motors_stop,
+    profiles::RaceProfiles,
+    storage::RaceConfigStorage,
+    uformat,
+    uformat::FormattedText,
+    vision::LASER_OVERFLOW,
+};
+
+use super::Screen;
+
+const DISTANCES: usize = LASER_CALIBRATION_DISTANCES.len();
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Step {
+    /// Waiting for the board at the given distance
+    Place(usize),
+    Sampling(usize),
+    /// Corrections fitted, waiting to be saved
+    Done,
+}
+
+/// `(reading, distance)` of each channel at each board distance, `None`
+/// where the channel was not sampled.
+type Points = [[Option<(u16, u16)>; DISTANCES]; RAW_LASERS_COUNT];
+
+/// Channels with a beam: the others are not calibrated.
+fn beam_channels() -> u8 {
+    LASER_GEOMETRY
+        .iter()
+        .fold(0, |channels, beam| channels | (1 << beam.channel))
+}
+
+/// Fits the corrections of every channel with a beam, keeping the current
+/// one for the channels that cannot be fitted. Returns the number of failed
+/// channels.
+fn fit(profiles: &mut RaceProfiles, points: &Points) -> usize {
+    let mut calibration = *profiles.calibration();
+    let mut failed = 0;
+    for (channel, readings) in points.iter().enumerate() {
+        if beam_channels() & (1 << channel) == 0 {
+            continue;
+        }
+        match LaserCalibration::fit_points(readings) {
+            Some(correction) => {
+                log::info!(
+                    "laser {} calibration: offset {}mm scale {}",
+                    channel,
+                    correction.offset,
+                    correction.scale
+                );
+                calibration.channels[channel] = correction;
+            }
+            None => {
+                log::error!("laser {} calibration failed: {:?}", channel, readings);
+                failed += 1;
+            }
+        }
+    }
+    profiles.set_calibration(calibration);
+    failed
+}
+
+/// Guided laser calibration: a flat board is placed in front of the car,
+/// square to it, at each of `LASER_CALIBRATION_DISTANCES`. The readings are
+/// averaged and compared with what each beam should read at its angle (see
+/// `calibration_targets`), and a correction is fitted for each channel.
+/// Beams that would read beyond the sensor range are not sampled, and the
+/// sampling moves on after `LASER_CALIBRATION_TIMEOUT` without the channels
+/// short of readings. Ok saves it with the profiles, Exit drops it if it was
+/// not saved.
+pub async fn run(profiles: &mut RaceProfiles, storage: &mut RaceConfigStorage) -> Screen {
+    let mut ui = VisualState::init();
+
+    ui.values_h[0].text_green("CALIBRATE");
+    ui.values_h[1].text("BOARD AT");
+    ui.values_h[2].empty();
+    ui.values_h[3].text("OK TO SAMPLE");
+    ui.values_h[4].empty();
+    ui.values_v[0].black();
+    ui.values_v[1].black();
+    ui.values_v[2].black();
+    ui.values_v[3].black();
+    ui.values_v[4].black();
+
+    let mut previous = *profiles.calibration();
+    let mut step = Step::Place(0);
+    let range = profiles
+        .config()
+        .laser_settings()
+        .shift
+        .max_distance()
+        .min(LASER_OVERFLOW);
+    let mut targets = [None; RAW_LASERS_COUNT];
+    let mut sampler = LaserCalibrationSampler::new(0);
+    let mut points: Points = [[None; DISTANCES]; RAW_LASERS_COUNT];
+
+    let mut lasers = RAW_LASER_READINGS.subscribe();
+
+    loop {
+        match select(lasers.wait(), CMD.wait()).await {
+            Either::First(raw) => {
+                if let Step::Sampling(index) = step {
+                    sampler.add(&raw);
+                    ui.values_h[4].value2_blue((index + 1) as i16, sampler.missing() as i16);
+                    if sampler.is_done() {
+                        for (channel, readings) in points.iter_mut().enumerate() {
+                            readings[index] = sampler.average(channel).zip(targets[channel]);
+                        }
+                        if sampler.missing() > 0 {
+                            log::error!(
+                                "laser calibration at {}mm: {} channels short of readings",
+                                LASER_CALIBRATION_DISTANCES[index],
+                                sampler.missing()
+                            );
+                        }
+                        if index + 1 < DISTANCES {
+                            step = Step::Place(index + 1);
+                        } else {
+                            let failed = fit(profiles, &points);
+                            step = Step::Done;
+                            ui.values_h[1].text("FIT DONE");
+                            ui.values_h[2].empty();
+                            ui.values_h[3].text("OK TO SAVE");
+                            if failed == 0 {
+                                ui.values_h[4].text_green("ALL FITTED");
+                            } else {
+                                ui.values_h[4]
+                                    .value2_red(failed as i16, beam_channels().count_ones() as i16);
+                            }
+                        }
+                    }
+                }
+            }
+            Either::Second(c) => {
+                log::info!("cmd: {}", c.name());
+                match (c, step) {
+                    (Cmd::Ok, Step::Place(index)) => {
+                        targets = calibration_targets(
+                            LASER_GEOMETRY,
+                            LASER_CALIBRATION_DISTANCES[index],
+                            range,
+                        );
+                        let channels = (0..RAW_LASERS_COUNT)
+                            .filter(|channel| targets[*channel].is_some())
+                            .fold(0, |channels, channel| channels | (1 << channel));
+                        sampler = LaserCalibrationSampler::new(channels);
+                        step = Step::Sampling(index);
+                    }
+                    (Cmd::Ok, Step::Done) => match storage.save(profiles) {
+                        Ok(()) => {
+                            log::info!("calibration saved");
+                            previous = *profiles.calibration();
+                            ui.values_h[3].text_green("SAVED");
+                        }
+                        Err(err) => {
+                            log::error!("calibration save failed: {}", err.description());
+                            ui.values_h[3].text_red("SAVE ERROR");
+                        }
+                    },
+                    (Cmd::Exit, _) => {
+                        profiles.set_calibration(previous);
+                        return Screen::Ready;
+                    }
+                    (Cmd::Previous, Step::Place(0)) => return Screen::Diagnostics,
+                    (Cmd::Next, Step::Place(0)) => return Screen::Ready,
+                    _ => {}
+                }
+            }
+        }
+
+        if let Step::Place(index) = step {
+            ui.values_h[1].text("BOARD AT");
+            ui.values_h[2].label(uformat!("{}MM", LASER_CALIBRATION_DISTANCES[index]).as_str());
+            ui.values_h[3].text("OK TO SAMPLE");
+            ui.values_h[4].value2_blue((index + 1) as i16, DISTANCES as i16);
+        } else if let Step::Sampling(_) = step {
+            ui.values_h[3].text_blue("SAMPLING");
+        }
+
+        motors_stop();
+        VISUAL_STATE.publish(ui);
+    }
+}
@@ -0,0 +1,156 @@
+use embassy_futures::select::{select3, Either3};
+
+use crate::{
+    cmd::{Cmd, CMD},
+    configuration::RaceConfigEntry,
+    imu::IMU_DATA,
+    lasers::{LASER_SETTINGS, RAW_LASER_READINGS},
+    lcd::{VisualState, VISUAL_STATE},
+    motors::motors_stop,
+    profiles::RaceProfiles,
+    storage::RaceConfigStorage,
+    uformat,
+    uformat::FormattedText,
+};
+
+use super::{profiles_changed, Screen};
+
+fn is_laser_entry(entry: RaceConfigEntry) -> bool {
+    matches!(
+        entry,
+        RaceConfigEntry::LaserShift
+            | RaceConfigEntry::LaserMedian
+            | RaceConfigEntry::LaserAccumulation
+    )
+}
+
+pub async fn run(profiles: &mut RaceProfiles, storage: &mut RaceConfigStorage) -> Screen {
+    let mut ui = VisualState::init();
+
+    ui.values_h[0].label_green(profiles.active().name());
+    ui.values_h[1].text_red("COUNTRYMAN");
+    ui.values_h[2].text_green("CONFIG");
+    ui.values_h[3].empty();
+    ui.values_h[4].empty();
+    ui.values_v[0].empty();
+    ui.values_v[1].empty();
+    ui.values_v[2].empty();
+    ui.values_v[3].empty();
+    ui.values_v[4].empty();
+
+    let mut entry = RaceConfigEntry::start();
+    let mut editing = false;
+
+    let mut lasers = RAW_LASER_READINGS.subscribe();
+    let mut imu = IMU_DATA.subscribe();
+    let mut saved = false;
+
+    loop {
+        match select3(lasers.wait(), imu.wait(), CMD.wait()).await {
+            Either3::First(data) => {
+                // Laser settings are applied while editing them, show the
+                // resulting scan rate
+                if is_laser_entry(entry) && !saved {
+                    ui.values_h[2].label_green(uformat!("SCAN {}MS", data.dt.as_millis()).as_str());
+                }
+            }
+            Either3::Second(_data) => {}
+            Either3::Third(c) => {
+                log::info!("cmd: {}", c.name());
+                let config = profiles.config_mut();
+                saved = false;
+                match c {
+                    Cmd::Previous => {
+                        if editing {
+                            config.dec(entry);
+                        } else {
+                            entry = entry.prev();
+                        }
+                    }
+                    Cmd::Next => {
+                        if editing {
+                            config.inc(entry);
+                        } else {
+                            entry = entry.next();
+                        }
+                    }
+                    Cmd::Plus => {
+                        if editing {
+                            for _ in 0..5 {
+                                config.inc(entry);
+                            }
+                        } else {
+                            editing = true;
+                        }
+                    }
+                    Cmd::Minus => {
+                        if editing {
+                            for _ in 0..5 {
+                                config.dec(entry);
+                            }
+                        } else {
+                            editing = true;
+                        }
+                    }
+                    Cmd::Ok => {
+                        if editing {
+                            editing = false;
+                        } else {
+                            saved = true;
+                            match storage.save(profiles) {
+                                Ok(()) => {
+                                    log::info!("config saved");
+                                    ui.values_h[2].text_green("SAVED");
+                                }
+                                Err(err) => {
+                                    log::error!("config save failed: {}", err.description());
+                                    ui.values_h[2].text_red("SAVE ERROR");
+                                }
+                            }
+                        }
+                    }
+                    Cmd::Exit => {
+                        if editing {
+                            editing = false;
+                        } else {
+                            return Screen::Ready;
+                        }
+                    }
+                }
+                if !saved {
+                    ui.values_h[2].text_green("CONFIG");
+                }
+                LASER_SETTINGS.signal(profiles.config().laser_settings());
+                profiles_changed(profiles);
+            }
+        }
+
+        let invalid = profiles
+            .config()
+            .validate()
+            .iter()
+            .any(|violation| violation.involves(entry));
+
+        if editing {
+            ui.values_h[3].text_green(entry.name());
+        } else if invalid {
+            ui.values_h[3].text_red(entry.name());
+        } else {
+            ui.values_h[3].text(entry.name());
+        }
+
+        let schema = entry.schema();
+        let value = profiles.config().get(entry);
+        match (schema.value_name(value), invalid) {
+            (Some(name), false) => ui.values_h[4].text(name),
+            (Some(name), true) => ui.values_h[4].text_red(name),
+            (None, false) => ui.values_h[4].label(uformat!("{} {}", value, schema.unit).as_str()),
+            (None, true) => {
+                ui.values_h[4].label_red(uformat!("{} {}", value, schema.unit).as_str())
+            }
+        }
+
+        motors_stop();
+        VISUAL_STATE.publish(ui);
+    }
+}
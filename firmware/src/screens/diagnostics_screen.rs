@@ -0,0 +1,88 @@
+use embassy_futures::select::{select, Either};
+
+use crate::{
+    cmd::{Cmd, CMD},
+    lasers::{RawLaserReadings, RAW_LASERS_COUNT, RAW_LASER_READINGS},
+    lcd::{VisualState, VISUAL_STATE},
+    motors::motors_stop,
+};
+
+use super::Screen;
+
+fn errors(raw: &RawLaserReadings, channel: usize) -> i16 {
+    raw.errors[channel].min(i16::MAX as u32) as i16
+}
+
+fn print_lasers(raw: &RawLaserReadings) {
+    for channel in 0..RAW_LASERS_COUNT {
+        let health = &raw.health[channel];
+        log::info!(
+            "laser {}: {} {}mm, {} errors, {} out of range, {} reinits (last {})",
+            channel,
+            raw.states[channel].name(),
+            raw.values[channel],
+            raw.errors[channel],
+            health.out_of_range,
+            health.reinits,
+            health
+                .last_fault
+                .map(|fault| fault.name())
+                .unwrap_or("none")
+        );
+    }
+    log::info!("I2C bus recoveries: {}", raw.bus_health.recoveries);
+    log::info!("cmd queue overflows: {}", CMD.overflows());
+}
+
+/// Laser channel error counters, two channels per line: red when one of
+/// them is failing right now.
+pub async fn run() -> Screen {
+    let mut ui = VisualState::init();
+
+    ui.values_h[0].text_green("DIAG");
+    ui.values_h[1].empty();
+    ui.values_h[2].empty();
+    ui.values_h[3].empty();
+    ui.values_h[4].empty();
+    ui.values_v[0].black();
+    ui.values_v[1].black();
+    ui.values_v[2].black();
+    ui.values_v[3].black();
+    ui.values_v[4].black();
+
+    let mut lasers = RAW_LASER_READINGS.subscribe();
+    let mut last = None;
+
+    loop {
+        match select(lasers.wait(), CMD.wait()).await {
+            Either::First(raw) => {
+                for line in 0..RAW_LASERS_COUNT / 2 {
+                    let (a, b) = (line * 2, line * 2 + 1);
+                    let (errors_a, errors_b) = (errors(&raw, a), errors(&raw, b));
+                    if raw.is_valid(a) && raw.is_valid(b) {
+                        ui.values_h[line + 1].value2_green(errors_a, errors_b);
+                    } else {
+                        ui.values_h[line + 1].value2_red(errors_a, errors_b);
+                    }
+                }
+                last = Some(raw);
+            }
+            Either::Second(c) => {
+                log::info!("cmd: {}", c.name());
+                match c {
+                    Cmd::Previous => return Screen::Rgb,
+                    Cmd::Next => return Screen::Calibration,
+                    Cmd::Ok => {
+                        if let Some(raw) = &last {
+                            print_lasers(raw);
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        motors_stop();
+        VISUAL_STATE.publish(ui);
+    }
+}
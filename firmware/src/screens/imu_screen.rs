@@ -0,0 +1,137 @@
+use embassy_futures::select::{select3, Either3};
+
+use crate::{
+    cmd::{Cmd, CMD},
+    configuration::RaceConfig,
+    heading::HeadingTracker,
+    imu::{ImuCommand, ImuLinkStats, ImuMode, IMU_COMMAND, IMU_DATA, IMU_MODE},
+    lasers::RAW_LASER_READINGS,
+    lcd::{VisualState, VISUAL_STATE},
+    motors::motors_stop,
+    race::Angle,
+    uformat,
+    uformat::FormattedText,
+    vision::Vision,
+};
+
+use super::Screen;
+
+/// Frames the link quality is computed over.
+const LINK_WINDOW: u32 = 100;
+/// Link quality (percent) below which it is shown in red.
+const LINK_QUALITY_OK: u8 = 95;
+
+fn print_link(link: &ImuLinkStats) {
+    log::info!(
+        "IMU link: {} good, {} bad, {} resyncs, {} lost",
+        link.good,
+        link.bad,
+        link.resyncs,
+        link.lost
+    );
+}
+
+/// IMU angles and accelerations, with the quality of the serial link in the
+/// title (good frames out of the last `LINK_WINDOW`). Ok logs the link
+/// counters, Plus saves the IMU calibration (SHTP mode only). In SHTP mode
+/// the yaw rate and the stability classifier are logged with the counters.
+///
+/// Exit switches to the heading page: drift corrected heading, drift rate
+/// (cdeg/min) and drift since the zero (cdeg), marker anchors and yaw rate
+/// (deg/s, SHTP mode only). Minus zeroes the heading.
+pub async fn run(config: &RaceConfig, heading: &mut HeadingTracker) -> Screen {
+    let mut ui = VisualState::init();
+    let mut v = Vision::new();
+    let mut current_pitch = Angle::ZERO;
+    let mut show_heading = false;
+
+    ui.values_h[0].text_green("IMU");
+    ui.values_h[1].text("");
+    ui.values_h[2].text("");
+    ui.values_h[3].text("");
+    ui.values_h[4].text("");
+
+    let mut lasers = RAW_LASER_READINGS.subscribe();
+    let mut imu = IMU_DATA.subscribe();
+    let mut window_start: Option<ImuLinkStats> = None;
+    let mut link = ImuLinkStats::new();
+    let mut extras = None;
+    let mut last_imu = None;
+
+    loop {
+        match select3(lasers.wait(), imu.wait(), CMD.wait()).await {
+            Either3::First(data) => {
+                v.update(&data, &config, current_pitch);
+                ui.update_vision(&v, None);
+            }
+            Either3::Second(data) => {
+                current_pitch = Angle::from_imu_value(data.pitch);
+                last_imu = Some(data);
+                if show_heading {
+                    let now = data.timestamp;
+                    ui.values_h[1].value(heading.heading(data.yaw, now).into());
+                    ui.values_h[2]
+                        .value2_blue(heading.drift_rate() as i16, heading.drift(now) as i16);
+                    ui.values_h[3].value(heading.anchors() as i16);
+                    match data.extras {
+                        Some(extras) => ui.values_h[4].value(extras.yaw_rate / 100),
+                        None => ui.values_h[4].text("NO GYRO"),
+                    }
+                } else {
+                    ui.values_h[1].imu_angles(data.yaw, data.pitch, data.roll);
+                    ui.values_h[2].value(data.forward);
+                    ui.values_h[3].value(data.side);
+                    ui.values_h[4].value(data.vertical);
+                }
+
+                link = data.link;
+                extras = data.extras;
+                let start = *window_start.get_or_insert(link);
+                if link.frames().wrapping_sub(start.frames()) >= LINK_WINDOW {
+                    let quality = link.quality_since(&start);
+                    let text = uformat!("IMU {}%", quality);
+                    if quality >= LINK_QUALITY_OK {
+                        ui.values_h[0].label_green(text.as_str());
+                    } else {
+                        ui.values_h[0].label_red(text.as_str());
+                    }
+                    window_start = Some(link);
+                }
+            }
+            Either3::Third(c) => {
+                log::info!("cmd: {}", c.name());
+                match c {
+                    Cmd::Previous => return Screen::Motors,
+                    Cmd::Next => return Screen::Rgb,
+                    Cmd::Ok => {
+                        print_link(&link);
+                        if let Some(extras) = extras {
+                            log::info!(
+                                "IMU yaw rate {} stability {} taps {}",
+                                extras.yaw_rate,
+                                extras.stability.name(),
+                                extras.taps
+                            );
+                        }
+                    }
+                    Cmd::Plus => {
+                        if IMU_MODE == ImuMode::Shtp {
+                            log::info!("saving IMU calibration");
+                            IMU_COMMAND.signal(ImuCommand::SaveCalibration);
+                        }
+                    }
+                    Cmd::Minus => {
+                        if let Some(data) = last_imu {
+                            heading.rezero(data.yaw, data.timestamp);
+                            log::info!("IMU heading zeroed at yaw {}", data.yaw);
+                        }
+                    }
+                    Cmd::Exit => show_heading = !show_heading,
+                }
+            }
+        }
+
+        motors_stop();
+        VISUAL_STATE.publish(ui);
+    }
+}
@@ -0,0 +1,301 @@
+use core::pin::pin;
+
+use embassy_futures::select::{select4, Either4};
+use embassy_sync::{blocking_mutex::raw::CriticalSectionRawMutex, signal::Signal};
+use embassy_time::Instant;
+
+use crate::{
+    cmd::{Cmd, CMD},
+    configuration::{ConfigViolations, RaceConfig, RaceConfigEntry},
+    console_println,
+    heading::HeadingTracker,
+    imu::IMU_DATA,
+    lasers::{LASER_CALIBRATION, LASER_SETTINGS},
+    lcd::{VisualState, VISUAL_STATE},
+    motors::{motors_stop, FaultCode, SAFETY_ACK, SAFETY_FAULT},
+    profiles::{RaceProfile, RaceProfiles},
+    race::race,
+    storage::RaceConfigStorage,
+    trace::{TraceCommand, TRACE},
+    usb::{ConsoleCommand, CONSOLE},
+};
+
+mod calibration_screen;
+mod config_screen;
+mod diagnostics_screen;
+mod imu_screen;
+mod motors_screen;
+mod profiles_screen;
+mod race_screen;
+mod ready_screen;
+mod rgb_screen;
+
+/// The active profile as changed by the running screen, so that console
+/// queries see the changes without interrupting it.
+static ACTIVE_PROFILE: Signal<CriticalSectionRawMutex, RaceProfile> = Signal::new();
+
+/// Called by the screens after changing the profiles.
+fn profiles_changed(profiles: &RaceProfiles) {
+    ACTIVE_PROFILE.signal(*profiles.active());
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Screen {
+    Ready,
+    Race,
+    RaceNow,
+    Motors,
+    Config,
+    Profiles,
+    Simulation,
+    Imu,
+    Rgb,
+    Diagnostics,
+    Calibration,
+}
+
+async fn simulation_screen(config: &RaceConfig, heading: &mut HeadingTracker) -> Screen {
+    let imu_data = IMU_DATA.subscribe().wait().await;
+    heading.rezero(imu_data.yaw, Instant::now());
+    race(config, *heading, true).await
+}
+
+/// Shown instead of starting a race while the config has violations.
+async fn invalid_config_screen(violations: &ConfigViolations) -> Screen {
+    let mut ui = VisualState::init();
+
+    motors_stop();
+
+    for violation in violations.iter() {
+        log::error!(
+            "config violation: {} ({})",
+            violation.description(),
+            violation.entries()[0].name()
+        );
+    }
+    let first = violations[0];
+
+    ui.values_h[0].empty();
+    ui.values_h[1].text_red("INVALID");
+    ui.values_h[2].text(first.description());
+    ui.values_h[3].text(first.entries()[0].name());
+    ui.values_h[4].value2_red(1, violations.len() as i16);
+    ui.values_v[0].red();
+    ui.values_v[1].red();
+    ui.values_v[2].red();
+    ui.values_v[3].red();
+    ui.values_v[4].red();
+    VISUAL_STATE.publish(ui);
+
+    CMD.wait().await;
+    Screen::Config
+}
+
+/// Shown when the safety supervisor stopped the motors, until the fault is
+/// acknowledged with Ok.
+async fn fault_screen(fault: FaultCode) -> Screen {
+    let mut ui = VisualState::init();
+
+    motors_stop();
+
+    ui.values_h[0].empty();
+    ui.values_h[1].text_red("FAULT");
+    ui.values_h[2].text(fault.description());
+    ui.values_h[3].value(fault.code());
+    ui.values_h[4].text("OK TO RESUME");
+    ui.values_v[0].red();
+    ui.values_v[1].red();
+    ui.values_v[2].red();
+    ui.values_v[3].red();
+    ui.values_v[4].red();
+    VISUAL_STATE.publish(ui);
+
+    while CMD.wait().await != Cmd::Ok {}
+    SAFETY_ACK.signal(());
+    Screen::Ready
+}
+
+fn print_config_value(config: &RaceConfig, entry: RaceConfigEntry) {
+    let value = config.get(entry);
+    match entry.value_name(value) {
+        Some(name) => console_println!("{} = {}", entry.key(), name),
+        None => console_println!("{} = {} {}", entry.key(), value, entry.unit()),
+    }
+}
+
+fn print_violations(config: &RaceConfig) {
+    for violation in config.validate().iter() {
+        console_println!(
+            "violation: {} ({})",
+            violation.description(),
+            violation.entries()[0].key()
+        );
+    }
+}
+
+/// Read only console commands, which can be answered without leaving the
+/// current screen.
+fn console_query(command: ConsoleCommand, profile: &RaceProfile) -> bool {
+    match command {
+        ConsoleCommand::ConfigGet(entry) => print_config_value(&profile.config, entry),
+        ConsoleCommand::ConfigDump => {
+            console_println!("profile {}", profile.name());
+            for entry in RaceConfigEntry::iter() {
+                print_config_value(&profile.config, entry);
+            }
+            print_violations(&profile.config);
+        }
+        ConsoleCommand::TraceDump => {
+            console_println!("trace on the log port");
+            TRACE.signal(TraceCommand::Print);
+        }
+        _ => return false,
+    }
+    true
+}
+
+/// Handles a console command that interrupted `screen`, returning the
+/// screen to go to next. Queries never get here, see `console_query`.
+fn console_command(
+    command: ConsoleCommand,
+    screen: Screen,
+    profiles: &mut RaceProfiles,
+    storage: &mut RaceConfigStorage,
+) -> Screen {
+    log::info!("console: {:?}", command);
+    match command {
+        ConsoleCommand::ConfigSet(entry, value) => {
+            profiles.config_mut().set(entry, value);
+            print_config_value(profiles.config(), entry);
+            print_violations(profiles.config());
+            screen
+        }
+        ConsoleCommand::ConfigSave => {
+            match storage.save(profiles) {
+                Ok(()) => console_println!("config saved"),
+                Err(err) => console_println!("config save failed: {}", err.description()),
+            }
+            screen
+        }
+        ConsoleCommand::RaceStart => Screen::Race,
+        ConsoleCommand::RaceStop => {
+            motors_stop();
+            Screen::Ready
+        }
+        ConsoleCommand::RebootBootsel => {
+            motors_stop();
+            console_println!("rebooting to bootsel");
+            rp2040_hal::rom_data::reset_to_usb_boot(0, 0);
+            screen
+        }
+        ConsoleCommand::ConfigGet(_) | ConsoleCommand::ConfigDump | ConsoleCommand::TraceDump => {
+            screen
+        }
+    }
+}
+
+enum Interruption {
+    None(Screen),
+    Console(ConsoleCommand),
+    Fault(FaultCode),
+}
+
+async fn run_screen(
+    screen: Screen,
+    profile: &RaceProfile,
+    violations: &ConfigViolations,
+    profiles: &mut RaceProfiles,
+    storage: &mut RaceConfigStorage,
+    heading: &mut HeadingTracker,
+) -> Screen {
+    let config = &profile.config;
+    match screen {
+        Screen::Race | Screen::RaceNow | Screen::Simulation if !violations.is_empty() => {
+            invalid_config_screen(violations).await
+        }
+        Screen::Ready => ready_screen::run(profiles, heading).await,
+        Screen::Race => {
+            race_screen::run(profile, false, heading).await;
+            Screen::Ready
+        }
+        Screen::RaceNow => {
+            race_screen::run(profile, true, heading).await;
+            Screen::Ready
+        }
+        Screen::Motors => motors_screen::run(config).await,
+        Screen::Config => config_screen::run(profiles, storage).await,
+        Screen::Profiles => profiles_screen::run(profiles, storage).await,
+        Screen::Imu => imu_screen::run(config, heading).await,
+        Screen::Rgb => rgb_screen::run().await,
+        Screen::Diagnostics => diagnostics_screen::run().await,
+        Screen::Calibration => calibration_screen::run(profiles, storage).await,
+        Screen::Simulation => simulation_screen(config, heading).await,
+    }
+}
+
+pub async fn run(storage: &mut RaceConfigStorage) -> ! {
+    let mut profiles = storage.load_or_default();
+    let mut screen = Screen::Ready;
+    // Zeroed at each race start, keeps the measured drift across races
+    let mut heading = HeadingTracker::new(0, Instant::now());
+
+    loop {
+        let profile: RaceProfile = *profiles.active();
+        let violations = profile.config.validate();
+        let racing = matches!(screen, Screen::Race | Screen::RaceNow);
+        // Applied by the lasers task only when they changed
+        LASER_SETTINGS.signal(profile.config.laser_settings());
+        LASER_CALIBRATION.signal(*profiles.calibration());
+
+        // Console queries are answered without interrupting the current
+        // screen, except `trace dump` while racing. The other commands
+        // interrupt it, except while racing where only `race stop` is
+        // accepted. Safety faults always interrupt it.
+        let mut live = profile;
+        // Left over from a screen that already ended
+        ACTIVE_PROFILE.try_take();
+        let interrupted = {
+            let mut current = pin!(run_screen(
+                screen,
+                &profile,
+                &violations,
+                &mut profiles,
+                storage,
+                &mut heading
+            ));
+            loop {
+                match select4(
+                    current.as_mut(),
+                    CONSOLE.wait(),
+                    SAFETY_FAULT.wait(),
+                    ACTIVE_PROFILE.wait(),
+                )
+                .await
+                {
+                    Either4::First(next) => break Interruption::None(next),
+                    Either4::Second(command) => {
+                        // The race pushes to the trace every step, which would
+                        // overwrite or hold up the dump
+                        let traced = racing && command == ConsoleCommand::TraceDump;
+                        if !traced && console_query(command, &live) {
+                            log::info!("console: {:?}", command);
+                        } else if command == ConsoleCommand::RaceStop || !racing {
+                            break Interruption::Console(command);
+                        } else {
+                            console_println!("busy racing");
+                        }
+                    }
+                    Either4::Third(fault) => break Interruption::Fault(fault),
+                    Either4::Fourth(changed) => live = changed,
+                }
+            }
+        };
+        screen = match interrupted {
+            Interruption::None(next) => next,
+            Interruption::Console(command) => {
+                console_command(command, screen, &mut profiles, storage)
+            }
+            Interruption::Fault(fault) => fault_screen(fault).await,
+        };
+    }
+}
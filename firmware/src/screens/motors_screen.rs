@@ -23,19 +23,24 @@ pub async fn run(config: &RaceConfig) -> Screen {
     ui.values_h[3].empty();
     ui.values_h[4].empty();
 
-    let mut steer = 0;
+    let mut steer = Angle::ZERO;
     let mut power = 0;
     let mut current_pitch = Angle::ZERO;
 
+    let mut lasers = RAW_LASER_READINGS.subscribe();
+    let mut imu = IMU_DATA.subscribe();
+
     loop {
-        match select3(RAW_LASER_READINGS.wait(), IMU_DATA.wait(), CMD.wait()).await {
+        match select3(lasers.wait(), imu.wait(), CMD.wait()).await {
             Either3::First(data) => {
                 v.update(&data, &config, current_pitch);
                 ui.update_vision(&v, None);
             }
             Either3::Second(data) => {
                 current_pitch = Angle::from_imu_value(data.pitch);
-                steer = -(data.yaw / 100).min(35).max(-35);
+                steer = (-Angle::from_imu_value(data.yaw))
+                    .min(Angle::MAX_STEER)
+                    .max(Angle::MIN_STEER);
                 let pitch = (data.pitch as i32 / 100).min(90).max(-90);
                 power = if pitch > 10 {
                     ((pitch - 10) * 10000 / 80).min(10000)
@@ -57,7 +62,7 @@ pub async fn run(config: &RaceConfig) -> Screen {
             }
         }
 
-        motors_go(power, steer);
-        VISUAL_STATE.signal(ui);
+        motors_go(power, config.servo_steer(steer));
+        VISUAL_STATE.publish(ui);
     }
 }
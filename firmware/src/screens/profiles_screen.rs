@@ -0,0 +1,84 @@
+use embassy_futures::select::{select3, Either3};
+
+use crate::{
+    cmd::{Cmd, CMD},
+    imu::IMU_DATA,
+    lasers::RAW_LASER_READINGS,
+    lcd::{VisualState, VISUAL_STATE},
+    motors::motors_stop,
+    profiles::{RaceProfiles, PROFILE_NAME_SIZE},
+    storage::RaceConfigStorage,
+};
+
+use super::{profiles_changed, Screen};
+
+pub async fn run(profiles: &mut RaceProfiles, storage: &mut RaceConfigStorage) -> Screen {
+    let mut ui = VisualState::init();
+
+    ui.values_h[0].empty();
+    ui.values_h[1].text_red("COUNTRYMAN");
+    ui.values_h[2].text_green("PROFILES");
+    ui.values_h[3].empty();
+    ui.values_h[4].empty();
+    ui.values_v[0].empty();
+    ui.values_v[1].empty();
+    ui.values_v[2].empty();
+    ui.values_v[3].empty();
+    ui.values_v[4].empty();
+
+    // Some(cursor) while renaming the active profile
+    let mut renaming: Option<usize> = None;
+
+    let mut lasers = RAW_LASER_READINGS.subscribe();
+    let mut imu = IMU_DATA.subscribe();
+
+    loop {
+        match renaming {
+            Some(cursor) => ui.values_h[3].label_edit(profiles.active().name(), cursor),
+            None => ui.values_h[3].label(profiles.active().name()),
+        }
+        ui.values_h[4].value2_blue(profiles.active_index() as i16 + 1, profiles.len() as i16);
+        motors_stop();
+        VISUAL_STATE.publish(ui);
+
+        match select3(lasers.wait(), imu.wait(), CMD.wait()).await {
+            Either3::First(_data) => {}
+            Either3::Second(_data) => {}
+            Either3::Third(c) => {
+                log::info!("cmd: {}", c.name());
+                ui.values_h[2].text_green("PROFILES");
+                match renaming {
+                    Some(cursor) => match c {
+                        Cmd::Previous => renaming = Some(cursor.saturating_sub(1)),
+                        Cmd::Next => renaming = Some((cursor + 1).min(PROFILE_NAME_SIZE - 1)),
+                        Cmd::Plus => profiles.active_mut().change_name_char(cursor, 1),
+                        Cmd::Minus => profiles.active_mut().change_name_char(cursor, -1),
+                        Cmd::Ok | Cmd::Exit => renaming = None,
+                    },
+                    None => match c {
+                        Cmd::Previous => profiles.select_prev(),
+                        Cmd::Next => profiles.select_next(),
+                        Cmd::Plus => {
+                            if !profiles.clone_active() {
+                                ui.values_h[2].text_red("FULL");
+                            }
+                        }
+                        Cmd::Minus => renaming = Some(0),
+                        Cmd::Ok => match storage.save(profiles) {
+                            Ok(()) => {
+                                log::info!("profiles saved");
+                                ui.values_h[2].text_green("SAVED");
+                            }
+                            Err(err) => {
+                                log::error!("profiles save failed: {}", err.description());
+                                ui.values_h[2].text_red("SAVE ERROR");
+                            }
+                        },
+                        Cmd::Exit => return Screen::Ready,
+                    },
+                }
+                profiles_changed(profiles);
+            }
+        }
+    }
+}
@@ -1,23 +1,28 @@
 use embassy_futures::select::{select, Either};
-use embassy_time::Duration;
+use embassy_time::{Duration, Instant};
 
 use crate::{
     cmd::CMD,
-    configuration::RaceConfig,
+    heading::HeadingTracker,
     imu::IMU_DATA,
     lcd::{VisualState, VISUAL_STATE},
     motors::motors_stop,
-    race::{race, Angle},
+    profiles::RaceProfile,
+    race::race,
+    trace::{TraceCommand, TRACE},
 };
 
 use super::Screen;
 
-pub async fn run(config: &RaceConfig, now: bool) -> Screen {
+/// The heading is zeroed on the direction the car faces at the start.
+pub async fn run(profile: &RaceProfile, now: bool, heading: &mut HeadingTracker) -> Screen {
+    TRACE.signal(TraceCommand::Start(*profile));
     let yaw = match if now { wait_1().await } else { wait_5().await } {
         Some(yaw) => yaw,
         None => return Screen::Ready,
     };
-    race(config, Angle::from_imu_value(yaw), false).await;
+    heading.rezero(yaw, Instant::now());
+    race(&profile.config, *heading, false).await;
     return Screen::Ready;
 }
 
@@ -37,7 +42,7 @@ async fn wait_5() -> Option<i16> {
     ui.values_v[2].red();
     ui.values_v[3].red();
     ui.values_v[4].red();
-    VISUAL_STATE.signal(ui);
+    VISUAL_STATE.publish(ui);
 
     for c in 0usize..4 {
         match select(
@@ -48,7 +53,7 @@ async fn wait_5() -> Option<i16> {
         {
             Either::First(_) => {
                 ui.values_v[c + 1].yellow();
-                VISUAL_STATE.signal(ui);
+                VISUAL_STATE.publish(ui);
             }
             Either::Second(_) => return None,
         }
@@ -59,7 +64,7 @@ async fn wait_5() -> Option<i16> {
     )
     .await
     {
-        Either::First(_) => Some(IMU_DATA.wait().await.yaw),
+        Either::First(_) => Some(IMU_DATA.subscribe().wait().await.yaw),
         Either::Second(_) => None,
     }
 }
@@ -80,7 +85,7 @@ async fn wait_1() -> Option<i16> {
     ui.values_v[2].yellow();
     ui.values_v[3].red();
     ui.values_v[4].yellow();
-    VISUAL_STATE.signal(ui);
+    VISUAL_STATE.publish(ui);
 
     match select(
         embassy_time::Timer::after(Duration::from_secs(1)),
@@ -88,7 +93,7 @@ async fn wait_1() -> Option<i16> {
     )
     .await
     {
-        Either::First(_) => Some(IMU_DATA.wait().await.yaw),
+        Either::First(_) => Some(IMU_DATA.subscribe().wait().await.yaw),
         Either::Second(_) => None,
     }
 }
@@ -3,26 +3,30 @@ use embassy_time::Instant;
 
 use crate::{
     cmd::{Cmd, CMD},
-    configuration::RaceConfig,
+    heading::{DriftMeter, HeadingTracker},
     imu::IMU_DATA,
     lasers::RAW_LASER_READINGS,
     lcd::{VisualState, VISUAL_STATE},
     motors::motors_stop,
+    profiles::RaceProfiles,
     race::Angle,
     rgb::RGB,
     vision::Vision,
 };
 
-use super::Screen;
+use super::{profiles_changed, Screen};
 
-pub async fn run(config: &RaceConfig) -> Screen {
+/// While the car stands still here the IMU drift is measured, and applied to
+/// the heading of the next races.
+pub async fn run(profiles: &mut RaceProfiles, heading: &mut HeadingTracker) -> Screen {
     let mut ui = VisualState::init();
     let mut v = Vision::new();
     let mut current_pitch = Angle::ZERO;
+    let mut drift = DriftMeter::new();
 
     motors_stop();
 
-    ui.values_h[0].empty();
+    ui.values_h[0].label_green(profiles.active().name());
     ui.values_h[1].text_red("COUNTRYMAN");
     ui.values_h[2].text_green("READY");
     ui.values_h[3].empty();
@@ -32,15 +36,13 @@ pub async fn run(config: &RaceConfig) -> Screen {
     let mut last_imu = now;
     let mut last_rgb = now;
 
+    let mut lasers = RAW_LASER_READINGS.subscribe();
+    let mut imu = IMU_DATA.subscribe();
+    let mut rgb = RGB.subscribe();
+
     loop {
-        match select4(
-            RAW_LASER_READINGS.wait(),
-            IMU_DATA.wait(),
-            CMD.wait(),
-            RGB.wait(),
-        )
-        .await
-        {
+        let config = profiles.config();
+        match select4(lasers.wait(), imu.wait(), CMD.wait(), rgb.wait()).await {
             Either4::First(data) => {
                 let now = Instant::now();
                 log::info!(
@@ -60,6 +62,10 @@ pub async fn run(config: &RaceConfig) -> Screen {
                     now.duration_since(last_imu).as_micros()
                 );
                 last_imu = now;
+                drift.update(&data);
+                if let Some(rate) = drift.rate() {
+                    heading.set_drift_rate(rate, data.timestamp);
+                }
                 current_pitch = Angle::from_imu_value(data.pitch);
                 ui.values_h[4].imu(
                     data.yaw,
@@ -76,7 +82,13 @@ pub async fn run(config: &RaceConfig) -> Screen {
                     Cmd::Next => return Screen::Race,
                     Cmd::Plus => return Screen::Simulation,
                     Cmd::Minus => return Screen::Config,
-                    _ => {}
+                    Cmd::Ok => {
+                        profiles.select_next();
+                        log::info!("profile: {}", profiles.active().name());
+                        ui.values_h[0].label_green(profiles.active().name());
+                        profiles_changed(profiles);
+                    }
+                    Cmd::Exit => return Screen::Profiles,
                 }
             }
             Either4::Fourth(data) => {
@@ -92,6 +104,6 @@ pub async fn run(config: &RaceConfig) -> Screen {
         }
 
         motors_stop();
-        VISUAL_STATE.signal(ui);
+        VISUAL_STATE.publish(ui);
     }
 }
@@ -35,8 +35,10 @@ pub async fn run() -> Screen {
     ui.values_v[3].black();
     ui.values_v[4].black();
 
+    let mut rgb = RGB.subscribe();
+
     loop {
-        match select(RGB.wait(), CMD.wait()).await {
+        match select(rgb.wait(), CMD.wait()).await {
             Either::First(data) => {
                 now = Instant::now();
 
@@ -87,7 +89,7 @@ pub async fn run() -> Screen {
                 log::info!("cmd: {}", c.name());
                 match c {
                     Cmd::Previous => return Screen::Imu,
-                    Cmd::Next => return Screen::Ready,
+                    Cmd::Next => return Screen::Diagnostics,
                     Cmd::Plus | Cmd::Minus => {
                         r_min = i16::MAX;
                         g_min = i16::MAX;
@@ -102,6 +104,6 @@ pub async fn run() -> Screen {
         }
 
         motors_stop();
-        VISUAL_STATE.signal(ui);
+        VISUAL_STATE.publish(ui);
     }
 }
@@ -0,0 +1,16 @@
+use embassy_rp::flash::{Blocking, Flash};
+use embassy_rp::peripherals::FLASH;
+
+pub use countryman_core::storage::*;
+
+/// The T-PicoC3 carries a 2MB W25Q16 flash.
+pub const FLASH_SIZE: usize = 2 * 1024 * 1024;
+/// Last sector of the flash, kept out of the program image by `memory.x`.
+pub const CONFIG_OFFSET: u32 = (FLASH_SIZE - 4096) as u32;
+
+pub type ConfigFlash = Flash<'static, FLASH, Blocking, FLASH_SIZE>;
+pub type RaceConfigStorage = ConfigStorage<ConfigFlash>;
+
+pub fn init(flash: FLASH) -> RaceConfigStorage {
+    ConfigStorage::new(Flash::new_blocking(flash), CONFIG_OFFSET)
+}
@@ -0,0 +1,93 @@
+//! Latest value broadcast to several subscribers.
+//!
+//! Sensor readings and the visual state are published as topics, so that a
+//! logger or a safety monitor can watch them while a screen or the race loop
+//! uses them too. Each subscriber only sees the latest value: when it is too
+//! slow the older ones are skipped and counted as lagged.
+//!
+//! Motor outputs are not a topic: `motors::MOTORS_DATA` keeps a single
+//! consumer, and only the active screen writes it.
+
+use embassy_sync::{
+    blocking_mutex::raw::CriticalSectionRawMutex,
+    pubsub::{PubSubChannel, Subscriber, WaitResult},
+};
+
+/// Subscribers each topic can have at the same time.
+pub const TOPIC_SUBSCRIBERS: usize = 4;
+
+type TopicChannel<T> = PubSubChannel<CriticalSectionRawMutex, T, 1, TOPIC_SUBSCRIBERS, 1>;
+
+pub struct Topic<T: Clone> {
+    channel: TopicChannel<T>,
+}
+
+impl<T: Clone> Topic<T> {
+    pub const fn new() -> Self {
+        Self {
+            channel: PubSubChannel::new(),
+        }
+    }
+
+    /// Publishes a value, replacing the previous one for subscribers that did
+    /// not read it yet.
+    pub fn publish(&self, value: T) {
+        self.channel.immediate_publisher().publish_immediate(value);
+    }
+
+    /// Panics when the topic already has `TOPIC_SUBSCRIBERS` subscribers.
+    pub fn subscribe(&self) -> TopicSubscriber<'_, T> {
+        TopicSubscriber {
+            subscriber: self
+                .channel
+                .subscriber()
+                .expect("too many topic subscribers"),
+            received: 0,
+            lagged: 0,
+        }
+    }
+}
+
+pub struct TopicSubscriber<'a, T: Clone> {
+    subscriber: Subscriber<'a, CriticalSectionRawMutex, T, 1, TOPIC_SUBSCRIBERS, 1>,
+    received: u32,
+    lagged: u32,
+}
+
+impl<'a, T: Clone> TopicSubscriber<'a, T> {
+    /// Waits for a value published after the last one received.
+    pub async fn wait(&mut self) -> T {
+        loop {
+            match self.subscriber.next_message().await {
+                WaitResult::Lagged(skipped) => self.lagged += skipped as u32,
+                WaitResult::Message(value) => {
+                    self.received += 1;
+                    return value;
+                }
+            }
+        }
+    }
+
+    /// The latest unread value, if any.
+    pub fn try_wait(&mut self) -> Option<T> {
+        loop {
+            match self.subscriber.try_next_message()? {
+                WaitResult::Lagged(skipped) => self.lagged += skipped as u32,
+                WaitResult::Message(value) => {
+                    self.received += 1;
+                    return Some(value);
+                }
+            }
+        }
+    }
+
+    /// Values received so far.
+    pub fn received(&self) -> u32 {
+        self.received
+    }
+
+    /// Values published while this subscriber was not reading them.
+    pub fn lagged(&self) -> u32 {
+        self.lagged
+    }
+}
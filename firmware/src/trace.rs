@@ -0,0 +1,113 @@
+use embassy_sync::{blocking_mutex::raw::CriticalSectionRawMutex, signal::Signal};
+use embassy_time::Duration;
+use static_cell::make_static;
+
+pub use countryman_core::trace::*;
+
+use crate::profiles::RaceProfile;
+use crate::race::Angle;
+
+pub static TRACE: Signal<CriticalSectionRawMutex, TraceCommand> = Signal::new();
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum TraceCommand {
+    /// Starts a new trace, recording the profile the race runs with
+    Start(RaceProfile),
+    Clear,
+    Print,
+    Push(TraceEvent),
+}
+
+const EMPTY_EVENT: TraceEvent = TraceEvent {
+    absolute_heading: Angle::ZERO,
+    track_heading: Angle::ZERO,
+    steer: Angle::ZERO,
+    speed: 0,
+    roll: Angle::ZERO,
+    pitch: Angle::ZERO,
+    yaw: Angle::ZERO,
+    forward: 0,
+    side: 0,
+    vertical: 0,
+    has_back_panic: false,
+    remaining_back_panic_ms: 0,
+    has_target: false,
+    remaining_target_ms: 0,
+    target: Angle::ZERO,
+    target_back: false,
+    stillness: false,
+    dt_us: 0,
+};
+
+const TRACE_EVENTS: usize = 3000;
+
+struct TraceData {
+    pub profile: Option<RaceProfile>,
+    pub start: usize,
+    pub end: usize,
+    pub events: [TraceEvent; TRACE_EVENTS],
+}
+
+impl TraceData {
+    pub fn clear(&mut self) {
+        self.start = 0;
+        self.end = 0;
+    }
+
+    pub fn start(&mut self, profile: RaceProfile) {
+        self.clear();
+        self.profile = Some(profile);
+    }
+
+    pub fn push(&mut self, event: TraceEvent) {
+        let new_end = (self.end + 1) % TRACE_EVENTS;
+        if self.start == new_end {
+            self.start = (self.start + 1) % TRACE_EVENTS;
+        }
+        self.events[self.end] = event;
+        self.end = new_end;
+    }
+
+    pub async fn print(&self) {
+        log::info!("printing trace: [{} {}]", self.start, self.end);
+        if let Some(profile) = &self.profile {
+            profile.print();
+            embassy_time::Timer::after(Duration::from_millis(16)).await;
+        }
+        let mut current = self.start;
+        let mut elapsed = Duration::from_millis(0);
+        loop {
+            self.events[current].print(current, elapsed);
+            embassy_time::Timer::after(Duration::from_millis(16)).await;
+            elapsed += Duration::from_micros(self.events[current].dt_us as u64);
+            current = (current + 1) % TRACE_EVENTS;
+            if current == self.end {
+                break;
+            }
+        }
+    }
+}
+
+pub async fn trace_task() {
+    let data: &'static mut TraceData = make_static!(TraceData {
+        profile: None,
+        start: 0,
+        end: 0,
+        events: [EMPTY_EVENT; TRACE_EVENTS],
+    });
+
+    loop {
+        match TRACE.wait().await {
+            TraceCommand::Start(profile) => {
+                log::info!("starting trace with profile {}", profile.name());
+                data.start(profile);
+            }
+            TraceCommand::Clear => {
+                log::info!("clearing trace");
+                data.clear();
+            }
+            TraceCommand::Print => data.print().await,
+            TraceCommand::Push(event) => data.push(event),
+        }
+    }
+}
@@ -0,0 +1,171 @@
+//! Composite USB device with two CDC-ACM serial ports: the first one carries
+//! the log output, the second one is the interactive console (see
+//! `countryman_core::console` for the protocol).
+
+use core::fmt::Write as _;
+
+use embassy_futures::join::{join, join3};
+use embassy_rp::{peripherals::USB, usb::Driver};
+use embassy_sync::{blocking_mutex::raw::CriticalSectionRawMutex, pipe::Pipe, signal::Signal};
+use embassy_usb::{
+    class::cdc_acm::{CdcAcmClass, Receiver, Sender, State},
+    driver::EndpointError,
+    Builder, Config,
+};
+use static_cell::make_static;
+
+pub use countryman_core::console::*;
+
+use crate::cmd::{CmdSource, CMD};
+
+const MAX_PACKET_SIZE: usize = 64;
+const OUTPUT_BUFFER_SIZE: usize = 1024;
+
+type OutputPipe = Pipe<CriticalSectionRawMutex, OUTPUT_BUFFER_SIZE>;
+
+/// Line commands received from the console, handled by `screens::run`.
+pub static CONSOLE: Signal<CriticalSectionRawMutex, ConsoleCommand> = Signal::new();
+
+static LOG_OUT: OutputPipe = Pipe::new();
+static CONSOLE_OUT: OutputPipe = Pipe::new();
+
+/// Writes into a pipe without waiting: when the host is not reading, the
+/// output is dropped instead of blocking the caller.
+struct PipeWriter(&'static OutputPipe);
+
+impl core::fmt::Write for PipeWriter {
+    fn write_str(&mut self, s: &str) -> core::fmt::Result {
+        let mut bytes = s.as_bytes();
+        while !bytes.is_empty() {
+            match self.0.try_write(bytes) {
+                Ok(written) => bytes = &bytes[written..],
+                Err(_) => break,
+            }
+        }
+        Ok(())
+    }
+}
+
+struct UsbLogger;
+
+impl log::Log for UsbLogger {
+    fn enabled(&self, _metadata: &log::Metadata) -> bool {
+        true
+    }
+
+    fn log(&self, record: &log::Record) {
+        if self.enabled(record.metadata()) {
+            let _ = write!(PipeWriter(&LOG_OUT), "{}\r\n", record.args());
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+static LOGGER: UsbLogger = UsbLogger;
+
+/// Prints a line on the console port (use `console_println!`).
+pub fn console_print(args: core::fmt::Arguments) {
+    let _ = write!(PipeWriter(&CONSOLE_OUT), "{}\r\n", args);
+}
+
+#[macro_export]
+macro_rules! console_println {
+    ($($arg:tt)*) => {
+        $crate::usb::console_print(format_args!($($arg)*))
+    };
+}
+
+async fn send_output<'d>(
+    sender: &mut Sender<'d, Driver<'d, USB>>,
+    output: &OutputPipe,
+) -> Result<(), EndpointError> {
+    let mut buf = [0; MAX_PACKET_SIZE];
+    loop {
+        let len = output.read(&mut buf).await;
+        sender.write_packet(&buf[..len]).await?;
+    }
+}
+
+async fn receive_console<'d>(
+    receiver: &mut Receiver<'d, Driver<'d, USB>>,
+) -> Result<(), EndpointError> {
+    let mut reader = ConsoleReader::new();
+    let mut buf = [0; MAX_PACKET_SIZE];
+    loop {
+        let len = receiver.read_packet(&mut buf).await?;
+        for received in &buf[..len] {
+            match reader.update(*received) {
+                Some(ConsoleInput::Cmd(cmd)) => CMD.send(cmd, CmdSource::Usb),
+                Some(ConsoleInput::Command(Ok(command))) => CONSOLE.signal(command),
+                Some(ConsoleInput::Command(Err(err))) => {
+                    crate::console_println!("error: {}", err.description())
+                }
+                None => {}
+            }
+        }
+    }
+}
+
+pub async fn usb_task(driver: Driver<'static, USB>) {
+    let mut config = Config::new(0xc0de, 0xcafe);
+    config.manufacturer = Some("Countryman");
+    config.product = Some("Countryman robot");
+    config.serial_number = Some("00000001");
+    config.max_power = 100;
+    config.max_packet_size_0 = MAX_PACKET_SIZE as u8;
+
+    // Required for two CDC-ACM functions on Windows
+    config.device_class = 0xEF;
+    config.device_sub_class = 0x02;
+    config.device_protocol = 0x01;
+    config.composite_with_iads = true;
+
+    let mut builder = Builder::new(
+        driver,
+        config,
+        &mut make_static!([0; 256])[..],
+        &mut make_static!([0; 256])[..],
+        &mut make_static!([0; 256])[..],
+        &mut make_static!([0; 64])[..],
+    );
+
+    let (mut log_tx, _) = CdcAcmClass::new(
+        &mut builder,
+        make_static!(State::new()),
+        MAX_PACKET_SIZE as u16,
+    )
+    .split();
+    let (mut console_tx, mut console_rx) = CdcAcmClass::new(
+        &mut builder,
+        make_static!(State::new()),
+        MAX_PACKET_SIZE as u16,
+    )
+    .split();
+    let mut device = builder.build();
+
+    unsafe {
+        let _ = log::set_logger_racy(&LOGGER).map(|()| log::set_max_level(log::LevelFilter::Info));
+    }
+
+    let log_fut = async {
+        loop {
+            log_tx.wait_connection().await;
+            let _ = send_output(&mut log_tx, &LOG_OUT).await;
+        }
+    };
+    let console_out_fut = async {
+        loop {
+            console_tx.wait_connection().await;
+            let _ = send_output(&mut console_tx, &CONSOLE_OUT).await;
+        }
+    };
+    let console_in_fut = async {
+        loop {
+            console_rx.wait_connection().await;
+            crate::console_println!("countryman console");
+            let _ = receive_console(&mut console_rx).await;
+        }
+    };
+    join3(device.run(), log_fut, join(console_out_fut, console_in_fut)).await;
+}
@@ -0,0 +1,125 @@
+//! Kinematic model of the car, driven by the same `(power, steer)` pairs the
+//! firmware hands to `motors_go`.
+
+use crate::geometry::{wrap_degrees, Vec2};
+use crate::track::{Pose, Track};
+
+/// `motors.rs` clamps the servo duty to `SERVO_CENTER_DUTY +- SERVO_MAX_DELTA_DUTY`
+/// (steer is in duty units, `SERVO_UNITS_PER_DEGREE` per degree of `Angle`).
+pub const MAX_STEER: f64 = 350.0;
+/// Full motor power (`MOTOR_TOP`).
+pub const MAX_POWER: f64 = 10000.0;
+
+/// Front wheel angle (degrees) per steer unit.
+const WHEEL_ANGLE_PER_STEER: f64 = 0.07;
+/// Servo speed, degrees per second of wheel angle.
+const SERVO_RATE: f64 = 400.0;
+const WHEELBASE: f64 = 140.0;
+/// Collision radius around the car center.
+pub const RADIUS: f64 = 80.0;
+/// Distance from the car center to the front sensor bar.
+pub const FRONT_OFFSET: f64 = 90.0;
+/// Power below which the motor does not overcome static friction.
+const DEADBAND_POWER: f64 = 1500.0;
+/// Speed reached at full power on flat floor, in mm/s.
+const TOP_SPEED: f64 = 4000.0;
+/// Speed lost per unit of `sin(pitch)` when climbing.
+const CLIMB_LOSS: f64 = 5000.0;
+/// Time constant of the speed response.
+const SPEED_TAU: f64 = 0.15;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Car {
+    pub position: Vec2,
+    pub heading: f64,
+    /// mm/s, negative when reversing
+    pub speed: f64,
+    pub wheel_angle: f64,
+    /// Heading change rate, degrees per second
+    pub yaw_rate: f64,
+    /// Forward acceleration, mm/s²
+    pub acceleration: f64,
+    pub pitch: f64,
+    pub roll: f64,
+    pub in_contact: bool,
+}
+
+impl Car {
+    pub fn new(pose: Pose) -> Self {
+        Self {
+            position: pose.position,
+            heading: pose.heading,
+            speed: 0.0,
+            wheel_angle: 0.0,
+            yaw_rate: 0.0,
+            acceleration: 0.0,
+            pitch: 0.0,
+            roll: 0.0,
+            in_contact: false,
+        }
+    }
+
+    pub fn front(&self) -> Vec2 {
+        self.position + Vec2::from_heading(self.heading).scale(FRONT_OFFSET)
+    }
+
+    fn target_speed(&self, power: i16) -> f64 {
+        let power = (power as f64).clamp(-MAX_POWER, MAX_POWER);
+        let effective = if power.abs() < DEADBAND_POWER {
+            0.0
+        } else {
+            power.signum() * (power.abs() - DEADBAND_POWER) / (MAX_POWER - DEADBAND_POWER)
+        };
+        let speed = effective * TOP_SPEED;
+        if speed == 0.0 {
+            0.0
+        } else {
+            speed - CLIMB_LOSS * self.pitch.to_radians().sin()
+        }
+    }
+
+    /// Advances the car by `dt` seconds; returns `true` if it hit a wall.
+    pub fn step(&mut self, track: &Track, power: i16, steer: i16, dt: f64) -> bool {
+        let steer = (steer as f64).clamp(-MAX_STEER, MAX_STEER);
+        let target_wheel = steer * WHEEL_ANGLE_PER_STEER;
+        let max_delta = SERVO_RATE * dt;
+        self.wheel_angle += (target_wheel - self.wheel_angle).clamp(-max_delta, max_delta);
+
+        let previous_speed = self.speed;
+        let target_speed = self.target_speed(power);
+        self.speed += (target_speed - self.speed) * (dt / SPEED_TAU).min(1.0);
+        self.acceleration = (self.speed - previous_speed) / dt;
+
+        self.yaw_rate = (self.speed / WHEELBASE * self.wheel_angle.to_radians().tan()).to_degrees();
+        let heading = wrap_degrees(self.heading + self.yaw_rate * dt);
+        let position = self.position + Vec2::from_heading(heading).scale(self.speed * dt);
+
+        self.heading = heading;
+        let hit = track.walls.iter().any(|w| {
+            w.distance_to(position) < RADIUS
+                && w.distance_to(position) < w.distance_to(self.position)
+        });
+        let collision = if hit {
+            self.speed = 0.0;
+            !self.in_contact
+        } else {
+            self.position = position;
+            false
+        };
+        self.in_contact = hit;
+
+        match track.ramp_at(self.position) {
+            Some(ramp) => {
+                let relative = (self.heading - ramp.direction).to_radians();
+                self.pitch = ramp.slope * relative.cos();
+                self.roll = ramp.slope * relative.sin();
+            }
+            None => {
+                self.pitch = 0.0;
+                self.roll = 0.0;
+            }
+        }
+
+        collision
+    }
+}
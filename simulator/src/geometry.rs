@@ -0,0 +1,164 @@
+//! Minimal 2D geometry, in millimetres.
+//!
+//! Headings are in degrees, clockwise from the positive y axis, so that they
+//! grow when turning right like the IMU yaw and the steering angle do.
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Vec2 {
+    pub x: f64,
+    pub y: f64,
+}
+
+impl Vec2 {
+    pub const fn new(x: f64, y: f64) -> Self {
+        Self { x, y }
+    }
+
+    pub fn from_heading(heading: f64) -> Self {
+        let radians = heading.to_radians();
+        Self::new(radians.sin(), radians.cos())
+    }
+
+    pub fn scale(self, factor: f64) -> Self {
+        Self::new(self.x * factor, self.y * factor)
+    }
+
+    pub fn dot(self, other: Self) -> f64 {
+        self.x * other.x + self.y * other.y
+    }
+
+    pub fn cross(self, other: Self) -> f64 {
+        self.x * other.y - self.y * other.x
+    }
+
+    pub fn length(self) -> f64 {
+        self.dot(self).sqrt()
+    }
+}
+
+impl core::ops::Add for Vec2 {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        Self::new(self.x + other.x, self.y + other.y)
+    }
+}
+
+impl core::ops::Sub for Vec2 {
+    type Output = Self;
+
+    fn sub(self, other: Self) -> Self {
+        Self::new(self.x - other.x, self.y - other.y)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Segment {
+    pub a: Vec2,
+    pub b: Vec2,
+}
+
+impl Segment {
+    pub const fn new(ax: f64, ay: f64, bx: f64, by: f64) -> Self {
+        Self {
+            a: Vec2::new(ax, ay),
+            b: Vec2::new(bx, by),
+        }
+    }
+
+    /// Solves `origin + t * dir == a + u * (b - a)`, returning `(t, u)`.
+    fn intersect(&self, origin: Vec2, dir: Vec2) -> Option<(f64, f64)> {
+        let edge = self.b - self.a;
+        let denom = dir.cross(edge);
+        if denom.abs() < 1e-9 {
+            return None;
+        }
+        let delta = self.a - origin;
+        let t = delta.cross(edge) / denom;
+        let u = delta.cross(dir) / denom;
+        if (0.0..=1.0).contains(&u) {
+            Some((t, u))
+        } else {
+            None
+        }
+    }
+
+    /// Distance along the unit vector `dir` at which a ray hits the segment.
+    pub fn ray_hit(&self, origin: Vec2, dir: Vec2) -> Option<f64> {
+        self.intersect(origin, dir)
+            .map(|(t, _)| t)
+            .filter(|t| *t >= 0.0)
+    }
+
+    /// Tells if moving from `from` to `to` crosses the segment.
+    pub fn crossed_by(&self, from: Vec2, to: Vec2) -> bool {
+        self.intersect(from, to - from)
+            .map(|(t, _)| (0.0..=1.0).contains(&t))
+            .unwrap_or(false)
+    }
+
+    /// Which side of the segment `p` lies on (positive on the right of `a -> b`).
+    pub fn side(&self, p: Vec2) -> f64 {
+        (p - self.a).cross(self.b - self.a)
+    }
+
+    pub fn distance_to(&self, p: Vec2) -> f64 {
+        let edge = self.b - self.a;
+        let length2 = edge.dot(edge);
+        let u = if length2 > 0.0 {
+            ((p - self.a).dot(edge) / length2).clamp(0.0, 1.0)
+        } else {
+            0.0
+        };
+        (p - (self.a + edge.scale(u))).length()
+    }
+}
+
+/// A convex polygon, used for ramps and floor markings.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Area {
+    pub points: Vec<Vec2>,
+}
+
+impl Area {
+    pub fn rectangle(x1: f64, y1: f64, x2: f64, y2: f64) -> Self {
+        let (x1, x2) = (x1.min(x2), x1.max(x2));
+        let (y1, y2) = (y1.min(y2), y1.max(y2));
+        Self {
+            points: vec![
+                Vec2::new(x1, y1),
+                Vec2::new(x1, y2),
+                Vec2::new(x2, y2),
+                Vec2::new(x2, y1),
+            ],
+        }
+    }
+
+    pub fn contains(&self, p: Vec2) -> bool {
+        let count = self.points.len();
+        let mut sign = 0.0;
+        for i in 0..count {
+            let a = self.points[i];
+            let b = self.points[(i + 1) % count];
+            let side = (p - a).cross(b - a);
+            if side != 0.0 {
+                if sign == 0.0 {
+                    sign = side.signum();
+                } else if side.signum() != sign {
+                    return false;
+                }
+            }
+        }
+        true
+    }
+}
+
+/// Wraps an angle in degrees into `-180..180`.
+pub fn wrap_degrees(angle: f64) -> f64 {
+    let wrapped = (angle + 180.0).rem_euclid(360.0) - 180.0;
+    if wrapped < -180.0 {
+        wrapped + 360.0
+    } else {
+        wrapped
+    }
+}
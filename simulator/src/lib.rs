@@ -0,0 +1,13 @@
+//! Closed loop simulator for the race logic.
+//!
+//! A 2D track model produces laser, IMU and RGB readings from the pose of a
+//! kinematic car, `countryman_core::race::RaceController` turns them into
+//! motor commands, and those move the car. This makes it possible to try
+//! configurations and logic changes on the host before running them on the
+//! real car.
+
+pub mod car;
+pub mod geometry;
+pub mod sensors;
+pub mod sim;
+pub mod track;
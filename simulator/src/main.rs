@@ -0,0 +1,92 @@
+use std::process::exit;
+
+use countryman_core::configuration::{RaceConfig, RaceConfigEntry, RACE_CONFIG_SCHEMA};
+use countryman_sim::sim::Simulation;
+use countryman_sim::track::Track;
+use embassy_time::Duration;
+
+struct StderrLogger;
+
+impl log::Log for StderrLogger {
+    fn enabled(&self, _metadata: &log::Metadata) -> bool {
+        true
+    }
+
+    fn log(&self, record: &log::Record) {
+        eprintln!("{}", record.args());
+    }
+
+    fn flush(&self) {}
+}
+
+static LOGGER: StderrLogger = StderrLogger;
+
+fn usage() -> ! {
+    eprintln!(
+        "usage: countryman-sim [--track {}] [--seconds N] [--seed N] [--trace] [KEY=VALUE...]",
+        Track::names().join("|")
+    );
+    eprintln!("config keys:");
+    for schema in RACE_CONFIG_SCHEMA.iter() {
+        eprintln!(
+            "  {:<24} {} {} ({}..{})",
+            schema.key, schema.default, schema.unit, schema.min, schema.max
+        );
+    }
+    exit(1)
+}
+
+fn parse<T: std::str::FromStr>(value: Option<String>) -> T {
+    value
+        .and_then(|v| v.parse().ok())
+        .unwrap_or_else(|| usage())
+}
+
+fn main() {
+    let mut track = Track::oval();
+    let mut seconds: u64 = 30;
+    let mut seed: u64 = 1;
+    let mut trace = false;
+    let mut config = RaceConfig::init();
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--track" => {
+                track = args
+                    .next()
+                    .and_then(|name| Track::by_name(&name))
+                    .unwrap_or_else(|| usage())
+            }
+            "--seconds" => seconds = parse(args.next()),
+            "--seed" => seed = parse(args.next()),
+            "--trace" => trace = true,
+            _ => match arg.split_once('=') {
+                Some((key, value)) => {
+                    let entry = RaceConfigEntry::from_key(key).unwrap_or_else(|| usage());
+                    let value: i16 = parse(Some(value.to_string()));
+                    config.set(entry, value.clamp(entry.min(), entry.max()));
+                }
+                None => usage(),
+            },
+        }
+    }
+
+    let violations = config.validate();
+    if !violations.is_empty() {
+        for violation in violations.iter() {
+            eprintln!("invalid config: {:?}", violation);
+        }
+        exit(1);
+    }
+
+    if trace {
+        log::set_logger(&LOGGER).unwrap();
+        log::set_max_level(log::LevelFilter::Info);
+    }
+
+    let report = Simulation::new(track, config, seed)
+        .with_trace(trace)
+        .run(Duration::from_secs(seconds));
+    println!("{}", report);
+}
@@ -0,0 +1,143 @@
+//! Synthetic sensor readings, produced in the same form the firmware tasks
+//! hand to the race logic.
+
+use countryman_core::geometry::{LaserBeam, LaserTier};
+use countryman_core::lasers::{RawLaserReadings, RAW_LASERS_COUNT};
+use countryman_core::vision::LASER_OVERFLOW;
+use embassy_time::{Duration, Instant};
+
+use crate::car::Car;
+use crate::geometry::{wrap_degrees, Vec2};
+use crate::track::Track;
+
+/// Height of the lower beam tier above the floor.
+const LOWER_HEIGHT: f64 = 10.0;
+/// Height of the upper beam tier above the floor.
+const UPPER_HEIGHT: f64 = 40.0;
+/// GP2Y0E02B readings do not go below this.
+const LASER_MIN: f64 = 40.0;
+const LASER_NOISE: f64 = 8.0;
+/// Accelerometer noise while the motor is running, in mg.
+const VIBRATION_NOISE: f64 = 1000.0;
+const ACCEL_NOISE: f64 = 20.0;
+const GRAVITY: f64 = 9810.0;
+
+/// Xorshift generator, good enough for sensor noise and reproducible by seed.
+#[derive(Clone, Copy, Debug)]
+pub struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        Self { state: seed.max(1) }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        let mut x = self.state;
+        x ^= x << 13;
+        x ^= x >> 7;
+        x ^= x << 17;
+        self.state = x;
+        x
+    }
+
+    /// Uniform in `-1.0..1.0`.
+    pub fn noise(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 52) as f64 - 1.0
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Beam {
+    /// Relative to the car heading, positive to the right
+    pub angle: f64,
+    pub height: f64,
+    /// From the middle of the front bumper, positive to the right
+    pub lateral: f64,
+    /// From the middle of the front bumper, positive ahead
+    pub forward: f64,
+}
+
+/// Where each physical laser channel looks, derived from the same geometry
+/// table `Vision` uses so that the two cannot drift apart. Channels without
+/// a beam always read as overflow.
+pub fn beam_layout(geometry: &[LaserBeam]) -> [Option<Beam>; RAW_LASERS_COUNT] {
+    let mut beams = [None; RAW_LASERS_COUNT];
+    for beam in geometry {
+        beams[beam.channel as usize] = Some(Beam {
+            angle: beam.angle.value() as f64,
+            height: match beam.tier {
+                LaserTier::Upper => UPPER_HEIGHT,
+                LaserTier::Lower => LOWER_HEIGHT,
+            },
+            lateral: beam.lateral as f64,
+            forward: beam.forward as f64,
+        });
+    }
+    beams
+}
+
+pub fn read_lasers(
+    track: &Track,
+    car: &Car,
+    beams: &[Option<Beam>; RAW_LASERS_COUNT],
+    rng: &mut Rng,
+    now: Instant,
+    dt: Duration,
+) -> RawLaserReadings {
+    let front = car.front();
+    let ahead = Vec2::from_heading(car.heading);
+    let right = Vec2::from_heading(car.heading + 90.0);
+    let mut values = [LASER_OVERFLOW; RAW_LASERS_COUNT];
+    for (value, beam) in values.iter_mut().zip(beams.iter()) {
+        let Some(beam) = beam else {
+            continue;
+        };
+        let origin = front + ahead.scale(beam.forward) + right.scale(beam.lateral);
+        let heading = wrap_degrees(car.heading + beam.angle);
+        if let Some(distance) = track.cast(origin, heading, beam.height) {
+            let distance = (distance + rng.noise() * LASER_NOISE).max(LASER_MIN);
+            *value = distance.min(LASER_OVERFLOW as f64) as u16;
+        }
+    }
+    RawLaserReadings::new(values, now, dt)
+}
+
+fn centi(degrees: f64) -> i16 {
+    (wrap_degrees(degrees) * 100.0).round() as i16
+}
+
+fn milli_g(acceleration: f64) -> i16 {
+    (acceleration / GRAVITY * 1000.0).clamp(i16::MIN as f64, i16::MAX as f64) as i16
+}
+
+/// Encodes the car state as a BNO080 UART-RVC frame.
+pub fn rvc_frame(car: &Car, counter: u8, rng: &mut Rng) -> [u8; 19] {
+    let vibration = if car.speed.abs() > 1.0 {
+        VIBRATION_NOISE
+    } else {
+        ACCEL_NOISE
+    };
+    let centripetal = car.speed * car.yaw_rate.to_radians();
+    let forward = car.acceleration - GRAVITY * car.pitch.to_radians().sin();
+    let side = centripetal + GRAVITY * car.roll.to_radians().sin();
+    let values = [
+        centi(car.heading),
+        centi(car.pitch),
+        centi(car.roll),
+        milli_g(side).saturating_add((rng.noise() * vibration) as i16),
+        milli_g(forward).saturating_add((rng.noise() * vibration) as i16),
+        1000,
+    ];
+
+    let mut frame = [0u8; 19];
+    frame[0] = 0xaa;
+    frame[1] = 0xaa;
+    frame[2] = counter;
+    for (i, value) in values.iter().enumerate() {
+        frame[3 + i * 2..5 + i * 2].copy_from_slice(&value.to_le_bytes());
+    }
+    frame[18] = frame[2..18].iter().fold(0u8, |sum, b| sum.wrapping_add(*b));
+    frame
+}
@@ -0,0 +1,274 @@
+//! The closed loop: sensors are sampled from the car pose at their real
+//! rates, every new sample steps the `RaceController` (like the firmware race
+//! loop wakes up on every signal) and its action drives the car.
+
+use core::fmt;
+
+use countryman_core::configuration::RaceConfig;
+use countryman_core::geometry::LASER_GEOMETRY;
+use countryman_core::heading::HeadingTracker;
+use countryman_core::imu::{Bno080Decoder, ImuData, ImuLinkStats, ImuStillnessDetector};
+use countryman_core::lasers::{RawLaserReadings, RAW_LASERS_COUNT};
+use countryman_core::race::{Angle, RaceAction, RaceColor, RaceController, RaceInputs};
+use countryman_core::rgb::{RgbEvent, RgbTracker};
+use countryman_core::trace::TraceEvent;
+use embassy_time::{Duration, Instant};
+
+use crate::car::Car;
+use crate::sensors::{self, Beam, Rng};
+use crate::track::Track;
+
+const PHYSICS_STEP_MS: u64 = 1;
+/// One GP2Y0E02B scan over all the channels.
+const LASERS_PERIOD_MS: u64 = 16;
+/// BNO080 UART-RVC runs at 100Hz.
+const IMU_PERIOD_MS: u64 = 10;
+/// TCS3472 integration time.
+const RGB_PERIOD_MS: u64 = 3;
+/// Brightness reported along with the RGB counts.
+const RGB_LIGHT: u16 = 1000;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Sensor {
+    Lasers,
+    Imu,
+    Rgb,
+}
+
+impl Sensor {
+    const ALL: [Sensor; 3] = [Sensor::Lasers, Sensor::Imu, Sensor::Rgb];
+
+    fn period_ms(self) -> u64 {
+        match self {
+            Sensor::Lasers => LASERS_PERIOD_MS,
+            Sensor::Imu => IMU_PERIOD_MS,
+            Sensor::Rgb => RGB_PERIOD_MS,
+        }
+    }
+}
+
+/// Simulated time starts here so that `Instant` arithmetic never underflows.
+fn at(ms: u64) -> Instant {
+    Instant::from_millis(1000 + ms)
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Report {
+    pub track: &'static str,
+    pub elapsed: Duration,
+    pub laps: Vec<Duration>,
+    pub collisions: u32,
+    pub back_panics: u32,
+    pub route_targets: u32,
+    pub tilt_stops: u32,
+    /// Driven distance, in mm
+    pub distance: f64,
+}
+
+impl Report {
+    pub fn best_lap(&self) -> Option<Duration> {
+        self.laps.iter().min().copied()
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "track:         {}", self.track)?;
+        writeln!(f, "elapsed:       {}ms", self.elapsed.as_millis())?;
+        writeln!(f, "distance:      {:.0}mm", self.distance)?;
+        write!(f, "laps:          {}", self.laps.len())?;
+        for lap in self.laps.iter() {
+            write!(f, " {}ms", lap.as_millis())?;
+        }
+        writeln!(f)?;
+        match self.best_lap() {
+            Some(best) => writeln!(f, "best lap:      {}ms", best.as_millis())?,
+            None => writeln!(f, "best lap:      -")?,
+        }
+        writeln!(f, "collisions:    {}", self.collisions)?;
+        writeln!(f, "back panics:   {}", self.back_panics)?;
+        writeln!(f, "route targets: {}", self.route_targets)?;
+        write!(f, "tilt stops:    {}", self.tilt_stops)
+    }
+}
+
+pub struct Simulation {
+    track: Track,
+    config: RaceConfig,
+    car: Car,
+    beams: [Option<Beam>; RAW_LASERS_COUNT],
+    rng: Rng,
+    imu_decoder: Bno080Decoder,
+    imu_stillness: ImuStillnessDetector,
+    imu_counter: u8,
+    rgb_tracker: RgbTracker,
+    trace: bool,
+}
+
+impl Simulation {
+    pub fn new(track: Track, config: RaceConfig, seed: u64) -> Self {
+        let car = Car::new(track.start);
+        Self {
+            track,
+            config,
+            car,
+            beams: sensors::beam_layout(LASER_GEOMETRY),
+            rng: Rng::new(seed),
+            imu_decoder: Bno080Decoder::init(),
+            imu_stillness: ImuStillnessDetector::new(config.stillness()),
+            imu_counter: 0,
+            rgb_tracker: RgbTracker::new(),
+            trace: false,
+        }
+    }
+
+    /// Logs every trace event, in the same format the firmware trace dump uses.
+    pub fn with_trace(mut self, trace: bool) -> Self {
+        self.trace = trace;
+        self
+    }
+
+    pub fn car(&self) -> &Car {
+        &self.car
+    }
+
+    fn read_lasers(&mut self, now: Instant, dt: Duration) -> RawLaserReadings {
+        sensors::read_lasers(&self.track, &self.car, &self.beams, &mut self.rng, now, dt)
+    }
+
+    /// Sends one RVC frame through the decoder, as the IMU task would.
+    fn read_imu(&mut self, imu: &mut ImuData, now: Instant) {
+        let frame = sensors::rvc_frame(&self.car, self.imu_counter, &mut self.rng);
+        self.imu_counter = self.imu_counter.wrapping_add(1);
+        for byte in frame {
+            if let Some(raw) = self.imu_decoder.update(byte) {
+                let dt = now - imu.timestamp;
+                self.imu_stillness.process_data(&raw, now, dt);
+                imu.update(&raw, now, dt, self.imu_stillness.last_stillness);
+                imu.link = self.imu_decoder.stats();
+            }
+        }
+    }
+
+    fn read_rgb(&mut self, dt: Duration) -> RgbEvent {
+        let (r, g, b) = self.track.floor_rgb(self.car.position);
+        self.rgb_tracker.update(r, g, b, RGB_LIGHT, dt)
+    }
+
+    pub fn run(&mut self, duration: Duration) -> Report {
+        let mut report = Report {
+            track: self.track.name,
+            ..Report::default()
+        };
+
+        let start = at(0);
+        let mut imu = ImuData {
+            yaw: 0,
+            pitch: 0,
+            roll: 0,
+            side: 0,
+            forward: 0,
+            vertical: 0,
+            timestamp: start,
+            dt: Duration::from_millis(IMU_PERIOD_MS),
+            last_stillness: None,
+            link: ImuLinkStats::new(),
+            extras: None,
+        };
+        self.read_imu(&mut imu, start);
+        let mut inputs = RaceInputs {
+            lasers: self.read_lasers(start, Duration::from_millis(LASERS_PERIOD_MS)),
+            imu,
+            rgb: self.read_rgb(Duration::from_millis(RGB_PERIOD_MS)),
+        };
+
+        let mut controller = RaceController::new(
+            &self.config,
+            HeadingTracker::new(inputs.imu.yaw, start),
+            false,
+            start,
+        );
+        let mut action = RaceAction {
+            power: 0,
+            steer: Angle::ZERO,
+        };
+        let mut last_color = None;
+        let mut last_has_target = false;
+        let mut next_checkpoint = 0;
+        let mut lap_start: Option<u64> = None;
+        let mut steps = 0;
+
+        let total_ms = duration.as_millis();
+        let mut ms = 0;
+        while ms < total_ms {
+            ms += PHYSICS_STEP_MS;
+            let now = at(ms);
+
+            let before = self.car.position;
+            let steer = self.config.servo_steer(action.steer);
+            if self.car.step(
+                &self.track,
+                action.power,
+                steer,
+                PHYSICS_STEP_MS as f64 / 1000.0,
+            ) {
+                report.collisions += 1;
+            }
+            report.distance += (self.car.position - before).length();
+
+            if self
+                .track
+                .checkpoints
+                .get(next_checkpoint)
+                .map(|c| c.crossed_by(before, self.car.position))
+                .unwrap_or(false)
+            {
+                if next_checkpoint == 0 {
+                    if let Some(lap_start) = lap_start {
+                        report.laps.push(Duration::from_millis(ms - lap_start));
+                    }
+                    lap_start = Some(ms);
+                }
+                next_checkpoint = (next_checkpoint + 1) % self.track.checkpoints.len();
+            }
+
+            let mut updated: Option<TraceEvent> = None;
+            for sensor in Sensor::ALL {
+                if ms % sensor.period_ms() != 0 {
+                    continue;
+                }
+                let dt = Duration::from_millis(sensor.period_ms());
+                match sensor {
+                    Sensor::Lasers => inputs.lasers = self.read_lasers(now, dt),
+                    Sensor::Imu => self.read_imu(&mut inputs.imu, now),
+                    Sensor::Rgb => inputs.rgb = self.read_rgb(dt),
+                }
+
+                let output = controller.step(&inputs, now);
+                action = output.action;
+                updated = Some(output.trace);
+
+                if last_color != Some(output.color) {
+                    match output.color {
+                        RaceColor::Red => report.back_panics += 1,
+                        RaceColor::Black => report.tilt_stops += 1,
+                        _ => {}
+                    }
+                    last_color = Some(output.color);
+                }
+                if output.trace.has_target && !last_has_target {
+                    report.route_targets += 1;
+                }
+                last_has_target = output.trace.has_target;
+            }
+
+            if let (true, Some(trace)) = (self.trace, updated) {
+                trace.print(steps, Duration::from_millis(ms));
+                steps += 1;
+            }
+        }
+
+        report.elapsed = Duration::from_millis(ms);
+        report
+    }
+}
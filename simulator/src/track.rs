@@ -0,0 +1,184 @@
+//! Track description: walls, ramps, colored floor markings and lap checkpoints.
+
+use crate::geometry::{Area, Segment, Vec2};
+
+/// A sloped part of the track.
+///
+/// `low_edge` is where the ramp meets the floor, `direction` is the uphill
+/// heading. Beams hit the low edge only when looking uphill, and they hit the
+/// sloped surface further away the higher they are mounted.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Ramp {
+    pub area: Area,
+    pub low_edge: Segment,
+    pub direction: f64,
+    pub slope: f64,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FloorColor {
+    Red,
+    Green,
+}
+
+impl FloorColor {
+    /// Raw TCS3472 counts as read over this marking.
+    pub fn rgb(self) -> (u16, u16, u16) {
+        match self {
+            FloorColor::Red => (900, 150, 150),
+            FloorColor::Green => (300, 700, 250),
+        }
+    }
+}
+
+/// Raw TCS3472 counts as read over the plain track floor.
+pub const FLOOR_RGB: (u16, u16, u16) = (300, 300, 300);
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Marking {
+    pub area: Area,
+    pub color: FloorColor,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Pose {
+    pub position: Vec2,
+    pub heading: f64,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Track {
+    pub name: &'static str,
+    pub walls: Vec<Segment>,
+    pub ramps: Vec<Ramp>,
+    pub markings: Vec<Marking>,
+    /// Lines that must be crossed in order; the first one is the finish line.
+    pub checkpoints: Vec<Segment>,
+    pub start: Pose,
+}
+
+fn polyline(points: &[(f64, f64)]) -> Vec<Segment> {
+    points
+        .iter()
+        .zip(points.iter().cycle().skip(1))
+        .map(|(a, b)| Segment::new(a.0, a.1, b.0, b.1))
+        .collect()
+}
+
+impl Track {
+    pub fn names() -> &'static [&'static str] {
+        &["oval", "ramps"]
+    }
+
+    pub fn by_name(name: &str) -> Option<Self> {
+        match name {
+            "oval" => Some(Self::oval()),
+            "ramps" => Some(Self::ramps()),
+            _ => None,
+        }
+    }
+
+    /// A flat 6 x 3 m stadium with an 800 mm wide lane, driven counterclockwise.
+    pub fn oval() -> Self {
+        let mut walls = polyline(&[
+            (-2400.0, -1500.0),
+            (-3000.0, -900.0),
+            (-3000.0, 900.0),
+            (-2400.0, 1500.0),
+            (2400.0, 1500.0),
+            (3000.0, 900.0),
+            (3000.0, -900.0),
+            (2400.0, -1500.0),
+        ]);
+        walls.extend(polyline(&[
+            (-2000.0, -700.0),
+            (-2200.0, -500.0),
+            (-2200.0, 500.0),
+            (-2000.0, 700.0),
+            (2000.0, 700.0),
+            (2200.0, 500.0),
+            (2200.0, -500.0),
+            (2000.0, -700.0),
+        ]));
+        Self {
+            name: "oval",
+            walls,
+            ramps: Vec::new(),
+            markings: Vec::new(),
+            checkpoints: vec![
+                Segment::new(0.0, -1500.0, 0.0, -700.0),
+                Segment::new(2200.0, 0.0, 3000.0, 0.0),
+                Segment::new(0.0, 700.0, 0.0, 1500.0),
+                Segment::new(-3000.0, 0.0, -2200.0, 0.0),
+            ],
+            start: Pose {
+                position: Vec2::new(-600.0, -1100.0),
+                heading: 90.0,
+            },
+        }
+    }
+
+    /// The oval with a ramp and a plateau on the far straight, and a red/green
+    /// marking pair after the start that reads as a good cross when driven in
+    /// the right direction.
+    pub fn ramps() -> Self {
+        let mut track = Self::oval();
+        track.name = "ramps";
+        track.ramps = vec![
+            Ramp {
+                area: Area::rectangle(600.0, 700.0, 1100.0, 1500.0),
+                low_edge: Segment::new(1100.0, 700.0, 1100.0, 1500.0),
+                direction: -90.0,
+                slope: 15.0,
+            },
+            Ramp {
+                area: Area::rectangle(-600.0, 700.0, -100.0, 1500.0),
+                low_edge: Segment::new(-600.0, 700.0, -600.0, 1500.0),
+                direction: 90.0,
+                slope: 15.0,
+            },
+        ];
+        track.markings = vec![
+            Marking {
+                area: Area::rectangle(-300.0, -1500.0, -200.0, -700.0),
+                color: FloorColor::Red,
+            },
+            Marking {
+                area: Area::rectangle(-150.0, -1500.0, -50.0, -700.0),
+                color: FloorColor::Green,
+            },
+        ];
+        track
+    }
+
+    pub fn ramp_at(&self, p: Vec2) -> Option<&Ramp> {
+        self.ramps.iter().find(|r| r.area.contains(p))
+    }
+
+    pub fn floor_rgb(&self, p: Vec2) -> (u16, u16, u16) {
+        self.markings
+            .iter()
+            .find(|m| m.area.contains(p))
+            .map(|m| m.color.rgb())
+            .unwrap_or(FLOOR_RGB)
+    }
+
+    /// Distance seen by a beam mounted `height` mm above the floor.
+    pub fn cast(&self, origin: Vec2, heading: f64, height: f64) -> Option<f64> {
+        let dir = Vec2::from_heading(heading);
+        let walls = self.walls.iter().filter_map(|w| w.ray_hit(origin, dir));
+        let ramps = self.ramps.iter().filter_map(|r| {
+            if dir.dot(Vec2::from_heading(r.direction)) > 0.0 {
+                r.low_edge
+                    .ray_hit(origin, dir)
+                    .map(|d| d + height / r.slope.to_radians().tan())
+            } else {
+                None
+            }
+        });
+        walls.chain(ramps).fold(None, |nearest, d| match nearest {
+            Some(n) if n <= d => Some(n),
+            _ => Some(d),
+        })
+    }
+}
@@ -0,0 +1,29 @@
+use countryman_core::configuration::RaceConfig;
+use countryman_sim::sim::Simulation;
+use countryman_sim::track::Track;
+use embassy_time::Duration;
+
+#[test]
+fn default_config_laps_the_oval() {
+    let report = Simulation::new(Track::oval(), RaceConfig::init(), 1).run(Duration::from_secs(30));
+    assert!(report.laps.len() >= 2, "{}", report);
+    assert_eq!(report.collisions, 0, "{}", report);
+    assert_eq!(report.tilt_stops, 0, "{}", report);
+}
+
+#[test]
+fn default_config_laps_the_ramps() {
+    let report =
+        Simulation::new(Track::ramps(), RaceConfig::init(), 1).run(Duration::from_secs(30));
+    assert!(report.laps.len() >= 2, "{}", report);
+    assert_eq!(report.collisions, 0, "{}", report);
+    assert_eq!(report.route_targets, 0, "{}", report);
+}
+
+#[test]
+fn same_seed_same_run() {
+    let run = |seed| {
+        Simulation::new(Track::ramps(), RaceConfig::init(), seed).run(Duration::from_secs(5))
+    };
+    assert_eq!(run(7), run(7));
+}